@@ -4,17 +4,22 @@ use std::error::Error;
 use std::sync::{mpsc, Arc, Mutex};
 use std::{thread, time};
 
-use crate::command::Command;
-use crate::commander::Commander;
-use crate::executor::Executor;
+use crate::command::{Command, Transaction, TransactionKind};
+use crate::commander::{Commander, Logged, Responder};
+use crate::executor::{Executor, HistoryLimits};
 use crate::log::{DummyLogger, Logger};
+use crate::outcome::Outcome;
 use crate::render::{DummyRenderer, Renderer};
+use crate::selector::Selection;
 use crate::shape::Shapes;
 
 pub struct App {
     // store shapes
     shapes: Arc<Mutex<Shapes>>,
 
+    // names of the selected shapes
+    selection: Selection,
+
     // draw shapes in its own way
     renderer: Arc<Mutex<dyn Renderer>>,
 
@@ -63,15 +68,28 @@ impl App {
     pub fn set_async_render(&mut self, async_render: bool) {
         self.async_render = async_render;
     }
-    pub fn execute(&mut self, cmd: Box<dyn Command>) -> Result<(), Box<dyn Error + '_>> {
-        self.executor
-            .execute(cmd, self.shapes.lock()?.borrow_mut())?;
+    pub fn set_history_limits(&mut self, limits: HistoryLimits) {
+        self.executor.limits = limits;
+    }
+    pub fn selection(&self) -> &Selection {
+        &self.selection
+    }
+    pub fn execute(&mut self, mut cmd: Box<dyn Command>) -> Result<Outcome, Box<dyn Error>> {
+        let mut shapes = self.shapes.lock().map_err(|error| error.to_string())?;
+        cmd.select(&mut self.selection, &shapes)?;
+        let result = self.executor.execute(cmd, shapes.borrow_mut());
+        // deleted shapes drop out of the selection
+        self.selection.retain(|name| shapes.contains_key(name));
+        result
+    }
+    fn share_selection(&self) -> Result<(), Box<dyn Error + '_>> {
+        self.renderer.lock()?.set_selection(&self.selection);
         Ok(())
     }
     pub fn render_shapes(&mut self) -> Result<(), Box<dyn Error + '_>> {
-        self.renderer
-            .lock()?
-            .render_shapes(self.shapes.lock()?.borrow())?;
+        let mut renderer = self.renderer.lock()?;
+        renderer.set_selection(&self.selection);
+        renderer.render_shapes(self.shapes.lock()?.borrow())?;
 
         Ok(())
     }
@@ -87,43 +105,71 @@ impl App {
                 thread::sleep(time::Duration::from_millis(1000 / fps));
                 Ok(())
             };
-            while let Ok(_) = rx.recv() {
-                while let Ok(_) = rx.try_recv() {}
-                match render() {
-                    Err(error) => log::error!("{}", error),
-                    Ok(_) => (),
+            while rx.recv().is_ok() {
+                while rx.try_recv().is_ok() {}
+                if let Err(error) = render() {
+                    log::error!("{}", error)
                 };
             }
         });
 
-        return (join_handle, tx);
+        (join_handle, tx)
     }
+    /// Run the commands of `commander`, logging how each of them went.
     pub fn run<CommanderType: Commander>(&mut self, commander: CommanderType) {
-        if self.async_render {
-            let (join_handle, render_signal) = self.start_render_thread();
-            for cmd in commander {
-                self.logger.log(&cmd.to_string());
-                match self.execute(cmd) {
-                    Err(error) => log::error!("{}", error),
-                    Ok(()) => (),
+        self.serve(Logged(commander.into_iter()));
+    }
+
+    /// Run the commands of `commander`, answering it after each of them.
+    /// A transaction still open when the commands run out is rolled back,
+    /// as the session never finished it.
+    pub fn serve<CommanderType>(&mut self, mut commander: CommanderType)
+    where
+        CommanderType: Iterator<Item = Box<dyn Command>> + Responder,
+    {
+        let render_thread = match self.async_render {
+            true => Some(self.start_render_thread()),
+            false => None,
+        };
+        let mut closing = false;
+        while !closing {
+            let cmd = match commander.next() {
+                Some(cmd) => cmd,
+                None if self.executor.transaction.is_some() => {
+                    closing = true;
+                    Box::new(Transaction::new(TransactionKind::Rollback))
                 }
-                render_signal.send(()).unwrap();
+                None => break,
+            };
+            self.logger.log(&cmd.to_string());
+            match self.execute(cmd) {
+                Ok(mut outcome) => {
+                    if closing {
+                        let warning = "The session ended inside a transaction.";
+                        outcome.warnings.push(warning.to_string());
+                    }
+                    commander.respond(Ok(&outcome))
+                }
+                Err(error) => commander.respond(Err(error.as_ref())),
             }
-            drop(render_signal);
-            join_handle.join().unwrap();
-        } else {
-            for cmd in commander {
-                self.logger.log(&cmd.to_string());
-                match self.execute(cmd) {
-                    Err(error) => log::error!("{}", error),
-                    Ok(()) => (),
+            match &render_thread {
+                Some((_, render_signal)) => {
+                    if let Err(error) = self.share_selection() {
+                        log::error!("{}", error)
+                    }
+                    render_signal.send(()).unwrap();
                 }
-                match self.render_shapes() {
-                    Err(error) => log::error!("{}", error),
-                    Ok(()) => (),
+                None => {
+                    if let Err(error) = self.render_shapes() {
+                        log::error!("{}", error)
+                    }
                 }
             }
         }
+        if let Some((join_handle, render_signal)) = render_thread {
+            drop(render_signal);
+            join_handle.join().unwrap();
+        }
     }
 }
 
@@ -131,6 +177,7 @@ impl Default for App {
     fn default() -> Self {
         App {
             shapes: Arc::new(Mutex::new(Shapes::default())),
+            selection: Selection::new(),
             executor: Executor::default(),
             renderer: Arc::new(Mutex::new(DummyRenderer)),
             logger: Box::new(DummyLogger),
@@ -153,15 +200,222 @@ pub mod tests {
 
     #[test]
     fn test_mock_app() {
-        let commander = get_cmd_vec();
         let mut app = get_test_app();
-        app.run(commander);
+        app.run(get_cmd_vec());
         assert_eq!(
             app.executor.executed.len(),
             app.shapes.lock().unwrap().borrow().len()
         );
     }
 
+    #[test]
+    fn test_selection() {
+        use crate::commander::CliCommander;
+
+        let script = "point a 0 0\npoint b 5 5\npoint c 9 9\n\
+                      select-rect 4 4 10 10\nmove @sel 1 0\ndelete c\n";
+        let commander = CliCommander::new(script.as_bytes(), std::io::sink(), std::io::sink());
+        let mut app = get_test_app();
+        app.serve(commander);
+
+        let shapes = app.shapes.lock().unwrap();
+        assert_eq!(shapes["a"].center(), crate::shape::Point { x: 0, y: 0 });
+        assert_eq!(shapes["b"].center(), crate::shape::Point { x: 6, y: 5 });
+        assert_eq!(Vec::from_iter(app.selection().iter()), ["b"]);
+    }
+
+    #[test]
+    fn test_responses() {
+        use crate::commander::CliCommander;
+
+        let script = "point a 0 0\nmove a 1 1\nfind type=point\nundo\nundo\nbogus\n";
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let commander = CliCommander::new(script.as_bytes(), &mut stdout, &mut stderr);
+        get_test_app().serve(commander);
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            "> > > Found a.\n> Undid move a 1 1.\n> Undid a Point { x: 0, y: 0 }.\n> > "
+        );
+        assert_eq!(
+            String::from_utf8(stderr).unwrap(),
+            "\"bogus\" is not a valid command.\n"
+        );
+
+        let script = "point a 0 0\nlistAll --format json\nredo\n";
+        let mut stdout = Vec::new();
+        let mut commander = CliCommander::new(script.as_bytes(), &mut stdout, std::io::sink());
+        commander.set_json(true);
+        get_test_app().serve(commander);
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            [
+                r#"{"ok":true,"message":null,"data":null,"warnings":[]}"#,
+                r#"{"ok":true,"message":null,"data":[{"name":"a","type":"point","params":{"x":0,"y":0},"links":[],"tags":[],"meta":{}}],"warnings":[]}"#,
+                r#"{"ok":false,"code":"no_command_to_redo","error":"No command to redo."}"#,
+                ""
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_open_transaction() {
+        use crate::commander::CliCommander;
+
+        let script = "point a 0 0\nbegin\npoint b 1 1\nmove a 1 0\n";
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let mut app = get_test_app();
+        app.serve(CliCommander::new(
+            script.as_bytes(),
+            &mut stdout,
+            &mut stderr,
+        ));
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            "> > Began a transaction.\n> > > Rolled back 2 commands.\n"
+        );
+        assert_eq!(
+            String::from_utf8(stderr).unwrap(),
+            "warning: The session ended inside a transaction.\n"
+        );
+
+        let script = "listAll\nundo\nrollback\n";
+        let mut stdout = Vec::new();
+        app.serve(CliCommander::new(
+            script.as_bytes(),
+            &mut stdout,
+            std::io::sink(),
+        ));
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            "> a point x=0 y=0\n> Undid a Point { x: 0, y: 0 }.\n> > "
+        );
+    }
+
+    #[test]
+    fn test_journal_recovery() {
+        use crate::commander::{CliCommander, Responder};
+        use crate::journal::{tests::temp_path, Entry, Journal};
+        use std::io::Cursor;
+
+        let path = temp_path("test_journal_recovery");
+        let scene = temp_path("test_journal_recovery.scene");
+        let script = format!("point q 5 5\nsave {}\n", scene.display());
+        get_test_app().serve(CliCommander::new(
+            Cursor::new(script.into_bytes()),
+            std::io::sink(),
+            std::io::sink(),
+        ));
+
+        let script = format!(
+            "point p 0 0\npoint p 1 1\nsave {}\nmove p 2 0\nundo\nredo\n",
+            scene.display()
+        );
+        let mut commander = CliCommander::new(
+            Cursor::new(script.into_bytes()),
+            std::io::sink(),
+            std::io::sink(),
+        );
+        let mut journal = Journal::lock(&path).unwrap();
+        journal.start(false).unwrap();
+        commander.set_journal(journal);
+        commander.open(&scene);
+        let mut app = App::default();
+        // the terminal dies before the last command
+        for _ in 0..6 {
+            let cmd = commander.next().unwrap();
+            match app.execute(cmd) {
+                Ok(outcome) => commander.respond(Ok(&outcome)),
+                Err(error) => commander.respond(Err(error.as_ref())),
+            }
+        }
+        drop(commander);
+        // the scene changes before the session is recovered
+        std::fs::remove_file(&scene).unwrap();
+
+        let mut journal = Journal::lock(&path).unwrap();
+        let pending = journal.pending().unwrap();
+        assert!(matches!(&pending[0], Entry::Scene { path, .. } if *path == scene));
+        let lines = ["point p 0 0", "move p 2 0", "undo"].map(|line| Entry::Line(line.into()));
+        assert_eq!(pending[1..], lines);
+
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let script = "redo\nlist p\nlist q\nundo\nundo\nundo\nundo\n";
+        let mut commander = CliCommander::new(script.as_bytes(), &mut stdout, &mut stderr);
+        commander.replay(pending);
+        journal.start(true).unwrap();
+        commander.set_journal(journal);
+        App::default().serve(commander);
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            [
+                "> Redid move p 2 0.",
+                "> p point x=2 y=0",
+                "> q point x=5 y=5",
+                "> Undid move p 2 0.",
+                "> Undid p Point { x: 0, y: 0 }.",
+                &format!("> Undid load {}.", scene.display()),
+                "> > ",
+            ]
+            .join("\n")
+        );
+        assert_eq!(String::from_utf8(stderr).unwrap(), "No command to undo.\n");
+        // saving is not replayed
+        assert!(!scene.exists());
+        // a session that ends normally leaves nothing to recover
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_journal_rollback_recovery() {
+        use crate::commander::{CliCommander, Responder};
+        use crate::journal::{tests::temp_path, Entry, Journal};
+        use std::io::Cursor;
+
+        let path = temp_path("test_journal_rollback_recovery");
+        let script = "begin\npoint p 0 0\nmove nope 1 1\npoint r 0 0\n";
+        let mut commander = CliCommander::new(
+            Cursor::new(script.as_bytes()),
+            std::io::sink(),
+            std::io::sink(),
+        );
+        let mut journal = Journal::lock(&path).unwrap();
+        journal.start(false).unwrap();
+        commander.set_journal(journal);
+        let mut app = App::default();
+        // the terminal dies before the session ends
+        for _ in 0..4 {
+            let cmd = commander.next().unwrap();
+            match app.execute(cmd) {
+                Ok(outcome) => commander.respond(Ok(&outcome)),
+                Err(error) => commander.respond(Err(error.as_ref())),
+            }
+        }
+        drop(commander);
+
+        let mut journal = Journal::lock(&path).unwrap();
+        let pending = journal.pending().unwrap();
+        let lines = ["begin", "point p 0 0", "rollback", "point r 0 0"];
+        assert_eq!(pending, lines.map(|line| Entry::Line(line.into())));
+
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let script = "list r\nlist p\ncommit\n";
+        let mut commander = CliCommander::new(script.as_bytes(), &mut stdout, &mut stderr);
+        commander.replay(pending);
+        journal.start(true).unwrap();
+        commander.set_journal(journal);
+        App::default().serve(commander);
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            "> r point x=0 y=0\n> > > "
+        );
+        assert_eq!(
+            String::from_utf8(stderr).unwrap(),
+            "Shape \"p\" is not found.\nNo transaction is open.\n"
+        );
+        assert!(!path.exists());
+    }
+
     #[test]
     fn test_file_renderer() {
         use crate::render::FileRenderer;
@@ -171,7 +425,40 @@ pub mod tests {
             FileRenderer::new(screen_file_name).unwrap(),
             true,
         );
-        let commander = get_cmd_vec();
-        app.run(commander);
+        app.run(get_cmd_vec());
+        std::fs::remove_file(screen_file_name).unwrap();
+    }
+
+    #[test]
+    fn test_scene_files() {
+        use crate::commander::CliCommander;
+        use crate::journal::tests::temp_path;
+
+        let path = temp_path("test_scene_files");
+        let script = format!(
+            "point p 1 2\ntag p pad\nsave {0}\ndelete p\npoint q 0 0\nload {0}\n",
+            path.display()
+        );
+        let commander = CliCommander::new(
+            std::io::Cursor::new(script.into_bytes()),
+            std::io::sink(),
+            std::io::sink(),
+        );
+        let mut app = App::default();
+        app.serve(commander);
+        {
+            let shapes = app.shapes.lock().unwrap();
+            assert_eq!(shapes.keys().collect::<Vec<_>>(), ["p"]);
+            assert!(shapes.metadata["p"].tags.contains("pad"));
+        }
+
+        // loading is a single step
+        let commander = CliCommander::new(&b"undo\n"[..], std::io::sink(), std::io::sink());
+        app.serve(commander);
+        {
+            let shapes = app.shapes.lock().unwrap();
+            assert_eq!(shapes.keys().collect::<Vec<_>>(), ["q"]);
+        }
+        std::fs::remove_file(&path).unwrap();
     }
 }
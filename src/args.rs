@@ -1,8 +1,10 @@
 use super::app::App;
+use super::executor::HistoryLimits;
 use super::log::DummyLogger;
 use super::render::{DummyRenderer, FileRenderer, HtmlRenderer};
 use clap::Parser;
 use std::error::Error;
+use std::path::PathBuf;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum, Debug)]
 enum RenderType {
@@ -31,6 +33,35 @@ pub struct Args {
     /// Async render or not
     #[clap(long, action = clap::ArgAction::StoreValue, default_value_t = true)]
     async_render: bool,
+
+    /// Keep at most this many commands to undo, branches included
+    #[clap(long, value_parser)]
+    max_history: Option<usize>,
+
+    /// Keep the undo history within roughly this many bytes
+    #[clap(long, value_parser)]
+    history_memory: Option<usize>,
+
+    /// Answer each command with a line of JSON, for tools
+    #[clap(long, action)]
+    pub json: bool,
+
+    /// Record the session here, to replay it after a crash
+    #[clap(long, value_parser, default_value = "clevis.journal")]
+    pub journal: PathBuf,
+
+    /// Do not record the session
+    #[clap(long, action)]
+    pub no_journal: bool,
+
+    /// Replay an unfinished journal without asking
+    #[clap(long, action)]
+    pub recover: bool,
+
+    /// Start with the drawing saved in this scene file, loaded as the first
+    /// command
+    #[clap(long, value_parser)]
+    pub open: Option<PathBuf>,
 }
 
 impl TryInto<App> for Args {
@@ -46,6 +77,10 @@ impl TryInto<App> for Args {
             LoggerType::Dummy => app.set_logger(DummyLogger),
         };
         app.set_async_render(self.async_render);
+        app.set_history_limits(HistoryLimits {
+            max_entries: self.max_history,
+            max_memory: self.history_memory,
+        });
         Ok(app)
     }
 }
@@ -1,4 +1,6 @@
 use crate::executor::Executor;
+use crate::outcome::Outcome;
+use crate::selector::Selection;
 use crate::shape::Shapes;
 use std::error::Error;
 use std::fmt;
@@ -11,6 +13,41 @@ pub trait Command: fmt::Display {
         executor: &mut Executor,
         shapes: &mut Shapes,
     ) -> Result<bool, Box<dyn Error>>;
+
+    /// Called before `execute` with the current selection, for commands
+    /// that act on the selection or change it.
+    fn select(&mut self, selection: &mut Selection, shapes: &Shapes) -> Result<(), Box<dyn Error>> {
+        let _ = (selection, shapes);
+        Ok(())
+    }
+
+    /// Whether `execute` leaves the shapes alone, so there is nothing to
+    /// bring up to date after it.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// What the last `execute` has to tell the user.
+    fn outcome(&mut self) -> Outcome {
+        Outcome::default()
+    }
+
+    /// Roughly how many bytes the command keeps around for undo, to bound
+    /// the memory of the history.
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    /// Fold the `next` command, which just ran, into this one so both are
+    /// undone as a single step. Returns whether it did.
+    fn coalesce(&mut self, next: &dyn Command) -> bool {
+        let _ = next;
+        false
+    }
+
+    fn as_move_by(&self) -> Option<&MoveBy> {
+        None
+    }
 }
 
 mod draw_shape;
@@ -22,5 +59,44 @@ pub use delete::Delete;
 mod move_by;
 pub use move_by::MoveBy;
 
+mod flip;
+pub use flip::{Flip, FlipDirection};
+
+mod mirror;
+pub use mirror::Mirror;
+
+mod array;
+pub use array::{Array, ArrayLayout};
+
+mod constrain;
+pub use constrain::{Constrain, Unconstrain};
+
+mod line_edit;
+pub use line_edit::{LineEdit, LineEditKind};
+
+mod geometry;
+pub use geometry::{Centroid, Hull, Offset, Triangulate};
+
+mod metadata;
+pub use metadata::{EditMetadata, MetadataEdit};
+
+mod for_each;
+pub use for_each::{ForEach, Order};
+
+mod select;
+pub use select::{Select, SelectMode};
+
+mod find;
+pub use find::Find;
+
+mod list;
+pub use list::{List, ListFormat};
+
+mod scene;
+pub use scene::{Load, Save};
+
+mod transaction;
+pub use transaction::{Batch, Transaction, TransactionKind};
+
 mod undo_redo;
-pub use undo_redo::Control;
+pub use undo_redo::{Control, ControlKind};
@@ -0,0 +1,302 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::shape::{ensure_independent, metadata_memory, DataType, Metadata, Point, Shape, Shapes};
+use std::fmt;
+
+type Copies = Vec<(String, Box<dyn Shape>)>;
+
+/// The most items an array can have, the source shape included.
+pub const MAX_ITEMS: usize = 10_000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ArrayLayout {
+    /// `rows` x `cols` copies, `dx` apart horizontally and `dy` apart vertically.
+    Rect {
+        rows: usize,
+        cols: usize,
+        dx: DataType,
+        dy: DataType,
+    },
+    /// `count` copies around `center`, each `angle` degrees after the previous.
+    /// Shapes can not rotate, so only their centers are moved.
+    Polar {
+        count: usize,
+        center: Point,
+        angle: f64,
+    },
+}
+
+impl ArrayLayout {
+    /// Refuse arrays with more than `MAX_ITEMS` items.
+    pub fn check(&self) -> Result<(), Box<dyn Error>> {
+        let items = match *self {
+            Self::Rect { rows, cols, .. } => rows.checked_mul(cols),
+            Self::Polar { count, .. } => Some(count),
+        };
+        if items.is_none_or(|items| items > MAX_ITEMS) {
+            let err_msg = format!("An array can have at most {} items.", MAX_ITEMS);
+            return Err(ClevisError::Validation(err_msg).into());
+        }
+        Ok(())
+    }
+}
+
+/// The offset `by`, if both it and the point it moves `from` stay within the
+/// coordinate range.
+fn fit(by: f64, from: DataType) -> Result<DataType, Box<dyn Error>> {
+    let range = DataType::MIN as f64..=DataType::MAX as f64;
+    if !range.contains(&by) || !range.contains(&(from as f64 + by)) {
+        return Err(ClevisError::validation("The array does not fit the drawing.").into());
+    }
+    Ok(by as DataType)
+}
+
+/// Duplicate a shape many times in a single undoable step. The source
+/// shape counts as the first item of the array and keeps its name. The
+/// copies take its metadata. Taken names are only replaced when asked to.
+pub struct Array {
+    name: String,
+    layout: ArrayLayout,
+    replace: bool,
+    created: Vec<String>,
+    replaced: Vec<(String, Box<dyn Shape>)>,
+    metadata: Vec<(String, Option<Metadata>)>,
+}
+
+impl Array {
+    pub fn new(name: String, layout: ArrayLayout) -> Self {
+        Self {
+            name,
+            layout,
+            replace: false,
+            created: Vec::new(),
+            replaced: Vec::new(),
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+
+    fn copies(&self, source: &dyn Shape) -> Result<Copies, Box<dyn Error>> {
+        self.layout.check()?;
+        let mut copies = Vec::new();
+        let start = source.center();
+        match self.layout {
+            ArrayLayout::Rect { rows, cols, dx, dy } => {
+                for row in 0..rows {
+                    for col in 0..cols {
+                        if row == 0 && col == 0 {
+                            continue;
+                        }
+                        let x = fit(col as f64 * dx as f64, start.x)?;
+                        let y = fit(row as f64 * dy as f64, start.y)?;
+                        let mut shape = source.clone_box();
+                        shape.move_by(x, y);
+                        copies.push((format!("{}_{}_{}", self.name, row, col), shape));
+                    }
+                }
+            }
+            ArrayLayout::Polar {
+                count,
+                center,
+                angle,
+            } => {
+                let x = start.x as f64 - center.x as f64;
+                let y = start.y as f64 - center.y as f64;
+                for i in 1..count {
+                    let (sin, cos) = (angle * i as f64).to_radians().sin_cos();
+                    let by_x = center.x as f64 + (x * cos - y * sin).round() - start.x as f64;
+                    let by_y = center.y as f64 + (x * sin + y * cos).round() - start.y as f64;
+                    let mut shape = source.clone_box();
+                    shape.move_by(fit(by_x, start.x)?, fit(by_y, start.y)?);
+                    copies.push((format!("{}_{}", self.name, i), shape));
+                }
+            }
+        }
+        Ok(copies)
+    }
+}
+
+impl fmt::Display for Array {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.layout {
+            ArrayLayout::Rect { rows, cols, dx, dy } => write!(
+                f,
+                "array-rect {} {} {} {} {}",
+                self.name, rows, cols, dx, dy
+            ),
+            ArrayLayout::Polar {
+                count,
+                center,
+                angle,
+            } => write!(
+                f,
+                "array-polar {} {} {} {} {}",
+                self.name, count, center.x, center.y, angle
+            ),
+        }?;
+        if self.replace {
+            write!(f, " --replace")?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for Array {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let source = shapes
+            .get(&self.name)
+            .ok_or_else(|| ClevisError::ShapeNotFound(self.name.clone()))?;
+        ensure_independent(&self.name, source.as_ref())?;
+        let copies = self.copies(source.as_ref())?;
+        if !self.replace {
+            if let Some((name, _)) = copies.iter().find(|(name, _)| shapes.contains_key(name)) {
+                return Err(ClevisError::NameConflict(name.clone()).into());
+            }
+        }
+
+        self.created = copies.iter().map(|(name, _)| name.clone()).collect();
+        self.metadata = shapes.save_metadata(&self.created);
+        for (name, copy) in copies {
+            if let Some(shape) = shapes.insert(name.clone(), copy) {
+                self.replaced.push((name, shape));
+            }
+        }
+        for name in self.created.iter() {
+            shapes.copy_metadata(&self.name, name);
+        }
+
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        shapes.put_back(
+            std::mem::take(&mut self.created),
+            std::mem::take(&mut self.replaced),
+        );
+        shapes.restore_metadata(std::mem::take(&mut self.metadata));
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let created = self.created.iter().map(|name| name.len());
+        let replaced = (self.replaced.iter()).map(|(name, shape)| name.len() + shape.memory());
+        std::mem::size_of_val(self)
+            + self.name.len()
+            + created.sum::<usize>()
+            + replaced.sum::<usize>()
+            + metadata_memory(&self.metadata)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    #[test]
+    fn test_array_rect() {
+        let mut shapes = Shapes::from([(
+            "pad".to_string(),
+            Box::new(Point { x: 1, y: 1 }) as Box<dyn Shape>,
+        )]);
+
+        let layout = ArrayLayout::Rect {
+            rows: 2,
+            cols: 3,
+            dx: 10,
+            dy: 5,
+        };
+        let mut cmd = Array::new("pad".into(), layout);
+        assert_eq!(cmd.to_string(), "array-rect pad 2 3 10 5");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 6);
+        assert_eq!(format!("{:?}", shapes["pad_0_2"]), "Point { x: 21, y: 1 }");
+        assert_eq!(format!("{:?}", shapes["pad_1_1"]), "Point { x: 11, y: 6 }");
+
+        // the generated names are taken now
+        let mut again = Array::new("pad".into(), layout);
+        assert_eq!(
+            again.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Shape "pad_0_1" already exists."#
+        );
+        assert_eq!(shapes.len(), 6);
+
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 1);
+
+        // a replaced shape comes back with its metadata on undo
+        shapes.insert("pad_0_1".into(), Box::new(Point { x: 0, y: 0 }));
+        (shapes.metadata.entry("pad_0_1".into()).or_default().tags).insert("old".into());
+        let mut cmd = Array::new("pad".into(), layout).with_replace(true);
+        assert_eq!(cmd.to_string(), "array-rect pad 2 3 10 5 --replace");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 6);
+        assert_eq!(shapes["pad_0_1"].center(), Point { x: 11, y: 1 });
+        assert!(!shapes.metadata.contains_key("pad_0_1"));
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 2);
+        assert_eq!(shapes["pad_0_1"].center(), Point { x: 0, y: 0 });
+        assert!(shapes.metadata["pad_0_1"].tags.contains("old"));
+        shapes.remove("pad_0_1");
+
+        let layout = ArrayLayout::Rect {
+            rows: 1,
+            cols: 3,
+            dx: DataType::MAX,
+            dy: 0,
+        };
+        let mut cmd = Array::new("pad".into(), layout);
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            "The array does not fit the drawing."
+        );
+        let layout = ArrayLayout::Rect {
+            rows: usize::MAX,
+            cols: 2,
+            dx: 1,
+            dy: 1,
+        };
+        let mut cmd = Array::new("pad".into(), layout);
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            "An array can have at most 10000 items."
+        );
+        assert_eq!(shapes.len(), 1);
+    }
+
+    #[test]
+    fn test_array_polar() {
+        let mut shapes = Shapes::from([(
+            "bolt".to_string(),
+            Box::new(Circle {
+                center: Point { x: 10, y: 0 },
+                radius: 2,
+            }) as Box<dyn Shape>,
+        )]);
+
+        let layout = ArrayLayout::Polar {
+            count: 4,
+            center: Point { x: 0, y: 0 },
+            angle: 90.0,
+        };
+        let mut cmd = Array::new("bolt".into(), layout);
+        assert_eq!(cmd.to_string(), "array-polar bolt 4 0 0 90");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 4);
+        assert_eq!(shapes["bolt_1"].center(), Point { x: 0, y: 10 });
+        assert_eq!(shapes["bolt_2"].center(), Point { x: -10, y: 0 });
+        assert_eq!(shapes["bolt_3"].center(), Point { x: 0, y: -10 });
+
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 1);
+    }
+}
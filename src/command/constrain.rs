@@ -0,0 +1,109 @@
+use super::{Command, Error, Executor};
+use crate::constraint::Constraint;
+use crate::error::ClevisError;
+use crate::shape::Shapes;
+use std::fmt;
+
+/// Add a constraint to the drawing. The executor solves the constraints
+/// after every command, so the shapes are adjusted right afterwards, and
+/// keeps what the solver moved to put it back on undo.
+pub struct Constrain {
+    constraint: Constraint,
+}
+
+impl Constrain {
+    pub fn new(constraint: Constraint) -> Self {
+        Self { constraint }
+    }
+}
+
+impl fmt::Display for Constrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "constrain {}", self.constraint)
+    }
+}
+
+impl Command for Constrain {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        self.constraint.validate(shapes)?;
+        if shapes.constraints.contains(&self.constraint) {
+            let err_msg = format!(
+                "Constraint {:?} already exists.",
+                self.constraint.to_string()
+            );
+            return Err(ClevisError::Validation(err_msg).into());
+        }
+        shapes.constraints.push(self.constraint.clone());
+
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let index = shapes
+            .constraints
+            .iter()
+            .rposition(|constraint| *constraint == self.constraint)
+            .ok_or_else(|| ClevisError::validation("No constraint was added."))?;
+        shapes.constraints.remove(index);
+
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+}
+
+/// Remove a constraint. The shapes stay where they are.
+pub struct Unconstrain {
+    constraint: Constraint,
+    index: Option<usize>,
+}
+
+impl Unconstrain {
+    pub fn new(constraint: Constraint) -> Self {
+        Self {
+            constraint,
+            index: None,
+        }
+    }
+}
+
+impl fmt::Display for Unconstrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unconstrain {}", self.constraint)
+    }
+}
+
+impl Command for Unconstrain {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let err_msg = format!("Constraint {:?} is not found.", self.constraint.to_string());
+        let index = shapes
+            .constraints
+            .iter()
+            .position(|constraint| *constraint == self.constraint)
+            .ok_or(ClevisError::Validation(err_msg))?;
+        shapes.constraints.remove(index);
+        self.index = Some(index);
+
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let index = self
+            .index
+            .take()
+            .ok_or_else(|| ClevisError::validation("No constraint was removed."))?;
+        shapes.constraints.insert(index, self.constraint.clone());
+
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+}
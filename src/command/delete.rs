@@ -1,12 +1,15 @@
 use super::{Command, Error, Executor};
-use crate::shape::{Shape, Shapes};
+use crate::error::ClevisError;
+use crate::shape::{dependents_of, Metadata, Shape, Shapes};
 use std::fmt;
-use std::mem;
 
 #[derive(Default)]
 pub struct Delete {
     name: String,
     deleted: Option<Box<dyn Shape>>,
+    /// Where the deleted shape was in the drawing order.
+    rank: usize,
+    metadata: Option<Metadata>,
 }
 
 impl Delete {
@@ -14,6 +17,8 @@ impl Delete {
         Self {
             name,
             deleted: None,
+            rank: 0,
+            metadata: None,
         }
     }
 }
@@ -26,16 +31,26 @@ impl fmt::Display for Delete {
 
 impl Command for Delete {
     fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
-        let err_msg = format!("Shape {:?} is not found.", self.name);
-        let shape = shapes.remove(&self.name).ok_or(err_msg)?;
+        let dependents = dependents_of(shapes, &self.name);
+        if !dependents.is_empty() {
+            let err_msg = format!("Shape {:?} is referenced by {:?}.", self.name, dependents);
+            return Err(ClevisError::Validation(err_msg).into());
+        }
+        let (shape, rank) = shapes
+            .take(&self.name)
+            .ok_or_else(|| ClevisError::ShapeNotFound(self.name.clone()))?;
         self.deleted = Some(shape);
-        // shapes.insert(self.name.clone(), Box::new((self.shape).clone()));
+        self.rank = rank;
+        self.metadata = shapes.metadata.remove(&self.name);
         Ok(())
     }
     fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
-        let shape = mem::replace(&mut self.deleted, None);
-        let shape = shape.ok_or("No shape was deleted.")?;
-        shapes.insert(self.name.clone(), shape);
+        let shape = self.deleted.take();
+        let shape = shape.ok_or_else(|| ClevisError::validation("No shape was deleted."))?;
+        shapes.insert_at(self.name.clone(), shape, self.rank);
+        if let Some(metadata) = self.metadata.take() {
+            shapes.metadata.insert(self.name.clone(), metadata);
+        }
         Ok(())
     }
     fn after_execute(
@@ -45,6 +60,14 @@ impl Command for Delete {
     ) -> Result<bool, Box<dyn Error>> {
         Ok(true)
     }
+    fn memory(&self) -> usize {
+        let shape = self.deleted.as_ref().map_or(0, |shape| shape.memory());
+        let metadata = self
+            .metadata
+            .as_ref()
+            .map_or(0, |metadata| metadata.memory());
+        std::mem::size_of_val(self) + self.name.len() + shape + metadata
+    }
 }
 
 #[cfg(test)]
@@ -78,4 +101,42 @@ pub mod tests {
         assert!(del1.deleted.is_none());
         assert_eq!(del1.to_string(), r#"Delete "name" with deleted None"#);
     }
+
+    #[test]
+    fn test_keeps_drawing_order() {
+        let shapes = &mut Shapes::from([
+            (
+                "b".to_string(),
+                Box::new(Point::default()) as Box<dyn Shape>,
+            ),
+            (
+                "a".to_string(),
+                Box::new(Point::default()) as Box<dyn Shape>,
+            ),
+            (
+                "c".to_string(),
+                Box::new(Point::default()) as Box<dyn Shape>,
+            ),
+        ]);
+        let order = |shapes: &Shapes| -> Vec<String> {
+            shapes
+                .ordered()
+                .into_iter()
+                .map(|(name, _)| name.clone())
+                .collect()
+        };
+        let mut delete = Delete::new("a".to_string());
+        delete.execute(shapes).unwrap();
+        assert_eq!(order(shapes), ["b", "c"]);
+        delete.undo(shapes).unwrap();
+        assert_eq!(order(shapes), ["b", "a", "c"]);
+
+        // a new shape under a deleted name goes on top
+        delete.execute(shapes).unwrap();
+        let memory = shapes.memory();
+        shapes.insert("a".to_string(), Box::new(Point::default()));
+        assert_eq!(order(shapes), ["b", "c", "a"]);
+        shapes.remove("a");
+        assert_eq!(shapes.memory(), memory);
+    }
 }
@@ -1,13 +1,20 @@
 use super::{Command, Error, Executor};
-use crate::shape::{Shape, Shapes};
+use crate::error::ClevisError;
+use crate::shape::{metadata_memory, Metadata, Shape, Shapes};
 use std::fmt;
 
+/// Draw a shape under a new name. Drawing onto a name that is taken fails,
+/// unless the command is made to replace the shape, which comes back on undo.
+/// The metadata of a replaced shape goes with it.
 pub struct DrawShape<ShapeType>
 where
     ShapeType: Shape,
 {
     name: String,
     shape: ShapeType,
+    replace: bool,
+    replaced: Option<Box<dyn Shape>>,
+    metadata: Vec<(String, Option<Metadata>)>,
 }
 
 impl<ShapeType> DrawShape<ShapeType>
@@ -15,7 +22,18 @@ where
     ShapeType: Shape,
 {
     pub fn new(name: String, shape: ShapeType) -> Self {
-        Self { name, shape }
+        Self {
+            name,
+            shape,
+            replace: false,
+            replaced: None,
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
     }
 }
 
@@ -24,7 +42,7 @@ where
     ShapeType: Shape + Clone,
 {
     fn clone(&self) -> Self {
-        Self::new(self.name.clone(), self.shape.clone())
+        Self::new(self.name.clone(), self.shape.clone()).with_replace(self.replace)
     }
 }
 
@@ -36,6 +54,9 @@ where
         Self {
             name: std::any::type_name::<ShapeType>().into(),
             shape: ShapeType::default(),
+            replace: false,
+            replaced: None,
+            metadata: Vec::new(),
         }
     }
 }
@@ -45,7 +66,11 @@ where
     ShapeType: Shape,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} {:?}", self.name, self.shape)
+        write!(f, "{} {:?}", self.name, self.shape)?;
+        if self.replace {
+            write!(f, " --replace")?;
+        }
+        Ok(())
     }
 }
 
@@ -54,11 +79,22 @@ where
     ShapeType: 'static + Shape + Clone,
 {
     fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
-        shapes.insert(self.name.clone(), Box::new((self.shape).clone()));
+        if !self.replace && shapes.contains_key(&self.name) {
+            return Err(ClevisError::NameConflict(self.name.clone()).into());
+        }
+        let mut shape = self.shape.clone();
+        shape.update(shapes)?;
+        self.replaced = shapes.insert(self.name.clone(), Box::new(shape));
+        self.metadata = shapes.save_metadata(std::slice::from_ref(&self.name));
+        shapes.metadata.remove(&self.name);
         Ok(())
     }
     fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
-        shapes.remove(&self.name);
+        match self.replaced.take() {
+            Some(shape) => shapes.insert(self.name.clone(), shape),
+            None => shapes.remove(&self.name),
+        };
+        shapes.restore_metadata(std::mem::take(&mut self.metadata));
         Ok(())
     }
     fn after_execute(
@@ -68,6 +104,14 @@ where
     ) -> Result<bool, Box<dyn Error>> {
         Ok(true)
     }
+    fn memory(&self) -> usize {
+        let replaced = self.replaced.as_ref().map_or(0, |shape| shape.memory());
+        std::mem::size_of_val(self)
+            + self.name.len()
+            + self.shape.memory()
+            + replaced
+            + metadata_memory(&self.metadata)
+    }
 }
 
 #[cfg(test)]
@@ -77,14 +121,8 @@ mod tests {
 
     #[test]
     fn execute() {
-        let mut cmd1 = DrawShape {
-            shape: Point::default(),
-            name: "p1".to_string(),
-        };
-        let mut cmd2 = DrawShape {
-            shape: Rectangle::default(),
-            name: "p2".to_string(),
-        };
+        let mut cmd1 = DrawShape::new("p1".to_string(), Point::default());
+        let mut cmd2 = DrawShape::new("p2".to_string(), Rectangle::default());
         let mut shapes = Shapes::default();
 
         cmd1.execute(&mut shapes).unwrap();
@@ -94,7 +132,7 @@ mod tests {
         );
 
         cmd1.undo(&mut shapes).unwrap();
-        assert!(shapes.get(&cmd1.name).is_none());
+        assert!(!shapes.contains_key(&cmd1.name));
         assert_eq!(shapes.len(), 0);
 
         cmd1.execute(&mut shapes).unwrap();
@@ -118,4 +156,34 @@ mod tests {
         cmd2.undo(&mut shapes).unwrap();
         assert_eq!(shapes.len(), 0);
     }
+
+    #[test]
+    fn test_replace() {
+        let mut shapes = Shapes::default();
+        DrawShape::new("p".to_string(), Point { x: 1, y: 2 })
+            .execute(&mut shapes)
+            .unwrap();
+        shapes
+            .metadata
+            .entry("p".into())
+            .or_default()
+            .tags
+            .insert("pad".into());
+
+        let mut cmd = DrawShape::new("p".to_string(), Point { x: 3, y: 4 });
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Shape "p" already exists."#
+        );
+        assert_eq!(shapes["p"].center(), Point { x: 1, y: 2 });
+
+        let mut cmd = cmd.with_replace(true);
+        assert_eq!(cmd.to_string(), "p Point { x: 3, y: 4 } --replace");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes["p"].center(), Point { x: 3, y: 4 });
+        assert!(!shapes.metadata.contains_key("p"));
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes["p"].center(), Point { x: 1, y: 2 });
+        assert!(shapes.metadata["p"].tags.contains("pad"));
+    }
 }
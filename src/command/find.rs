@@ -0,0 +1,108 @@
+use super::{Command, Error, Executor};
+use crate::outcome::{quote, Outcome};
+use crate::query::Query;
+use crate::selector::Selection;
+use crate::shape::Shapes;
+use std::fmt;
+
+/// Report the shapes matching a query, and select them with `--select`.
+/// The shapes are left alone, so this is not recorded for undo.
+pub struct Find {
+    query: Query,
+    select: bool,
+    found: Vec<String>,
+}
+
+impl Find {
+    pub fn new(query: Query, select: bool) -> Self {
+        Self {
+            query,
+            select,
+            found: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Find {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "find ")?;
+        if self.select {
+            write!(f, "--select ")?;
+        }
+        write!(f, "{}", self.query)
+    }
+}
+
+impl Command for Find {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        self.found = self.query.find(shapes);
+        Ok(())
+    }
+    fn undo(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+    fn is_read_only(&self) -> bool {
+        true
+    }
+    fn outcome(&mut self) -> Outcome {
+        let message = if self.found.is_empty() {
+            format!("No shape matches {}.", self.query)
+        } else {
+            format!("Found {}.", self.found.join(" "))
+        };
+        let names: Vec<String> = self.found.iter().map(|name| quote(name)).collect();
+        Outcome::message(message).with_data(format!("[{}]", names.join(",")))
+    }
+    fn select(&mut self, selection: &mut Selection, shapes: &Shapes) -> Result<(), Box<dyn Error>> {
+        if self.select {
+            *selection = self.query.find(shapes).into_iter().collect();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    #[test]
+    fn test_find() {
+        let mut shapes = Shapes::from([
+            (
+                "a".to_string(),
+                Box::new(Point { x: 0, y: 0 }) as Box<dyn Shape>,
+            ),
+            (
+                "b".to_string(),
+                Box::new(Circle {
+                    center: Point { x: 5, y: 5 },
+                    radius: 3,
+                }) as Box<dyn Shape>,
+            ),
+        ]);
+        let mut selection = Selection::from(["a".to_string()]);
+
+        let mut cmd = Find::new(Query::parse("type=circle").unwrap(), false);
+        assert_eq!(cmd.to_string(), "find type=circle");
+        cmd.select(&mut selection, &shapes).unwrap();
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(cmd.found, ["b"]);
+        let outcome = cmd.outcome();
+        assert_eq!(outcome.message.unwrap(), "Found b.");
+        assert_eq!(outcome.data.unwrap(), r#"["b"]"#);
+        assert_eq!(Vec::from_iter(selection.iter()), ["a"]);
+
+        let mut cmd = Find::new(Query::parse("cx>=0 and cy<=5").unwrap(), true);
+        assert_eq!(cmd.to_string(), "find --select cx>=0 and cy<=5");
+        cmd.select(&mut selection, &shapes).unwrap();
+        assert_eq!(Vec::from_iter(selection.iter()), ["a", "b"]);
+    }
+}
@@ -0,0 +1,186 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::shape::{bounding_box, ensure_independent, DataType, Line, Point, Shape, Shapes};
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FlipDirection {
+    Horizontal,
+    Vertical,
+}
+
+impl fmt::Display for FlipDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Self::Horizontal => "horizontal",
+                Self::Vertical => "vertical",
+            }
+        )
+    }
+}
+
+/// Flip a shape in place. Without an explicit axis the shape is flipped
+/// around the middle of its bounding box, so it keeps its place.
+pub struct Flip {
+    name: String,
+    direction: FlipDirection,
+    axis: Option<DataType>,
+    previous: Option<Box<dyn Shape>>,
+}
+
+impl Flip {
+    pub fn new(name: String, direction: FlipDirection, axis: Option<DataType>) -> Self {
+        Self {
+            name,
+            direction,
+            axis,
+            previous: None,
+        }
+    }
+}
+
+impl fmt::Display for Flip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "flip {} {}", self.name, self.direction)?;
+        if let Some(axis) = self.axis {
+            write!(f, " {}", axis)?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for Flip {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let shape = shapes
+            .get_mut(&self.name)
+            .ok_or_else(|| ClevisError::ShapeNotFound(self.name.clone()))?;
+        ensure_independent(&self.name, shape.as_ref())?;
+        let bounds = bounding_box(&shape.outline());
+        // twice the axis, as the middle of a box can fall between two points
+        let twice = |axis: Option<DataType>, low: DataType, size: DataType| match axis {
+            Some(axis) => 2 * axis as i64,
+            None => 2 * low as i64 + size as i64,
+        };
+        self.previous = Some(shape.clone());
+        match self.direction {
+            FlipDirection::Horizontal => {
+                let twice = twice(self.axis, bounds.corner.x, bounds.w);
+                let x = twice.div_euclid(2) as DataType;
+                shape.reflect(&Line(Point { x, y: 0 }, Point { x, y: 1 }));
+                shape.move_by(twice.rem_euclid(2) as DataType, 0);
+            }
+            FlipDirection::Vertical => {
+                let twice = twice(self.axis, bounds.corner.y, bounds.h);
+                let y = twice.div_euclid(2) as DataType;
+                shape.reflect(&Line(Point { x: 0, y }, Point { x: 1, y }));
+                shape.move_by(0, twice.rem_euclid(2) as DataType);
+            }
+        }
+
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let previous = self
+            .previous
+            .take()
+            .ok_or_else(|| ClevisError::validation("No shape was flipped."))?;
+        let shape = shapes
+            .get_mut(&self.name)
+            .ok_or_else(|| ClevisError::ShapeNotFound(self.name.clone()))?;
+        *shape = previous;
+
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let previous = self.previous.as_ref().map_or(0, |shape| shape.memory());
+        std::mem::size_of_val(self) + self.name.len() + previous
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::render::tests::get_writer_render_result;
+    use crate::shape::*;
+
+    #[test]
+    fn test_flip() {
+        let mut shapes = Shapes::from([(
+            "rect".to_string(),
+            Box::new(Rectangle {
+                corner: Point { x: 10, y: 0 },
+                w: -4,
+                h: 2,
+                radius: 0,
+            }) as Box<dyn Shape>,
+        )]);
+
+        let mut cmd = Flip::new("rect".into(), FlipDirection::Horizontal, Some(0));
+        assert_eq!(cmd.to_string(), "flip rect horizontal 0");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(
+            get_writer_render_result(&shapes),
+            "\nrect Rectangle { corner: Point { x: -10, y: 0 }, w: 4, h: 2, radius: 0 }\n"
+        );
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(
+            get_writer_render_result(&shapes),
+            "\nrect Rectangle { corner: Point { x: 10, y: 0 }, w: -4, h: 2, radius: 0 }\n"
+        );
+
+        shapes.insert(
+            "rect".into(),
+            Box::new(Line(Point { x: 0, y: 0 }, Point { x: 4, y: 2 })),
+        );
+        let mut cmd = Flip::new("rect".into(), FlipDirection::Vertical, None);
+        assert_eq!(cmd.to_string(), "flip rect vertical");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(
+            get_writer_render_result(&shapes),
+            "\nrect Line(Point { x: 0, y: 2 }, Point { x: 4, y: 0 })\n"
+        );
+
+        // an odd width keeps its place
+        shapes.insert(
+            "rect".into(),
+            Box::new(Rectangle {
+                corner: Point { x: 0, y: 0 },
+                w: 3,
+                h: 2,
+                radius: 0,
+            }),
+        );
+        let mut cmd = Flip::new("rect".into(), FlipDirection::Horizontal, None);
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(
+            get_writer_render_result(&shapes),
+            "\nrect Rectangle { corner: Point { x: 0, y: 0 }, w: 3, h: 2, radius: 0 }\n"
+        );
+        shapes.insert(
+            "rect".into(),
+            Box::new(Line(Point { x: 0, y: 0 }, Point { x: 4, y: 3 })),
+        );
+        let mut cmd = Flip::new("rect".into(), FlipDirection::Vertical, None);
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(
+            get_writer_render_result(&shapes),
+            "\nrect Line(Point { x: 0, y: 3 }, Point { x: 4, y: 0 })\n"
+        );
+
+        let mut cmd = Flip::new("aaa".into(), FlipDirection::Vertical, None);
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Shape "aaa" is not found."#
+        );
+    }
+}
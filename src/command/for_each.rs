@@ -0,0 +1,297 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::executor::ExecutionError;
+use crate::outcome::Outcome;
+use crate::selector::{Selection, Selector};
+use crate::shape::Shapes;
+use std::collections::HashSet;
+use std::fmt;
+
+type MakeFn = Box<dyn Fn(String) -> Box<dyn Command>>;
+
+/// Which of the selected shapes `ForEach` visits, and in which order.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Order {
+    /// All of them, by name.
+    ByName,
+    /// Only shapes not derived from others, by name. Derived shapes follow
+    /// the shapes they depend on, so moving those moves them too.
+    Independent,
+    /// All of them, every shape before the shapes it depends on, so
+    /// deleting all of them does not trip over a dependent.
+    DependentsFirst,
+}
+
+impl Order {
+    fn arrange(self, names: Vec<String>, shapes: &Shapes) -> Vec<String> {
+        fn visit(
+            name: &str,
+            shapes: &Shapes,
+            selected: &mut HashSet<String>,
+            order: &mut Vec<String>,
+        ) {
+            if !selected.remove(name) {
+                return;
+            }
+            if let Some(shape) = shapes.get(name) {
+                for dep in shape.dependencies() {
+                    visit(&dep, shapes, selected, order);
+                }
+            }
+            order.push(name.to_string());
+        }
+
+        match self {
+            Self::ByName => names,
+            Self::Independent => names
+                .into_iter()
+                .filter(|name| match shapes.get(name) {
+                    Some(shape) => shape.dependencies().is_empty(),
+                    None => true,
+                })
+                .collect(),
+            Self::DependentsFirst => {
+                let mut selected: HashSet<String> = names.iter().cloned().collect();
+                let mut order = Vec::new();
+                for name in names.iter() {
+                    visit(name, shapes, &mut selected, &mut order);
+                }
+                order.reverse();
+                order
+            }
+        }
+    }
+}
+
+/// Run a command on every shape picked by a selector, as a single
+/// undoable step. If one of them fails, the others are undone again.
+pub struct ForEach {
+    cmd_name: String,
+    selector: Selector,
+    args: String,
+    make: MakeFn,
+    order: Order,
+    executed: Vec<Box<dyn Command>>,
+}
+
+impl ForEach {
+    /// `make` builds the command for one shape name. `cmd_name` and `args`
+    /// are only used to display the command around its selector.
+    pub fn new(cmd_name: &str, selector: Selector, args: &str, make: MakeFn) -> Self {
+        Self {
+            cmd_name: cmd_name.to_string(),
+            selector,
+            args: args.to_string(),
+            make,
+            order: Order::ByName,
+            executed: Vec::new(),
+        }
+    }
+
+    pub fn with_order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+}
+
+impl fmt::Display for ForEach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.cmd_name, self.selector)?;
+        if !self.args.is_empty() {
+            write!(f, " {}", self.args)?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for ForEach {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        self.executed.clear();
+        let names = self.order.arrange(self.selector.resolve(shapes)?, shapes);
+        if names.is_empty() {
+            let err_msg = format!(
+                "All shapes matched by {} follow other shapes.",
+                self.selector
+            );
+            return Err(ClevisError::Validation(err_msg).into());
+        }
+        for name in names {
+            let mut cmd = (self.make)(name);
+            if let Err(error) = cmd.execute(shapes) {
+                if let Err(restore) = self.undo(shapes) {
+                    return Err(ExecutionError::Unrestored { error, restore }.into());
+                }
+                return Err(error);
+            }
+            self.executed.push(cmd);
+        }
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        for i in (0..self.executed.len()).rev() {
+            if let Err(error) = self.executed[i].undo(shapes) {
+                // redo the ones already undone, so nothing is half undone
+                for cmd in self.executed[i + 1..].iter_mut() {
+                    if let Err(restore) = cmd.execute(shapes) {
+                        return Err(ExecutionError::Unrestored { error, restore }.into());
+                    }
+                }
+                return Err(error);
+            }
+        }
+        self.executed.clear();
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let commands = self.executed.iter().map(|cmd| cmd.memory());
+        std::mem::size_of_val(self) + commands.sum::<usize>()
+    }
+    fn outcome(&mut self) -> Outcome {
+        let mut outcome = Outcome::message(format!(
+            "{} applied to {} shapes.",
+            self.cmd_name,
+            self.executed.len()
+        ));
+        for cmd in self.executed.iter_mut() {
+            outcome.warnings.extend(cmd.outcome().warnings);
+        }
+        outcome
+    }
+    fn select(
+        &mut self,
+        selection: &mut Selection,
+        _shapes: &Shapes,
+    ) -> Result<(), Box<dyn Error>> {
+        // keep the names, so redo acts on the same shapes
+        if let Selector::Selection(names) = &mut self.selector {
+            *names = selection.iter().cloned().collect();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::command::{Delete, MoveBy};
+    use crate::shape::*;
+
+    fn get_shapes() -> Shapes {
+        Shapes::from([
+            (
+                "pad_1".to_string(),
+                Box::new(Point { x: 0, y: 0 }) as Box<dyn Shape>,
+            ),
+            (
+                "pad_2".to_string(),
+                Box::new(Point { x: 5, y: 0 }) as Box<dyn Shape>,
+            ),
+            (
+                "hole".to_string(),
+                Box::new(Point { x: 9, y: 9 }) as Box<dyn Shape>,
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_for_each() {
+        let mut shapes = get_shapes();
+        let selector = Selector::parse("pad_?").unwrap();
+        let mut cmd = ForEach::new(
+            "move",
+            selector,
+            "1 2",
+            Box::new(|name| Box::new(MoveBy::new(name, 1, 2))),
+        );
+        assert_eq!(cmd.to_string(), "move pad_? 1 2");
+
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes["pad_1"].center(), Point { x: 1, y: 2 });
+        assert_eq!(shapes["pad_2"].center(), Point { x: 6, y: 2 });
+        assert_eq!(shapes["hole"].center(), Point { x: 9, y: 9 });
+        assert_eq!(cmd.outcome().message.unwrap(), "move applied to 2 shapes.");
+        cmd.undo(&mut shapes).unwrap();
+        for (name, shape) in get_shapes() {
+            assert_eq!(shapes[&name].center(), shape.center());
+        }
+    }
+
+    #[test]
+    fn test_for_each_rolls_back() {
+        let mut shapes = get_shapes();
+        shapes.insert(
+            "pad_3".into(),
+            Box::new(Connector::new("hole".into(), "pad_2".into())),
+        );
+        update_dependents(&mut shapes).unwrap();
+
+        // pad_2 is referenced by the connector, so pad_1 is restored too
+        let selector = Selector::parse(r"/^pad_\d$/").unwrap();
+        let mut cmd = ForEach::new(
+            "delete",
+            selector,
+            "",
+            Box::new(|name| Box::new(Delete::new(name))),
+        );
+        assert_eq!(cmd.to_string(), r"delete /^pad_\d$/");
+        assert!(cmd.execute(&mut shapes).is_err());
+        assert_eq!(shapes.len(), 4);
+        assert!(shapes.contains_key("pad_1"));
+    }
+
+    #[test]
+    fn test_for_each_order() {
+        // "a_line" follows the pads and sorts before them
+        let mut shapes = get_shapes();
+        shapes.insert(
+            "a_line".into(),
+            Box::new(Connector::new("pad_1".into(), "pad_2".into())),
+        );
+        update_dependents(&mut shapes).unwrap();
+        let saved = format!("{:?}", shapes.ordered());
+
+        let mut cmd = ForEach::new(
+            "move",
+            Selector::parse("*").unwrap(),
+            "1 1",
+            Box::new(|name| Box::new(MoveBy::new(name, 1, 1))),
+        )
+        .with_order(Order::Independent);
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(cmd.outcome().message.unwrap(), "move applied to 3 shapes.");
+        assert_eq!(shapes["pad_1"].center(), Point { x: 1, y: 1 });
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(format!("{:?}", shapes.ordered()), saved);
+
+        let mut cmd = ForEach::new(
+            "move",
+            Selector::parse("a_*").unwrap(),
+            "1 1",
+            Box::new(|name| Box::new(MoveBy::new(name, 1, 1))),
+        )
+        .with_order(Order::Independent);
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            "All shapes matched by a_* follow other shapes."
+        );
+
+        let mut cmd = ForEach::new(
+            "delete",
+            Selector::parse("*").unwrap(),
+            "",
+            Box::new(|name| Box::new(Delete::new(name))),
+        )
+        .with_order(Order::DependentsFirst);
+        cmd.execute(&mut shapes).unwrap();
+        assert!(shapes.is_empty());
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(format!("{:?}", shapes.ordered()), saved);
+    }
+}
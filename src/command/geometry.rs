@@ -0,0 +1,528 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::outcome::Outcome;
+use crate::shape::{
+    centroid, convex_hull, metadata_memory, triangulate, DataType, Metadata, Point, Polygon, Shape,
+    Shapes,
+};
+use std::fmt;
+
+fn get_outline(shapes: &Shapes, name: &str) -> Result<Vec<Point>, Box<dyn Error>> {
+    Ok(shapes
+        .get(name)
+        .ok_or_else(|| ClevisError::ShapeNotFound(name.to_string()))?
+        .outline())
+}
+
+/// Draw `shape` under `name`, the way `DrawShape` does: a shape replaced
+/// there is returned, and its metadata is kept in `metadata` for undo.
+fn put(
+    shapes: &mut Shapes,
+    name: &str,
+    shape: Box<dyn Shape>,
+    metadata: &mut Vec<(String, Option<Metadata>)>,
+) -> Option<Box<dyn Shape>> {
+    metadata.extend(shapes.save_metadata(&[name.to_string()]));
+    shapes.metadata.remove(name);
+    shapes.insert(name.to_string(), shape)
+}
+
+/// Draw the convex hull of some shapes as a new polygon. Like drawing, a
+/// taken name is only replaced when asked to.
+pub struct Hull {
+    new_name: String,
+    names: Vec<String>,
+    replace: bool,
+    replaced: Option<Box<dyn Shape>>,
+    metadata: Vec<(String, Option<Metadata>)>,
+}
+
+impl Hull {
+    pub fn new(new_name: String, names: Vec<String>) -> Self {
+        Self {
+            new_name,
+            names,
+            replace: false,
+            replaced: None,
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+}
+
+impl fmt::Display for Hull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hull {} {}", self.new_name, self.names.join(" "))?;
+        if self.replace {
+            write!(f, " --replace")?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for Hull {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        if !self.replace && shapes.contains_key(&self.new_name) {
+            return Err(ClevisError::NameConflict(self.new_name.clone()).into());
+        }
+        let mut points = Vec::new();
+        for name in self.names.iter() {
+            points.extend(get_outline(shapes, name)?);
+        }
+        let vertices = convex_hull(&points);
+        if vertices.len() < 3 {
+            let err_msg = "The shapes lie on a single line and have no hull.";
+            return Err(ClevisError::validation(err_msg).into());
+        }
+        let hull = Box::new(Polygon { vertices });
+        self.replaced = put(shapes, &self.new_name, hull, &mut self.metadata);
+
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        match self.replaced.take() {
+            Some(shape) => shapes.insert(self.new_name.clone(), shape),
+            None => shapes.remove(&self.new_name),
+        };
+        shapes.restore_metadata(std::mem::take(&mut self.metadata));
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let replaced = self.replaced.as_ref().map_or(0, |shape| shape.memory());
+        let names = self.names.iter().map(|name| name.len());
+        std::mem::size_of_val(self)
+            + self.new_name.len()
+            + names.sum::<usize>()
+            + replaced
+            + metadata_memory(&self.metadata)
+    }
+}
+
+/// Cover a shape with triangles named `<name>_0`, `<name>_1`, ...
+/// The shape itself is kept. Taken names are only replaced when asked to.
+pub struct Triangulate {
+    name: String,
+    replace: bool,
+    created: Vec<String>,
+    replaced: Vec<(String, Box<dyn Shape>)>,
+    metadata: Vec<(String, Option<Metadata>)>,
+}
+
+impl Triangulate {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            replace: false,
+            created: Vec::new(),
+            replaced: Vec::new(),
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+}
+
+impl fmt::Display for Triangulate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "triangulate {}", self.name)?;
+        if self.replace {
+            write!(f, " --replace")?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for Triangulate {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let triangles: Vec<(String, Box<dyn Shape>)> =
+            triangulate(&get_outline(shapes, &self.name)?)?
+                .into_iter()
+                .enumerate()
+                .map(|(i, triangle)| {
+                    let polygon = Polygon {
+                        vertices: triangle.to_vec(),
+                    };
+                    (format!("{}_{}", self.name, i), Box::new(polygon) as _)
+                })
+                .collect();
+        if !self.replace {
+            if let Some((name, _)) = triangles.iter().find(|(name, _)| shapes.contains_key(name)) {
+                return Err(ClevisError::NameConflict(name.clone()).into());
+            }
+        }
+
+        self.created = triangles.iter().map(|(name, _)| name.clone()).collect();
+        for (name, triangle) in triangles {
+            if let Some(shape) = put(shapes, &name, triangle, &mut self.metadata) {
+                self.replaced.push((name, shape));
+            }
+        }
+
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        shapes.put_back(
+            std::mem::take(&mut self.created),
+            std::mem::take(&mut self.replaced),
+        );
+        shapes.restore_metadata(std::mem::take(&mut self.metadata));
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let created = self.created.iter().map(|name| name.len());
+        let replaced = (self.replaced.iter()).map(|(name, shape)| name.len() + shape.memory());
+        std::mem::size_of_val(self)
+            + self.name.len()
+            + created.sum::<usize>()
+            + replaced.sum::<usize>()
+            + metadata_memory(&self.metadata)
+    }
+}
+
+/// Draw a copy of a shape grown outward by `distance`, or shrunk inward for
+/// a negative distance. Lines become the outline around them. Like drawing,
+/// a taken name is only replaced when asked to.
+pub struct Offset {
+    name: String,
+    new_name: String,
+    distance: DataType,
+    round: bool,
+    replace: bool,
+    replaced: Option<Box<dyn Shape>>,
+    metadata: Vec<(String, Option<Metadata>)>,
+}
+
+impl Offset {
+    pub fn new(name: String, new_name: String, distance: DataType, round: bool) -> Self {
+        Self {
+            name,
+            new_name,
+            distance,
+            round,
+            replace: false,
+            replaced: None,
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+}
+
+impl fmt::Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "offset {} {} {}",
+            self.name, self.new_name, self.distance
+        )?;
+        if self.round {
+            write!(f, " round")?;
+        }
+        if self.replace {
+            write!(f, " --replace")?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for Offset {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        if !self.replace && shapes.contains_key(&self.new_name) {
+            return Err(ClevisError::NameConflict(self.new_name.clone()).into());
+        }
+        let shape = shapes
+            .get(&self.name)
+            .ok_or_else(|| ClevisError::ShapeNotFound(self.name.clone()))?;
+        let offset = shape.offset(self.distance, self.round).map_err(|error| {
+            let err_msg = format!("Can not offset {:?}: {}", self.name, error);
+            ClevisError::Validation(err_msg)
+        })?;
+        self.replaced = put(shapes, &self.new_name, offset, &mut self.metadata);
+
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        match self.replaced.take() {
+            Some(shape) => shapes.insert(self.new_name.clone(), shape),
+            None => shapes.remove(&self.new_name),
+        };
+        shapes.restore_metadata(std::mem::take(&mut self.metadata));
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let replaced = self.replaced.as_ref().map_or(0, |shape| shape.memory());
+        std::mem::size_of_val(self)
+            + self.name.len()
+            + self.new_name.len()
+            + replaced
+            + metadata_memory(&self.metadata)
+    }
+}
+
+/// Report the center of mass of a shape. Changes nothing, so it is not
+/// recorded for undo.
+pub struct Centroid {
+    name: String,
+    centroid: Option<Point>,
+}
+
+impl Centroid {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            centroid: None,
+        }
+    }
+}
+
+impl fmt::Display for Centroid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "centroid {}", self.name)
+    }
+}
+
+impl Command for Centroid {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        self.centroid = Some(centroid(&get_outline(shapes, &self.name)?));
+        Ok(())
+    }
+    fn undo(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+    fn is_read_only(&self) -> bool {
+        true
+    }
+    fn outcome(&mut self) -> Outcome {
+        match self.centroid {
+            Some(point) => Outcome::message(format!(
+                "The centroid of {:?} is at {} {}.",
+                self.name, point.x, point.y
+            ))
+            .with_data(format!(r#"{{"x":{},"y":{}}}"#, point.x, point.y)),
+            None => Outcome::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn point(x: i32, y: i32) -> Box<dyn Shape> {
+        Box::new(Point { x, y })
+    }
+
+    #[test]
+    fn test_hull() {
+        let mut shapes = Shapes::from([
+            ("a".to_string(), point(0, 0)),
+            ("b".to_string(), point(4, 0)),
+            ("c".to_string(), point(2, 1)),
+            ("d".to_string(), point(2, 4)),
+        ]);
+
+        let mut cmd = Hull::new(
+            "h".into(),
+            vec!["a".into(), "b".into(), "c".into(), "d".into()],
+        );
+        assert_eq!(cmd.to_string(), "hull h a b c d");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(
+            shapes["h"].outline(),
+            vec![
+                Point { x: 0, y: 0 },
+                Point { x: 4, y: 0 },
+                Point { x: 2, y: 4 }
+            ]
+        );
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Shape "h" already exists."#
+        );
+        cmd.undo(&mut shapes).unwrap();
+        assert!(!shapes.contains_key("h"));
+
+        // replacing a shape drops its metadata, undo puts both back
+        shapes
+            .metadata
+            .entry("d".into())
+            .or_default()
+            .tags
+            .insert("top".into());
+        let mut cmd = Hull::new("d".into(), vec!["a".into(), "b".into(), "d".into()]);
+        assert!(cmd.execute(&mut shapes).is_err());
+        let mut cmd = cmd.with_replace(true);
+        assert_eq!(cmd.to_string(), "hull d a b d --replace");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes["d"].outline().len(), 3);
+        assert!(!shapes.metadata.contains_key("d"));
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes["d"].center(), Point { x: 2, y: 4 });
+        assert!(shapes.metadata["d"].tags.contains("top"));
+
+        let mut cmd = Hull::new("h".into(), vec!["a".into(), "b".into()]);
+        assert!(cmd.execute(&mut shapes).is_err());
+    }
+
+    #[test]
+    fn test_triangulate_and_centroid() {
+        // an L shaped hexagon
+        let vertices = vec![
+            Point { x: 0, y: 0 },
+            Point { x: 6, y: 0 },
+            Point { x: 6, y: 3 },
+            Point { x: 3, y: 3 },
+            Point { x: 3, y: 6 },
+            Point { x: 0, y: 6 },
+        ];
+        let mut shapes = Shapes::from([(
+            "l".to_string(),
+            Box::new(Polygon { vertices }) as Box<dyn Shape>,
+        )]);
+
+        let mut cmd = Triangulate::new("l".into());
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 5);
+        let area: i32 = (0..4)
+            .map(|i| {
+                let t = shapes[&format!("l_{}", i)].outline();
+                ((t[1].x - t[0].x) * (t[2].y - t[0].y) - (t[1].y - t[0].y) * (t[2].x - t[0].x))
+                    .abs()
+            })
+            .sum();
+        assert_eq!(area, 2 * 27);
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 1);
+
+        shapes.insert("l_1".into(), Box::new(Point { x: 9, y: 9 }));
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Shape "l_1" already exists."#
+        );
+        let mut cmd = Triangulate::new("l".into()).with_replace(true);
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 5);
+        assert_eq!(shapes["l_1"].outline().len(), 3);
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 2);
+        assert_eq!(shapes["l_1"].center(), Point { x: 9, y: 9 });
+        shapes.remove("l_1");
+
+        let triangle = Polygon {
+            vertices: vec![
+                Point { x: 0, y: 0 },
+                Point { x: 6, y: 0 },
+                Point { x: 0, y: 3 },
+            ],
+        };
+        shapes.insert("t".into(), Box::new(triangle));
+        let mut cmd = Centroid::new("t".into());
+        assert_eq!(cmd.to_string(), "centroid t");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(cmd.centroid, Some(Point { x: 2, y: 1 }));
+        assert_eq!(
+            cmd.outcome().message.unwrap(),
+            r#"The centroid of "t" is at 2 1."#
+        );
+    }
+
+    #[test]
+    fn test_offset() {
+        use crate::render::tests::get_writer_render_result;
+        use crate::shape::{Circle, Line, Rectangle};
+
+        let mut shapes = Shapes::from([
+            (
+                "c".to_string(),
+                Box::new(Circle {
+                    center: Point { x: 0, y: 0 },
+                    radius: 5,
+                }) as Box<dyn Shape>,
+            ),
+            (
+                "r".to_string(),
+                Box::new(Rectangle {
+                    corner: Point { x: 10, y: 10 },
+                    w: -4,
+                    h: 6,
+                    radius: 0,
+                }) as Box<dyn Shape>,
+            ),
+            (
+                "l".to_string(),
+                Box::new(Line(Point { x: 0, y: 0 }, Point { x: 10, y: 0 })) as Box<dyn Shape>,
+            ),
+        ]);
+
+        for (name, new_name, distance, round) in [
+            ("c", "c2", -2, false),
+            ("r", "r2", 1, true),
+            ("r", "r3", -1, false),
+            ("l", "l2", 2, false),
+        ] {
+            Offset::new(name.into(), new_name.into(), distance, round)
+                .execute(&mut shapes)
+                .unwrap();
+        }
+        let result = get_writer_render_result(&shapes);
+        for answer in [
+            "c2 Circle { center: Point { x: 0, y: 0 }, radius: 3 }",
+            "r2 Rectangle { corner: Point { x: 5, y: 9 }, w: 6, h: 8, radius: 1 }",
+            "r3 Rectangle { corner: Point { x: 7, y: 11 }, w: 2, h: 4, radius: 0 }",
+            "l2 Polygon { vertices: [Point { x: 0, y: 2 }, Point { x: 10, y: 2 }, \
+             Point { x: 10, y: -2 }, Point { x: 0, y: -2 }] }",
+        ] {
+            assert!(result.contains(answer), "{} not in {}", answer, result);
+        }
+
+        let mut cmd = Offset::new("r".into(), "r4".into(), -2, false);
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Can not offset "r": The offset shape would have no area left."#
+        );
+        let error = cmd.execute(&mut shapes).unwrap_err();
+        assert_eq!(crate::error::error_code(error.as_ref()), "validation_error");
+        assert_eq!(
+            Offset::new("r".into(), "r4".into(), 3, true).to_string(),
+            "offset r r4 3 round"
+        );
+    }
+}
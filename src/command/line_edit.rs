@@ -0,0 +1,367 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::shape::{
+    dependents_of, ensure_independent, metadata_memory, Line, Metadata, Point, Shape, Shapes,
+};
+use std::fmt;
+
+/// Tolerance on line parameters, so a line ending exactly on the boundary
+/// does not count as crossing it again.
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineEditKind {
+    /// Cut the line where it first crosses the boundary, keeping the part
+    /// that starts at its first point.
+    Trim { line: String, boundary: String },
+    /// Move the second point of the line forward until it meets the boundary.
+    Extend { line: String, boundary: String },
+    /// Replace the line by `<line>_1` and `<line>_2`, cut at the point on the
+    /// line closest to `at`.
+    Split { line: String, at: Point },
+    /// Merge two touching collinear lines into the first one.
+    Join { first: String, second: String },
+}
+
+/// The names of the shapes to remove and the lines to add, in that order.
+type Plan = (Vec<String>, Vec<(String, Line)>);
+
+/// CAD style editing of lines. Each edit removes some shapes and adds
+/// others in a single undoable step. Shapes already under the added names
+/// are only replaced when asked to.
+pub struct LineEdit {
+    kind: LineEditKind,
+    replace: bool,
+    /// The removed shapes, with their places in the drawing order.
+    removed: Vec<(String, Box<dyn Shape>, usize)>,
+    added: Vec<String>,
+    metadata: Vec<(String, Option<Metadata>)>,
+}
+
+impl LineEdit {
+    pub fn new(kind: LineEditKind) -> Self {
+        Self {
+            kind,
+            replace: false,
+            removed: Vec::new(),
+            added: Vec::new(),
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+
+    fn plan(&self, shapes: &Shapes) -> Result<Plan, Box<dyn Error>> {
+        let plan = match &self.kind {
+            LineEditKind::Trim { line, boundary } | LineEditKind::Extend { line, boundary } => {
+                let segment = get_line(shapes, line)?;
+                let crossings = shapes
+                    .get(boundary)
+                    .ok_or_else(|| ClevisError::ShapeNotFound(boundary.to_string()))?
+                    .crossings(&segment);
+                let t = match self.kind {
+                    LineEditKind::Trim { .. } => crossings
+                        .into_iter()
+                        .filter(|t| *t > EPSILON && *t < 1.0 - EPSILON)
+                        .reduce(f64::min),
+                    _ => crossings
+                        .into_iter()
+                        .filter(|t| *t > 1.0 + EPSILON)
+                        .reduce(f64::min),
+                };
+                let err_msg = format!("Line {:?} does not reach {:?}.", line, boundary);
+                let t = t.ok_or(ClevisError::Validation(err_msg))?;
+                let new_line = Line(segment.0, segment.point_at(t));
+                (vec![line.clone()], vec![(line.clone(), new_line)])
+            }
+            LineEditKind::Split { line, at } => {
+                let segment = get_line(shapes, line)?;
+                let t = segment.project(*at);
+                if t <= EPSILON || t >= 1.0 - EPSILON {
+                    let err_msg = format!("Point {:?} is not inside line {:?}.", at, line);
+                    return Err(ClevisError::Validation(err_msg).into());
+                }
+                let middle = segment.point_at(t);
+                (
+                    vec![line.clone()],
+                    vec![
+                        (format!("{}_1", line), Line(segment.0, middle)),
+                        (format!("{}_2", line), Line(middle, segment.1)),
+                    ],
+                )
+            }
+            LineEditKind::Join { first, second } => {
+                let a = get_line(shapes, first)?;
+                let b = get_line(shapes, second)?;
+                let cross = |p: Point| {
+                    (a.1.x - a.0.x) as i64 * (p.y - a.0.y) as i64
+                        - (a.1.y - a.0.y) as i64 * (p.x - a.0.x) as i64
+                };
+                let (t0, t1) = (a.project(b.0), a.project(b.1));
+                let err_msg = format!("Lines {:?} and {:?} can not be joined.", first, second);
+                if a.0 == a.1 || cross(b.0) != 0 || cross(b.1) != 0 {
+                    return Err(ClevisError::Validation(err_msg).into());
+                }
+                if t0.min(t1) > 1.0 + EPSILON || t0.max(t1) < -EPSILON {
+                    return Err(ClevisError::Validation(err_msg).into());
+                }
+                let start = if t0.min(t1) < 0.0 {
+                    if t0 < t1 {
+                        b.0
+                    } else {
+                        b.1
+                    }
+                } else {
+                    a.0
+                };
+                let end = if t0.max(t1) > 1.0 {
+                    if t0 > t1 {
+                        b.0
+                    } else {
+                        b.1
+                    }
+                } else {
+                    a.1
+                };
+                (
+                    vec![first.clone(), second.clone()],
+                    vec![(first.clone(), Line(start, end))],
+                )
+            }
+        };
+        Ok(plan)
+    }
+}
+
+fn get_line(shapes: &Shapes, name: &str) -> Result<Line, Box<dyn Error>> {
+    let shape = shapes
+        .get(name)
+        .ok_or_else(|| ClevisError::ShapeNotFound(name.to_string()))?;
+    ensure_independent(name, shape.as_ref())?;
+    let err_msg = format!("Shape {:?} is not a line.", name);
+    Ok(*shape.as_line().ok_or(ClevisError::Validation(err_msg))?)
+}
+
+impl fmt::Display for LineEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            LineEditKind::Trim { line, boundary } => write!(f, "trim {} {}", line, boundary),
+            LineEditKind::Extend { line, boundary } => write!(f, "extend {} {}", line, boundary),
+            LineEditKind::Split { line, at } => write!(f, "split {} {} {}", line, at.x, at.y),
+            LineEditKind::Join { first, second } => write!(f, "join {} {}", first, second),
+        }?;
+        if self.replace {
+            write!(f, " --replace")?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for LineEdit {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let (remove, add) = self.plan(shapes)?;
+        for name in remove.iter() {
+            // shapes that keep their name keep their dependents too
+            if add.iter().any(|(added, _)| added == name) {
+                continue;
+            }
+            let dependents = dependents_of(shapes, name);
+            if !dependents.is_empty() {
+                let err_msg = format!("Shape {:?} is referenced by {:?}.", name, dependents);
+                return Err(ClevisError::Validation(err_msg).into());
+            }
+        }
+        let replaced: Vec<&String> = (add.iter())
+            .map(|(name, _)| name)
+            .filter(|name| shapes.contains_key(*name) && !remove.contains(name))
+            .collect();
+        if let (false, Some(name)) = (self.replace, replaced.first()) {
+            return Err(ClevisError::NameConflict(name.to_string()).into());
+        }
+
+        self.removed = (remove.iter())
+            .chain(replaced)
+            .filter_map(|name| {
+                let (shape, rank) = shapes.take(name)?;
+                Some((name.clone(), shape, rank))
+            })
+            .collect();
+        self.added = add.iter().map(|(name, _)| name.clone()).collect();
+        // a line that keeps its name keeps its place in the drawing
+        for (name, line) in add {
+            match self.removed.iter().find(|(removed, ..)| *removed == name) {
+                Some((_, _, rank)) => shapes.insert_at(name, Box::new(line), *rank),
+                None => shapes.insert(name, Box::new(line)),
+            };
+        }
+
+        // the pieces of a split line keep its metadata
+        let involved: Vec<String> = remove.iter().chain(self.added.iter()).cloned().collect();
+        self.metadata = shapes.save_metadata(&involved);
+        if let LineEditKind::Split { line, .. } = &self.kind {
+            for name in self.added.iter() {
+                shapes.copy_metadata(line, name);
+            }
+        }
+        for name in remove.iter().filter(|name| !self.added.contains(name)) {
+            shapes.metadata.remove(name);
+        }
+
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        for name in self.added.drain(..) {
+            shapes.remove(&name);
+        }
+        for (name, shape, rank) in self.removed.drain(..) {
+            shapes.insert_at(name, shape, rank);
+        }
+        shapes.restore_metadata(std::mem::take(&mut self.metadata));
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let removed = (self.removed.iter()).map(|(name, shape, _)| name.len() + shape.memory());
+        let added = self.added.iter().map(|name| name.len());
+        std::mem::size_of_val(self)
+            + removed.sum::<usize>()
+            + added.sum::<usize>()
+            + metadata_memory(&self.metadata)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    fn line(x1: i32, y1: i32, x2: i32, y2: i32) -> Line {
+        Line(Point { x: x1, y: y1 }, Point { x: x2, y: y2 })
+    }
+
+    fn get_shapes() -> Shapes {
+        Shapes::from([
+            (
+                "l1".to_string(),
+                Box::new(line(0, 5, 20, 5)) as Box<dyn Shape>,
+            ),
+            (
+                "box".to_string(),
+                Box::new(Rectangle {
+                    corner: Point { x: 10, y: 0 },
+                    w: 20,
+                    h: 10,
+                    radius: 0,
+                }) as Box<dyn Shape>,
+            ),
+            (
+                "ring".to_string(),
+                Box::new(Circle {
+                    center: Point { x: 50, y: 5 },
+                    radius: 10,
+                }) as Box<dyn Shape>,
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_trim_extend() {
+        let mut shapes = get_shapes();
+
+        let trim = LineEditKind::Trim {
+            line: "l1".into(),
+            boundary: "box".into(),
+        };
+        let mut cmd = LineEdit::new(trim);
+        assert_eq!(cmd.to_string(), "trim l1 box");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes["l1"].as_line(), Some(&line(0, 5, 10, 5)));
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes["l1"].as_line(), Some(&line(0, 5, 20, 5)));
+
+        let extend = LineEditKind::Extend {
+            line: "l1".into(),
+            boundary: "ring".into(),
+        };
+        let mut cmd = LineEdit::new(extend);
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes["l1"].as_line(), Some(&line(0, 5, 40, 5)));
+
+        let extend = LineEditKind::Extend {
+            line: "l1".into(),
+            boundary: "box".into(),
+        };
+        assert_eq!(
+            LineEdit::new(extend)
+                .execute(&mut shapes)
+                .unwrap_err()
+                .to_string(),
+            r#"Line "l1" does not reach "box"."#
+        );
+    }
+
+    #[test]
+    fn test_split_join() {
+        let mut shapes = get_shapes();
+
+        let split = LineEditKind::Split {
+            line: "l1".into(),
+            at: Point { x: 8, y: 100 },
+        };
+        let mut cmd = LineEdit::new(split);
+        assert_eq!(cmd.to_string(), "split l1 8 100");
+        cmd.execute(&mut shapes).unwrap();
+        assert!(!shapes.contains_key("l1"));
+        assert_eq!(shapes["l1_1"].as_line(), Some(&line(0, 5, 8, 5)));
+        assert_eq!(shapes["l1_2"].as_line(), Some(&line(8, 5, 20, 5)));
+
+        let join = LineEditKind::Join {
+            first: "l1_2".into(),
+            second: "l1_1".into(),
+        };
+        let mut join = LineEdit::new(join);
+        assert_eq!(join.to_string(), "join l1_2 l1_1");
+        join.execute(&mut shapes).unwrap();
+        assert!(!shapes.contains_key("l1_1"));
+        assert_eq!(shapes["l1_2"].as_line(), Some(&line(0, 5, 20, 5)));
+
+        join.undo(&mut shapes).unwrap();
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 3);
+        assert_eq!(shapes["l1"].as_line(), Some(&line(0, 5, 20, 5)));
+
+        shapes.insert("l2".into(), Box::new(line(0, 6, 20, 6)));
+        let join = LineEditKind::Join {
+            first: "l1".into(),
+            second: "l2".into(),
+        };
+        assert!(LineEdit::new(join).execute(&mut shapes).is_err());
+
+        let split = LineEditKind::Split {
+            line: "l1".into(),
+            at: Point { x: 8, y: 5 },
+        };
+        shapes.insert("l1_2".into(), Box::new(line(1, 1, 2, 2)));
+        let mut cmd = LineEdit::new(split.clone());
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Shape "l1_2" already exists."#
+        );
+        let mut cmd = LineEdit::new(split).with_replace(true);
+        assert_eq!(cmd.to_string(), "split l1 8 5 --replace");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes["l1_2"].as_line(), Some(&line(8, 5, 20, 5)));
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes["l1_2"].as_line(), Some(&line(1, 1, 2, 2)));
+        assert_eq!(shapes["l1"].as_line(), Some(&line(0, 5, 20, 5)));
+    }
+}
@@ -0,0 +1,212 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::outcome::{quote, Outcome};
+use crate::shape::{Metadata, Shape, Shapes};
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ListFormat {
+    /// One line per shape, for people.
+    Text,
+    /// A JSON object per shape, for tools.
+    Json,
+}
+
+impl fmt::Display for ListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+fn describe(
+    name: &str,
+    shape: &dyn Shape,
+    metadata: Option<&Metadata>,
+    format: ListFormat,
+) -> String {
+    let params = shape.params();
+    let dependencies = shape.dependencies();
+    let empty = Metadata::default();
+    let metadata = metadata.unwrap_or(&empty);
+    match format {
+        ListFormat::Text => {
+            let mut line = format!("{} {}", name, shape.kind());
+            for (key, value) in params {
+                line += &format!(" {}={}", key, value);
+            }
+            if !dependencies.is_empty() {
+                line += &format!(" linked to {}", dependencies.join(" "));
+            }
+            for tag in metadata.tags.iter() {
+                line += &format!(" #{}", tag);
+            }
+            for (key, value) in metadata.values.iter() {
+                line += &format!(" {}:{}", key, value);
+            }
+            line
+        }
+        ListFormat::Json => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|(key, value)| format!("{}:{}", quote(key), value))
+                .collect();
+            let dependencies: Vec<String> = dependencies.iter().map(|name| quote(name)).collect();
+            let tags: Vec<String> = metadata.tags.iter().map(|tag| quote(tag)).collect();
+            let values: Vec<String> = metadata
+                .values
+                .iter()
+                .map(|(key, value)| format!("{}:{}", quote(key), quote(value)))
+                .collect();
+            format!(
+                r#"{{"name":{},"type":{},"params":{{{}}},"links":[{}],"tags":[{}],"meta":{{{}}}}}"#,
+                quote(name),
+                quote(shape.kind()),
+                params.join(","),
+                dependencies.join(","),
+                tags.join(","),
+                values.join(",")
+            )
+        }
+    }
+}
+
+/// Show the parameters and metadata of one shape, or of every shape when
+/// no name is given. Shapes are listed in drawing order, from the bottom
+/// up. The drawing has no groups of shapes, so the listing is flat; tags
+/// are the way to gather shapes. The JSON format answers with data instead
+/// of a message. Changes nothing, so it is not recorded for undo.
+pub struct List {
+    name: Option<String>,
+    format: ListFormat,
+    output: String,
+}
+
+impl List {
+    pub fn new(name: Option<String>, format: ListFormat) -> Self {
+        Self {
+            name,
+            format,
+            output: String::new(),
+        }
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "list {}", name)?,
+            None => write!(f, "listAll")?,
+        }
+        if self.format != ListFormat::Text {
+            write!(f, " --format {}", self.format)?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for List {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        self.output = match &self.name {
+            Some(name) => {
+                let shape = shapes
+                    .get(name)
+                    .ok_or_else(|| ClevisError::ShapeNotFound(name.to_string()))?;
+                describe(name, shape.as_ref(), shapes.metadata.get(name), self.format)
+            }
+            None => {
+                let lines: Vec<String> = (shapes.ordered().into_iter())
+                    .map(|(name, shape)| {
+                        describe(name, shape, shapes.metadata.get(name), self.format)
+                    })
+                    .collect();
+                match self.format {
+                    ListFormat::Text => lines.join("\n"),
+                    ListFormat::Json => format!("[{}]", lines.join(",")),
+                }
+            }
+        };
+        Ok(())
+    }
+    fn undo(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+    fn is_read_only(&self) -> bool {
+        true
+    }
+    fn outcome(&mut self) -> Outcome {
+        let output = std::mem::take(&mut self.output);
+        match self.format {
+            ListFormat::Text => Outcome::message(output),
+            ListFormat::Json => Outcome::default().with_data(output),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    #[test]
+    fn test_list() {
+        let mut shapes = Shapes::from([
+            (
+                "c".to_string(),
+                Box::new(Circle {
+                    center: Point { x: 1, y: 2 },
+                    radius: 3,
+                }) as Box<dyn Shape>,
+            ),
+            (
+                "a".to_string(),
+                Box::new(Point { x: -1, y: 0 }) as Box<dyn Shape>,
+            ),
+        ]);
+        let mut metadata = Metadata::default();
+        metadata.tags.insert("pad".into());
+        metadata.values.insert("note".into(), "say \"hi\"".into());
+        shapes.metadata.insert("c".into(), metadata);
+
+        let mut cmd = List::new(Some("c".into()), ListFormat::Text);
+        assert_eq!(cmd.to_string(), "list c");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(
+            cmd.output,
+            r#"c circle x=1 y=2 radius=3 #pad note:say "hi""#
+        );
+
+        // in drawing order, not by name
+        let mut cmd = List::new(None, ListFormat::Text);
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(cmd.output.lines().nth(1), Some("a point x=-1 y=0"));
+
+        let mut cmd = List::new(None, ListFormat::Json);
+        assert_eq!(cmd.to_string(), "listAll --format json");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(
+            cmd.outcome().data.unwrap(),
+            [
+                r#"[{"name":"c","type":"circle","params":{"x":1,"y":2,"radius":3},"links":[],"#,
+                r#""tags":["pad"],"meta":{"note":"say \"hi\""}},"#,
+                r#"{"name":"a","type":"point","params":{"x":-1,"y":0},"links":[],"tags":[],"meta":{}}]"#
+            ]
+            .concat()
+        );
+
+        let mut cmd = List::new(Some("d".into()), ListFormat::Json);
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Shape "d" is not found."#
+        );
+    }
+}
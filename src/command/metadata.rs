@@ -0,0 +1,163 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::shape::{Metadata, Shapes};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataEdit {
+    Tag(Vec<String>),
+    Untag(Vec<String>),
+    /// Set each key to its value, an empty value removes the key.
+    Set(Vec<(String, String)>),
+}
+
+/// Change the tags or key/value metadata of a shape.
+pub struct EditMetadata {
+    name: String,
+    edit: MetadataEdit,
+    previous: Option<Metadata>,
+}
+
+impl EditMetadata {
+    pub fn new(name: String, edit: MetadataEdit) -> Self {
+        Self {
+            name,
+            edit,
+            previous: None,
+        }
+    }
+}
+
+impl fmt::Display for EditMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.edit {
+            MetadataEdit::Tag(tags) => write!(f, "tag {} {}", self.name, tags.join(" ")),
+            MetadataEdit::Untag(tags) => write!(f, "untag {} {}", self.name, tags.join(" ")),
+            MetadataEdit::Set(pairs) => {
+                write!(f, "meta {}", self.name)?;
+                for (key, value) in pairs {
+                    write!(f, " {}={}", key, value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Command for EditMetadata {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        if !shapes.contains_key(&self.name) {
+            return Err(ClevisError::ShapeNotFound(self.name.clone()).into());
+        }
+        let mut metadata = shapes.metadata.get(&self.name).cloned().unwrap_or_default();
+        match &self.edit {
+            MetadataEdit::Tag(tags) => metadata.tags.extend(tags.iter().cloned()),
+            MetadataEdit::Untag(tags) => {
+                for tag in tags {
+                    if !metadata.tags.remove(tag) {
+                        let err_msg = format!("Shape {:?} has no tag {:?}.", self.name, tag);
+                        return Err(ClevisError::Validation(err_msg).into());
+                    }
+                }
+            }
+            MetadataEdit::Set(pairs) => {
+                for (key, value) in pairs {
+                    if value.is_empty() {
+                        metadata.values.remove(key);
+                    } else {
+                        metadata.values.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+
+        self.previous = if metadata.is_empty() {
+            shapes.metadata.remove(&self.name)
+        } else {
+            shapes.metadata.insert(self.name.clone(), metadata)
+        };
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        match self.previous.take() {
+            Some(metadata) => shapes.metadata.insert(self.name.clone(), metadata),
+            None => shapes.metadata.remove(&self.name),
+        };
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let previous = self
+            .previous
+            .as_ref()
+            .map_or(0, |metadata| metadata.memory());
+        std::mem::size_of_val(self) + self.name.len() + previous
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::command::{Array, ArrayLayout, Delete};
+    use crate::shape::*;
+
+    #[test]
+    fn test_edit_metadata() {
+        let mut shapes = Shapes::from([(
+            "p".to_string(),
+            Box::new(Point::default()) as Box<dyn Shape>,
+        )]);
+        let tags = |shapes: &Shapes, name: &str| -> Vec<String> {
+            shapes.metadata.get(name).map_or(Vec::new(), |metadata| {
+                metadata.tags.iter().cloned().collect()
+            })
+        };
+
+        let mut tag =
+            EditMetadata::new("p".into(), MetadataEdit::Tag(vec!["b".into(), "a".into()]));
+        assert_eq!(tag.to_string(), "tag p b a");
+        tag.execute(&mut shapes).unwrap();
+        assert_eq!(tags(&shapes, "p"), ["a", "b"]);
+
+        let pairs = vec![
+            ("part".into(), "A-113".into()),
+            ("owner".into(), "kim".into()),
+        ];
+        let mut meta = EditMetadata::new("p".into(), MetadataEdit::Set(pairs));
+        assert_eq!(meta.to_string(), "meta p part=A-113 owner=kim");
+        meta.execute(&mut shapes).unwrap();
+        assert_eq!(shapes.metadata["p"].values["owner"], "kim");
+
+        let mut untag = EditMetadata::new("p".into(), MetadataEdit::Untag(vec!["c".into()]));
+        assert_eq!(
+            untag.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Shape "p" has no tag "c"."#
+        );
+
+        // copies and deletions carry the metadata along
+        let layout = ArrayLayout::Rect {
+            rows: 1,
+            cols: 2,
+            dx: 5,
+            dy: 0,
+        };
+        Array::new("p".into(), layout).execute(&mut shapes).unwrap();
+        assert_eq!(shapes.metadata["p_0_1"], shapes.metadata["p"]);
+        let mut delete = Delete::new("p".into());
+        delete.execute(&mut shapes).unwrap();
+        assert!(!shapes.metadata.contains_key("p"));
+        delete.undo(&mut shapes).unwrap();
+        assert_eq!(shapes.metadata["p"].values["part"], "A-113");
+
+        meta.undo(&mut shapes).unwrap();
+        assert!(shapes.metadata["p"].values.is_empty());
+        tag.undo(&mut shapes).unwrap();
+        assert!(!shapes.metadata.contains_key("p"));
+    }
+}
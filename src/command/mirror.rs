@@ -0,0 +1,161 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::shape::{ensure_independent, metadata_memory, Line, Metadata, Shape, Shapes};
+use std::fmt;
+
+/// Reflect a shape across an arbitrary line into a new shape. Rectangles and
+/// squares mirrored across a slanted line become polygons. Like drawing, a
+/// taken name is only replaced when asked to.
+pub struct Mirror {
+    name: String,
+    new_name: String,
+    axis: Line,
+    replace: bool,
+    replaced: Option<Box<dyn Shape>>,
+    metadata: Vec<(String, Option<Metadata>)>,
+}
+
+impl Mirror {
+    pub fn new(name: String, new_name: String, axis: Line) -> Self {
+        Self {
+            name,
+            new_name,
+            axis,
+            replace: false,
+            replaced: None,
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn with_replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+}
+
+impl fmt::Display for Mirror {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mirror {} {} {} {} {} {}",
+            self.name, self.new_name, self.axis.0.x, self.axis.0.y, self.axis.1.x, self.axis.1.y
+        )?;
+        if self.replace {
+            write!(f, " --replace")?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for Mirror {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        if self.axis.0 == self.axis.1 {
+            let err_msg = "The mirror line needs two different points.";
+            return Err(ClevisError::validation(err_msg).into());
+        }
+        if !self.replace && shapes.contains_key(&self.new_name) {
+            return Err(ClevisError::NameConflict(self.new_name.clone()).into());
+        }
+        let source = shapes
+            .get(&self.name)
+            .ok_or_else(|| ClevisError::ShapeNotFound(self.name.clone()))?;
+        ensure_independent(&self.name, source.as_ref())?;
+        let shape = source.mirrored(&self.axis);
+        self.replaced = shapes.insert(self.new_name.clone(), shape);
+        self.metadata = shapes.save_metadata(std::slice::from_ref(&self.new_name));
+        shapes.copy_metadata(&self.name, &self.new_name);
+
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        match self.replaced.take() {
+            Some(shape) => shapes.insert(self.new_name.clone(), shape),
+            None => shapes.remove(&self.new_name),
+        };
+        shapes.restore_metadata(std::mem::take(&mut self.metadata));
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let replaced = self.replaced.as_ref().map_or(0, |shape| shape.memory());
+        std::mem::size_of_val(self)
+            + self.name.len()
+            + self.new_name.len()
+            + replaced
+            + metadata_memory(&self.metadata)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    #[test]
+    fn test_mirror() {
+        let mut shapes = Shapes::from([(
+            "sq".to_string(),
+            Box::new(Square {
+                corner: Point { x: 2, y: 0 },
+                side: 2,
+                radius: 0,
+            }) as Box<dyn Shape>,
+        )]);
+
+        // mirror across the diagonal y = x
+        let axis = Line(Point { x: 0, y: 0 }, Point { x: 1, y: 1 });
+        let mut cmd = Mirror::new("sq".into(), "sq2".into(), axis);
+        assert_eq!(cmd.to_string(), "mirror sq sq2 0 0 1 1");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(
+            format!("{:?}", shapes["sq2"]),
+            "Square { corner: Point { x: 0, y: 2 }, side: 2, radius: 0 }"
+        );
+        assert_eq!(
+            format!("{:?}", shapes["sq"]),
+            "Square { corner: Point { x: 2, y: 0 }, side: 2, radius: 0 }"
+        );
+
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes.len(), 1);
+
+        let axis = Line(Point { x: 1, y: 1 }, Point { x: 1, y: 1 });
+        let mut cmd = Mirror::new("sq".into(), "sq2".into(), axis);
+        assert!(cmd.execute(&mut shapes).is_err());
+
+        // mirror in place
+        let axis = Line(Point { x: 0, y: 0 }, Point { x: 0, y: 1 });
+        let mut cmd = Mirror::new("sq".into(), "sq".into(), axis);
+        assert_eq!(
+            cmd.execute(&mut shapes).unwrap_err().to_string(),
+            r#"Shape "sq" already exists."#
+        );
+        let mut cmd = cmd.with_replace(true);
+        assert_eq!(cmd.to_string(), "mirror sq sq 0 0 0 1 --replace");
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes["sq"].center(), Point { x: -3, y: 1 });
+        cmd.undo(&mut shapes).unwrap();
+        assert_eq!(shapes["sq"].center(), Point { x: 3, y: 1 });
+
+        // a slanted axis turns the square into a polygon
+        let axis = Line(Point { x: 0, y: 0 }, Point { x: 2, y: 1 });
+        let mut cmd = Mirror::new("sq".into(), "sq3".into(), axis);
+        cmd.execute(&mut shapes).unwrap();
+        assert_eq!(shapes["sq3"].kind(), "polygon");
+        assert_eq!(
+            shapes["sq3"].outline(),
+            [
+                Point { x: 1, y: 2 },
+                Point { x: 2, y: 3 },
+                Point { x: 4, y: 2 },
+                Point { x: 3, y: 0 }
+            ]
+        );
+    }
+}
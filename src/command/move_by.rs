@@ -1,6 +1,7 @@
 use super::{Command, Error, Executor};
+use crate::error::ClevisError;
 use crate::shape::DataType;
-use crate::shape::Shapes;
+use crate::shape::{ensure_independent, Shapes};
 use std::fmt;
 
 #[derive(Clone)]
@@ -21,15 +22,18 @@ impl fmt::Display for MoveBy {
 }
 impl Command for MoveBy {
     fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
-        let err_msg = format!("Shape {:?} is not found.", self.name);
-        let shape = shapes.get_mut(&self.name).ok_or(err_msg)?;
+        let shape = shapes
+            .get_mut(&self.name)
+            .ok_or_else(|| ClevisError::ShapeNotFound(self.name.clone()))?;
+        ensure_independent(&self.name, shape.as_ref())?;
         shape.move_by(self.dx, self.dy);
 
         Ok(())
     }
     fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
-        let err_msg = format!("Shape {:?} is not found.", self.name);
-        let shape = shapes.get_mut(&self.name).ok_or(err_msg)?;
+        let shape = shapes
+            .get_mut(&self.name)
+            .ok_or_else(|| ClevisError::ShapeNotFound(self.name.clone()))?;
         shape.move_by(-self.dx, -self.dy);
 
         Ok(())
@@ -41,6 +45,19 @@ impl Command for MoveBy {
     ) -> Result<bool, Box<dyn Error>> {
         Ok(true)
     }
+    fn coalesce(&mut self, next: &dyn Command) -> bool {
+        match next.as_move_by() {
+            Some(next) if next.name == self.name => {
+                self.dx += next.dx;
+                self.dy += next.dy;
+                true
+            }
+            _ => false,
+        }
+    }
+    fn as_move_by(&self) -> Option<&MoveBy> {
+        Some(self)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,242 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::outcome::{Journaled, Outcome};
+use crate::scene::{self, SceneFormat};
+use crate::shape::Shapes;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Write the whole drawing into a scene file. The shapes are left alone,
+/// so this is not recorded for undo.
+pub struct Save {
+    path: PathBuf,
+    format: SceneFormat,
+    count: usize,
+}
+
+impl Save {
+    pub fn new(path: PathBuf, format: SceneFormat) -> Self {
+        Self {
+            path,
+            format,
+            count: 0,
+        }
+    }
+}
+
+impl fmt::Display for Save {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "save ")?;
+        if self.format == SceneFormat::Binary {
+            write!(f, "--binary ")?;
+        }
+        write!(f, "{}", self.path.display())
+    }
+}
+
+impl Command for Save {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        scene::save(shapes, &self.path, self.format)?;
+        self.count = shapes.len();
+        Ok(())
+    }
+    fn undo(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+    fn is_read_only(&self) -> bool {
+        true
+    }
+    /// Saving again on recovery would overwrite whatever the file holds by
+    /// then, and the drawing does not need it.
+    fn outcome(&mut self) -> Outcome {
+        Outcome::message(format!(
+            "Saved {} shapes to {}.",
+            self.count,
+            self.path.display()
+        ))
+        .with_journal(Journaled::Nothing)
+    }
+}
+
+/// Replace the drawing by the scene in a file, text or binary, undone as a
+/// single step. The file is read once, redo puts back the scene as it was
+/// loaded.
+pub struct Load {
+    path: PathBuf,
+    count: usize,
+    /// A scene given up front, loaded instead of reading the file.
+    given: Option<Shapes>,
+    /// The scene that is not shown: the loaded one before `execute`, the
+    /// replaced one after.
+    other: Option<Shapes>,
+    /// The memory of the shown scene and of `other`, measured once as both
+    /// can be large.
+    memory: (usize, usize),
+}
+
+impl Load {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            count: 0,
+            given: None,
+            other: None,
+            memory: (0, 0),
+        }
+    }
+
+    /// Load `scene` as if it was read from `path`, as when a journal is
+    /// replayed.
+    pub fn with_scene(path: PathBuf, scene: Shapes) -> Self {
+        Self {
+            given: Some(scene),
+            ..Self::new(path)
+        }
+    }
+}
+
+impl fmt::Display for Load {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "load {}", self.path.display())
+    }
+}
+
+impl Command for Load {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let scene = match self.other.take() {
+            Some(scene) => scene,
+            None => {
+                let scene = match self.given.take() {
+                    Some(scene) => scene,
+                    None => scene::load(&self.path)?,
+                };
+                self.memory = (shapes.memory(), scene.memory());
+                scene
+            }
+        };
+        self.count = scene.len();
+        self.other = Some(std::mem::replace(shapes, scene));
+        self.memory = (self.memory.1, self.memory.0);
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let previous = self
+            .other
+            .take()
+            .ok_or_else(|| ClevisError::validation("No scene was loaded."))?;
+        self.other = Some(std::mem::replace(shapes, previous));
+        self.memory = (self.memory.1, self.memory.0);
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn outcome(&mut self) -> Outcome {
+        Outcome::message(format!(
+            "Loaded {} shapes from {}.",
+            self.count,
+            self.path.display()
+        ))
+        .with_journal(Journaled::Scene {
+            path: self.path.clone(),
+        })
+    }
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.memory.1
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::journal::tests::temp_path;
+    use crate::shape::*;
+
+    #[test]
+    fn test_save_load() {
+        let path = temp_path("test_save_load");
+        let mut saved = Shapes::from([(
+            "a".to_string(),
+            Box::new(Point { x: 1, y: 2 }) as Box<dyn Shape>,
+        )]);
+        saved
+            .metadata
+            .entry("a".into())
+            .or_default()
+            .tags
+            .insert("pad".into());
+        let mut save = Save::new(path.clone(), SceneFormat::Text);
+        assert_eq!(save.to_string(), format!("save {}", path.display()));
+        save.execute(&mut saved).unwrap();
+        assert_eq!(
+            save.outcome().message.unwrap(),
+            format!("Saved 1 shapes to {}.", path.display())
+        );
+
+        let mut shapes = Shapes::from([
+            (
+                "b".to_string(),
+                Box::new(Point::default()) as Box<dyn Shape>,
+            ),
+            (
+                "c".to_string(),
+                Box::new(Point::default()) as Box<dyn Shape>,
+            ),
+        ]);
+        let mut load = Load::new(path.clone());
+        load.execute(&mut shapes).unwrap();
+        let scene = scene::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(shapes.keys().collect::<Vec<_>>(), ["a"]);
+        assert_eq!(shapes.metadata, saved.metadata);
+        assert!(load.memory() > 2 * std::mem::size_of::<Point>());
+        // the journal keeps its own copy of the file
+        assert_eq!(
+            load.outcome().journal,
+            Some(Journaled::Scene { path: path.clone() })
+        );
+
+        load.undo(&mut shapes).unwrap();
+        let mut names: Vec<&String> = shapes.keys().collect();
+        names.sort();
+        assert_eq!(names, ["b", "c"]);
+        assert!(shapes.metadata.is_empty());
+
+        // redo does not need the file any more
+        load.execute(&mut shapes).unwrap();
+        assert_eq!(shapes.keys().collect::<Vec<_>>(), ["a"]);
+
+        let mut load = Load::new(path.clone());
+        assert!(load.execute(&mut shapes).is_err());
+        assert_eq!(shapes.keys().collect::<Vec<_>>(), ["a"]);
+        let mut shapes = Shapes::new();
+        let mut load = Load::with_scene(path.clone(), scene);
+        load.execute(&mut shapes).unwrap();
+        assert_eq!(shapes.keys().collect::<Vec<_>>(), ["a"]);
+        assert_eq!(shapes.metadata, saved.metadata);
+
+        // binary scenes are told apart by their first bytes
+        let mut save = Save::new(path.clone(), SceneFormat::Binary);
+        assert_eq!(
+            save.to_string(),
+            format!("save --binary {}", path.display())
+        );
+        save.execute(&mut saved).unwrap();
+        let mut load = Load::new(path.clone());
+        load.execute(&mut shapes).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(shapes.keys().collect::<Vec<_>>(), ["a"]);
+        assert_eq!(shapes.metadata, saved.metadata);
+    }
+}
@@ -0,0 +1,237 @@
+use super::{Command, Error, Executor};
+use crate::error::ClevisError;
+use crate::selector::{Selection, Selector};
+use crate::shape::{Point, Rectangle, Shapes};
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub enum SelectMode {
+    /// Select exactly the matching shapes.
+    Set(Vec<Selector>),
+    /// Add the matching shapes to the selection.
+    Add(Vec<Selector>),
+    Clear,
+    /// Select the shapes whose outline lies inside or passes through a
+    /// region.
+    Rect(Rectangle),
+}
+
+/// Change the selection. The shapes are left alone, so this is not
+/// recorded for undo.
+pub struct Select {
+    mode: SelectMode,
+}
+
+impl Select {
+    pub fn new(mode: SelectMode) -> Self {
+        Self { mode }
+    }
+}
+
+fn resolve_all(selectors: &[Selector], shapes: &Shapes) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut names = Vec::new();
+    for selector in selectors {
+        for name in selector.resolve(shapes)? {
+            if !shapes.contains_key(&name) {
+                return Err(ClevisError::ShapeNotFound(name.clone()).into());
+            }
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// Whether the segment from `a` to `b` reaches into the normalized
+/// rectangle `region`, by clipping it to the region one edge at a time.
+fn segment_touches(a: Point, b: Point, region: &Rectangle) -> bool {
+    let (dx, dy) = ((b.x - a.x) as f64, (b.y - a.y) as f64);
+    let (left, top) = (region.corner.x as f64, region.corner.y as f64);
+    let (right, bottom) = (left + region.w as f64, top + region.h as f64);
+    let (x, y) = (a.x as f64, a.y as f64);
+    // the part of the segment, as parameters from 0 to 1, inside each edge
+    let edges = [
+        (-dx, x - left),
+        (dx, right - x),
+        (-dy, y - top),
+        (dy, bottom - y),
+    ];
+    let (mut start, mut end) = (0.0_f64, 1.0_f64);
+    for (p, q) in edges {
+        if p == 0.0 {
+            if q < 0.0 {
+                return false;
+            }
+        } else if p < 0.0 {
+            start = start.max(q / p);
+        } else {
+            end = end.min(q / p);
+        }
+    }
+    start <= end
+}
+
+/// Whether the outline of a shape, closed if it has an area, reaches into
+/// the normalized rectangle `region`.
+fn outline_touches(outline: &[Point], region: &Rectangle) -> bool {
+    match outline.len() {
+        0 => false,
+        1 => segment_touches(outline[0], outline[0], region),
+        2 => segment_touches(outline[0], outline[1], region),
+        len => (0..len).any(|i| segment_touches(outline[i], outline[(i + 1) % len], region)),
+    }
+}
+
+fn join(selectors: &[Selector]) -> String {
+    let selectors: Vec<String> = selectors.iter().map(|s| s.to_string()).collect();
+    selectors.join(" ")
+}
+
+impl fmt::Display for Select {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.mode {
+            SelectMode::Set(selectors) => write!(f, "select {}", join(selectors)),
+            SelectMode::Add(selectors) => write!(f, "select-add {}", join(selectors)),
+            SelectMode::Clear => write!(f, "select-clear"),
+            SelectMode::Rect(rect) => write!(
+                f,
+                "select-rect {} {} {} {}",
+                rect.corner.x, rect.corner.y, rect.w, rect.h
+            ),
+        }
+    }
+}
+
+impl Command for Select {
+    fn execute(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    fn undo(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+    fn is_read_only(&self) -> bool {
+        true
+    }
+    fn select(&mut self, selection: &mut Selection, shapes: &Shapes) -> Result<(), Box<dyn Error>> {
+        match &self.mode {
+            SelectMode::Set(selectors) => {
+                *selection = resolve_all(selectors, shapes)?.into_iter().collect()
+            }
+            SelectMode::Add(selectors) => selection.extend(resolve_all(selectors, shapes)?),
+            SelectMode::Clear => selection.clear(),
+            SelectMode::Rect(rect) => {
+                let region = rect.normalized();
+                *selection = shapes
+                    .iter()
+                    .filter(|(_, shape)| outline_touches(&shape.outline(), &region))
+                    .map(|(name, _)| name.clone())
+                    .collect();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    #[test]
+    fn test_select() {
+        let shapes = Shapes::from([
+            (
+                "a".to_string(),
+                Box::new(Point { x: 0, y: 0 }) as Box<dyn Shape>,
+            ),
+            (
+                "b".to_string(),
+                Box::new(Point { x: 10, y: 0 }) as Box<dyn Shape>,
+            ),
+            (
+                "c".to_string(),
+                Box::new(Circle {
+                    center: Point { x: 20, y: 20 },
+                    radius: 5,
+                }) as Box<dyn Shape>,
+            ),
+        ]);
+        let mut selection = Selection::new();
+        let selectors = |names: &[&str]| -> Vec<Selector> {
+            names
+                .iter()
+                .map(|name| Selector::parse(name).unwrap())
+                .collect()
+        };
+
+        let mut cmd = Select::new(SelectMode::Set(selectors(&["a"])));
+        cmd.select(&mut selection, &shapes).unwrap();
+        assert_eq!(Vec::from_iter(selection.iter()), ["a"]);
+
+        let mut cmd = Select::new(SelectMode::Add(selectors(&["?"])));
+        assert_eq!(cmd.to_string(), "select-add ?");
+        cmd.select(&mut selection, &shapes).unwrap();
+        assert_eq!(selection.len(), 3);
+
+        let mut cmd = Select::new(SelectMode::Set(selectors(&["d"])));
+        assert_eq!(
+            cmd.select(&mut selection, &shapes).unwrap_err().to_string(),
+            r#"Shape "d" is not found."#
+        );
+        assert_eq!(selection.len(), 3);
+
+        // the circle reaches into the region, the points do not
+        let mut cmd = Select::new(SelectMode::Rect(Rectangle {
+            corner: Point { x: 30, y: 30 },
+            w: -14,
+            h: -14,
+            radius: 0,
+        }));
+        assert_eq!(cmd.to_string(), "select-rect 30 30 -14 -14");
+        cmd.select(&mut selection, &shapes).unwrap();
+        assert_eq!(Vec::from_iter(selection.iter()), ["c"]);
+
+        // the bounding boxes reach into the region, the outlines do not
+        let mut shapes = shapes;
+        shapes.insert(
+            "l".into(),
+            Box::new(Line(Point { x: 0, y: 0 }, Point { x: 10, y: 10 })),
+        );
+        let mut cmd = Select::new(SelectMode::Rect(Rectangle {
+            corner: Point { x: 7, y: 1 },
+            w: 2,
+            h: 1,
+            radius: 0,
+        }));
+        cmd.select(&mut selection, &shapes).unwrap();
+        assert!(selection.is_empty());
+        let mut cmd = Select::new(SelectMode::Rect(Rectangle {
+            corner: Point { x: 25, y: 25 },
+            w: 1,
+            h: 1,
+            radius: 0,
+        }));
+        cmd.select(&mut selection, &shapes).unwrap();
+        assert!(selection.is_empty());
+        // a segment crossing the region with both ends outside
+        let mut cmd = Select::new(SelectMode::Rect(Rectangle {
+            corner: Point { x: 4, y: 3 },
+            w: 2,
+            h: 4,
+            radius: 0,
+        }));
+        cmd.select(&mut selection, &shapes).unwrap();
+        assert_eq!(Vec::from_iter(selection.iter()), ["l"]);
+
+        Select::new(SelectMode::Clear)
+            .select(&mut selection, &shapes)
+            .unwrap();
+        assert!(selection.is_empty());
+    }
+}
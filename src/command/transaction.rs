@@ -0,0 +1,135 @@
+use super::{Command, Error, Executor};
+use crate::executor::ExecutionError;
+use crate::outcome::Outcome;
+use crate::shape::Shapes;
+use std::fmt;
+
+/// The commands of a committed transaction, undone and redone as one step.
+pub struct Batch {
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl Batch {
+    pub fn new(commands: Vec<Box<dyn Command>>) -> Self {
+        Self { commands }
+    }
+
+    pub fn into_commands(self) -> Vec<Box<dyn Command>> {
+        self.commands
+    }
+}
+
+impl fmt::Display for Batch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "begin")?;
+        for cmd in self.commands.iter() {
+            write!(f, "; {}", cmd)?;
+        }
+        write!(f, "; commit")
+    }
+}
+
+impl Command for Batch {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        for i in 0..self.commands.len() {
+            if let Err(error) = self.commands[i].execute(shapes) {
+                for cmd in self.commands[..i].iter_mut().rev() {
+                    if let Err(restore) = cmd.undo(shapes) {
+                        return Err(ExecutionError::Unrestored { error, restore }.into());
+                    }
+                }
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        for i in (0..self.commands.len()).rev() {
+            if let Err(error) = self.commands[i].undo(shapes) {
+                // redo the ones already undone, so the batch stays whole
+                for cmd in self.commands[i + 1..].iter_mut() {
+                    if let Err(restore) = cmd.execute(shapes) {
+                        return Err(ExecutionError::Unrestored { error, restore }.into());
+                    }
+                }
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        _executor: &mut Executor,
+        _shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+    fn memory(&self) -> usize {
+        let commands = self.commands.iter().map(|cmd| cmd.memory());
+        std::mem::size_of_val(self) + commands.sum::<usize>()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransactionKind {
+    Begin,
+    Commit,
+    Rollback,
+}
+
+/// Open, commit or roll back the transaction of the executor.
+pub struct Transaction {
+    kind: TransactionKind,
+    outcome: Outcome,
+}
+
+impl Transaction {
+    pub fn new(kind: TransactionKind) -> Self {
+        Self {
+            kind,
+            outcome: Outcome::default(),
+        }
+    }
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self.kind {
+                TransactionKind::Begin => "begin",
+                TransactionKind::Commit => "commit",
+                TransactionKind::Rollback => "rollback",
+            }
+        )
+    }
+}
+
+impl Command for Transaction {
+    fn execute(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    fn undo(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        executor: &mut Executor,
+        shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        self.outcome = match self.kind {
+            TransactionKind::Begin => executor.begin()?,
+            TransactionKind::Commit => executor.commit()?,
+            TransactionKind::Rollback => executor.rollback(shapes)?,
+        };
+
+        Ok(false)
+    }
+    fn is_read_only(&self) -> bool {
+        true
+    }
+    fn outcome(&mut self) -> Outcome {
+        std::mem::take(&mut self.outcome)
+    }
+}
@@ -1,21 +1,52 @@
 use super::{Command, Error, Executor};
+use crate::outcome::Outcome;
 use crate::shape::Shapes;
 use std::fmt;
 
-pub enum Control {
-    Redo,
-    Undo,
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ControlKind {
+    /// Redo this many commands.
+    Redo(usize),
+    /// Undo this many commands.
+    Undo(usize),
+    RedoAll,
+    /// Undo or redo until the command with this history number is the
+    /// last one applied.
+    UndoTo(usize),
+    History,
+    HistoryTree,
+    BranchList,
+    BranchSwitch(usize),
 }
+
+pub struct Control {
+    kind: ControlKind,
+    outcome: Outcome,
+}
+
+impl Control {
+    pub fn new(kind: ControlKind) -> Self {
+        Self {
+            kind,
+            outcome: Outcome::default(),
+        }
+    }
+}
+
 impl fmt::Display for Control {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match *self {
-                Self::Redo => "redo",
-                Self::Undo => "undo",
-            }
-        )
+        match self.kind {
+            ControlKind::Redo(1) => write!(f, "redo"),
+            ControlKind::Redo(count) => write!(f, "redo {}", count),
+            ControlKind::Undo(1) => write!(f, "undo"),
+            ControlKind::Undo(count) => write!(f, "undo {}", count),
+            ControlKind::RedoAll => write!(f, "redo all"),
+            ControlKind::UndoTo(index) => write!(f, "undo-to {}", index),
+            ControlKind::History => write!(f, "history"),
+            ControlKind::HistoryTree => write!(f, "history tree"),
+            ControlKind::BranchList => write!(f, "branch list"),
+            ControlKind::BranchSwitch(id) => write!(f, "branch switch {}", id),
+        }
     }
 }
 impl Command for Control {
@@ -23,18 +54,31 @@ impl Command for Control {
         Ok(())
     }
     fn undo(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
-        unimplemented!()
+        // controls are never recorded, so there is nothing to take back
+        Ok(())
     }
     fn after_execute(
         &mut self,
         executor: &mut Executor,
         shapes: &mut Shapes,
     ) -> Result<bool, Box<dyn Error>> {
-        match self {
-            Control::Redo => executor.redo(shapes)?,
-            Control::Undo => executor.undo(shapes)?,
+        self.outcome = match self.kind {
+            ControlKind::Redo(count) => executor.redo_many(count, shapes)?,
+            ControlKind::Undo(count) => executor.undo_many(count, shapes)?,
+            ControlKind::RedoAll => executor.redo_many(executor.undone.len(), shapes)?,
+            ControlKind::UndoTo(index) => executor.undo_to(index, shapes)?,
+            ControlKind::History => executor.history(),
+            ControlKind::HistoryTree => executor.history_tree(),
+            ControlKind::BranchList => executor.branch_list(),
+            ControlKind::BranchSwitch(id) => executor.switch_branch(id, shapes)?,
         };
 
         Ok(false)
     }
+    fn is_read_only(&self) -> bool {
+        true
+    }
+    fn outcome(&mut self) -> Outcome {
+        std::mem::take(&mut self.outcome)
+    }
 }
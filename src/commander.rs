@@ -1,9 +1,45 @@
 use crate::command::Command;
+use crate::outcome::Outcome;
+use std::error::Error;
 
+/// A source of commands.
 pub trait Commander: IntoIterator<Item = Box<dyn Command>> {}
 
 impl<T> Commander for T where T: IntoIterator<Item = Box<dyn Command>> {}
 
+/// Hears back how each command it gave out went.
+pub trait Responder {
+    /// Called with the result of every command. Logs it by default.
+    fn respond(&mut self, result: Result<&Outcome, &(dyn Error + 'static)>) {
+        match result {
+            Ok(outcome) => {
+                if let Some(message) = &outcome.message {
+                    log::info!("{}", message);
+                }
+                for warning in outcome.warnings.iter() {
+                    log::warn!("{}", warning);
+                }
+            }
+            Err(error) => log::error!("{}", error),
+        }
+    }
+}
+
+/// The commands of a plain commander, answered in the log.
+pub struct Logged<I>(pub I);
+
+impl<I> Iterator for Logged<I>
+where
+    I: Iterator<Item = Box<dyn Command>>,
+{
+    type Item = Box<dyn Command>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl<I> Responder for Logged<I> {}
+
 pub mod cli_commander;
 pub use cli_commander::CliCommander;
 
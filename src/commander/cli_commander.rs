@@ -1,11 +1,18 @@
 use super::*;
+use crate::command::Load;
+use crate::error::ClevisError;
+use crate::executor::ExecutionError;
+use crate::journal::{Entry, Journal};
+use crate::outcome::{error_json, Journaled, Outcome};
+use crate::scene;
 use crate::shape::*;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
 use std::io;
 use std::io::{BufRead, Write};
+use std::path::Path;
 
 pub struct CliCommander<Reader, Stdout, Stderr>
 where
@@ -18,6 +25,16 @@ where
     stdout: Stdout,
     stderr: Stderr,
     parse_fn: HashMap<String, ParseFn>,
+    // answer with a JSON line per command instead of text
+    json: bool,
+    journal: Option<Journal>,
+    // entries of a crashed session, run before the reader
+    replay: VecDeque<Entry>,
+    replaying: bool,
+    // a line run first, as if it was typed
+    opening: Option<String>,
+    // the line of the command given out, journaled once it succeeds
+    pending: Option<String>,
 }
 
 type ParseFn = fn(&str) -> Result<Box<dyn Command>, Box<dyn Error>>;
@@ -39,6 +56,12 @@ where
             stdout,
             stderr,
             parse_fn: HashMap::new(),
+            json: false,
+            journal: None,
+            replay: VecDeque::new(),
+            replaying: false,
+            opening: None,
+            pending: None,
         };
         this.register_parse_fn();
 
@@ -51,11 +74,46 @@ where
         self.register_parser("line".to_lowercase(), parse_cmd::line);
         self.register_parser("circle".to_lowercase(), parse_cmd::circle);
         self.register_parser("square".to_lowercase(), parse_cmd::square);
+        self.register_parser("ngon".to_lowercase(), parse_cmd::ngon);
+        self.register_parser("star".to_lowercase(), parse_cmd::star);
 
         self.register_parser("move".to_lowercase(), parse_cmd::move_by);
         self.register_parser("undo".to_lowercase(), parse_cmd::undo);
         self.register_parser("redo".to_lowercase(), parse_cmd::redo);
+        self.register_parser("undo-to".to_lowercase(), parse_cmd::undo_to);
+        self.register_parser("history".to_lowercase(), parse_cmd::history);
+        self.register_parser("branch".to_lowercase(), parse_cmd::branch);
+        self.register_parser("begin".to_lowercase(), parse_cmd::begin);
+        self.register_parser("commit".to_lowercase(), parse_cmd::commit);
+        self.register_parser("rollback".to_lowercase(), parse_cmd::rollback);
         self.register_parser("delete".to_lowercase(), parse_cmd::delete);
+        self.register_parser("connect".to_lowercase(), parse_cmd::connect);
+        self.register_parser("flip".to_lowercase(), parse_cmd::flip);
+        self.register_parser("mirror".to_lowercase(), parse_cmd::mirror);
+        self.register_parser("array-rect".to_lowercase(), parse_cmd::array_rect);
+        self.register_parser("array-polar".to_lowercase(), parse_cmd::array_polar);
+        self.register_parser("constrain".to_lowercase(), parse_cmd::constrain);
+        self.register_parser("unconstrain".to_lowercase(), parse_cmd::unconstrain);
+        self.register_parser("trim".to_lowercase(), parse_cmd::trim);
+        self.register_parser("extend".to_lowercase(), parse_cmd::extend);
+        self.register_parser("split".to_lowercase(), parse_cmd::split);
+        self.register_parser("join".to_lowercase(), parse_cmd::join);
+        self.register_parser("hull".to_lowercase(), parse_cmd::hull);
+        self.register_parser("triangulate".to_lowercase(), parse_cmd::triangulate);
+        self.register_parser("centroid".to_lowercase(), parse_cmd::centroid);
+        self.register_parser("offset".to_lowercase(), parse_cmd::offset);
+        self.register_parser("tag".to_lowercase(), parse_cmd::tag);
+        self.register_parser("untag".to_lowercase(), parse_cmd::untag);
+        self.register_parser("meta".to_lowercase(), parse_cmd::meta);
+        self.register_parser("select".to_lowercase(), parse_cmd::select);
+        self.register_parser("select-add".to_lowercase(), parse_cmd::select_add);
+        self.register_parser("select-clear".to_lowercase(), parse_cmd::select_clear);
+        self.register_parser("select-rect".to_lowercase(), parse_cmd::select_rect);
+        self.register_parser("find".to_lowercase(), parse_cmd::find);
+        self.register_parser("list".to_lowercase(), parse_cmd::list);
+        self.register_parser("listAll".to_lowercase(), parse_cmd::list_all);
+        self.register_parser("save".to_lowercase(), parse_cmd::save);
+        self.register_parser("load".to_lowercase(), parse_cmd::load);
     }
 }
 impl<Reader, Stdout, Stderr> CliCommander<Reader, Stdout, Stderr>
@@ -68,37 +126,105 @@ where
         self.parse_fn.insert(cmd_name, func)
     }
 
+    /// Answer every command with a JSON object on its own line of stdout,
+    /// and drop the prompt, for tools driving the commander.
+    pub fn set_json(&mut self, json: bool) {
+        self.json = json;
+    }
+
+    /// Record every command that succeeds in `journal`, and the failures
+    /// that roll back a transaction. Replayed entries are already there.
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    /// Run the entries of a crashed session before reading new commands,
+    /// quietly apart from errors.
+    pub fn replay(&mut self, entries: Vec<Entry>) {
+        self.replay.extend(entries);
+    }
+
+    /// Load the scene in `path` before reading commands, answered and
+    /// journaled like a typed `load`.
+    pub fn open(&mut self, path: &Path) {
+        self.opening = Some(format!("load {}", path.display()));
+    }
+
+    fn write_response(
+        &mut self,
+        result: Result<&Outcome, &(dyn Error + 'static)>,
+    ) -> io::Result<()> {
+        if self.replaying && result.is_ok() {
+            return Ok(());
+        }
+        if self.json {
+            let response = match result {
+                Ok(outcome) => outcome.to_json(),
+                Err(error) => error_json(error),
+            };
+            return writeln!(self.stdout, "{}", response);
+        }
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            Err(error) => return writeln!(self.stderr, "{}", error),
+        };
+        if let Some(text) = outcome.message.as_ref().or(outcome.data.as_ref()) {
+            writeln!(self.stdout, "{}", text)?;
+        }
+        for warning in outcome.warnings.iter() {
+            writeln!(self.stderr, "warning: {}", warning)?;
+        }
+        Ok(())
+    }
+
     fn parse_line(
         &mut self,
         line: Result<String, io::Error>,
     ) -> Result<Box<dyn Command>, Box<dyn Error>> {
         lazy_static! {
-            static ref RE_POINT: Regex = Regex::new(r"^\s*(?P<cmd_name>\w+)(\s+(.*))?$").unwrap();
+            static ref RE_POINT: Regex =
+                Regex::new(r"^\s*(?P<cmd_name>[\w-]+)(\s+(.*))?$").unwrap();
         }
         let line = line?;
 
-        let err_msg = "usage: command args ...";
-        let caps = RE_POINT.captures(&line).ok_or(&err_msg[..])?;
-        let cmd_name = caps
-            .name("cmd_name")
-            .ok_or(&err_msg[..])?
-            .as_str()
-            .to_lowercase();
+        let usage = || ClevisError::Parse {
+            message: "usage: command args ...".to_string(),
+            position: 0,
+        };
+        let caps = RE_POINT.captures(&line).ok_or_else(usage)?;
+        let cmd_name = caps.name("cmd_name").ok_or_else(usage)?;
+        let (position, cmd_name) = (cmd_name.start(), cmd_name.as_str().to_lowercase());
 
-        let err_msg = format!("{:?} is not a valid command.", cmd_name);
-        let parse_fn = self.parse_fn.get(&cmd_name).ok_or(&err_msg[..])?;
+        let parse_fn = self.parse_fn.get(&cmd_name).ok_or(ClevisError::Parse {
+            message: format!("{:?} is not a valid command.", cmd_name),
+            position,
+        })?;
 
-        parse_fn(&line)
+        parse_fn(&line).map_err(|error| parse_cmd::parse_error(error, &cmd_name, &line))
+    }
+
+    /// The command that replays a journal entry.
+    fn parse_entry(&mut self, entry: Entry) -> Result<Box<dyn Command>, Box<dyn Error>> {
+        match entry {
+            Entry::Line(line) => self.parse_line(Ok(line)),
+            Entry::Scene { path, snapshot } => {
+                Ok(Box::new(Load::with_scene(path, scene::load(&snapshot)?)))
+            }
+        }
     }
 
     fn next_line(&mut self) -> Option<Result<String, io::Error>> {
-        match self.stdout.write_all(b"> ") {
-            Err(error) => return Some(Err(error)),
-            Ok(_) => (),
+        if let Some(line) = self.opening.take() {
+            return Some(Ok(line));
+        }
+        if self.json {
+            return self.lines.next();
+        }
+        if let Err(error) = self.stdout.write_all(b"> ") {
+            return Some(Err(error));
         }
-        match self.stdout.flush() {
-            Err(error) => return Some(Err(error)),
-            Ok(_) => (),
+        if let Err(error) = self.stdout.flush() {
+            return Some(Err(error));
         }
 
         self.lines.next()
@@ -131,25 +257,85 @@ where
 {
     type Item = Box<dyn Command>;
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
-        while let Some(line) = self.next_line() {
-            match self.parse_line(line) {
-                Ok(cmd) => return Some(cmd),
-                Err(error) => {
-                    let res = self.stderr.write_all(format!("{}\n", error).as_bytes());
-                    match res {
-                        Err(error) => io::stdout()
-                            .lock()
-                            .write_all(format!("{}\n", error).as_bytes())
-                            .unwrap(),
-                        Ok(_) => (),
+        loop {
+            self.replaying = !self.replay.is_empty();
+            let (parsed, journaled) = match self.replay.pop_front() {
+                Some(entry) => (self.parse_entry(entry), None),
+                None => match self.next_line() {
+                    Some(line) => {
+                        let journaled = line.as_ref().ok().cloned();
+                        (self.parse_line(line), journaled)
                     }
+                    None => break,
+                },
+            };
+            match parsed {
+                Ok(cmd) => {
+                    self.pending = journaled;
+                    return Some(cmd);
                 }
+                Err(error) => self.respond(Err(error.as_ref())),
+            }
+        }
+        // the session is over, nothing to recover
+        if let Some(journal) = self.journal.take() {
+            if let Err(error) = journal.finish() {
+                log::error!("{}", error);
             }
         }
         None
     }
 }
 
+impl<Reader, Stdout, Stderr> Responder for CliCommander<Reader, Stdout, Stderr>
+where
+    Reader: 'static + BufRead,
+    Stdout: Write,
+    Stderr: Write,
+    Point: Shape,
+    Rectangle: Shape,
+    Line: Shape,
+    Circle: Shape,
+    Square: Shape,
+{
+    /// Messages and data go to stdout, warnings and errors to stderr.
+    fn respond(&mut self, result: Result<&Outcome, &(dyn Error + 'static)>) {
+        let pending = self.pending.take();
+        if let (Some(journal), Some(line)) = (self.journal.as_mut(), pending) {
+            let entry = match result {
+                Ok(outcome) => match outcome.journal.clone() {
+                    None => Ok(Some(Entry::Line(line))),
+                    Some(Journaled::Nothing) => Ok(None),
+                    Some(Journaled::Scene { path }) => journal.snapshot(&path).map(Some),
+                },
+                // a failure that changed the drawing or closed the transaction
+                // has to change it on replay too
+                Err(error) => Ok(match error.downcast_ref::<ExecutionError>() {
+                    Some(ExecutionError::RolledBack { rollback: None, .. }) => {
+                        Some(Entry::Line("rollback".to_string()))
+                    }
+                    Some(ExecutionError::RolledBack { .. } | ExecutionError::Unrestored { .. }) => {
+                        Some(Entry::Line(line))
+                    }
+                    _ => None,
+                }),
+            };
+            if let Err(error) = entry.and_then(|entry| match entry {
+                Some(entry) => journal.append(&entry),
+                None => Ok(()),
+            }) {
+                log::error!("{}", error);
+            }
+        }
+        if let Err(error) = self.write_response(result) {
+            io::stdout()
+                .lock()
+                .write_all(format!("{}\n", error).as_bytes())
+                .unwrap()
+        }
+    }
+}
+
 pub mod parse_cmd;
 pub use parse_cmd::{HELP_INFO, READABLE_PATTERNS};
 
@@ -195,10 +381,10 @@ pub mod tests {
         let correct_buff = HashSet::from([
             "",
             "cic Circle { center: Point { x: 23, y: 4 }, radius: 45 }",
-            "rect Rectangle { corner: Point { x: 2, y: 3 }, w: 4, h: 5 }",
+            "rect Rectangle { corner: Point { x: 2, y: 3 }, w: 4, h: 5, radius: 0 }",
             "l1 Line(Point { x: 2, y: 3 }, Point { x: 4, y: 5 })",
             "p1 Point { x: 0, y: 0 }",
-            "sq Square { corner: Point { x: 32, y: 34 }, side: 56 }",
+            "sq Square { corner: Point { x: 32, y: 34 }, side: 56, radius: 0 }",
         ]);
         let buff = io::BufReader::new(&buff[..]);
         for line in buff.lines() {
@@ -206,4 +392,27 @@ pub mod tests {
             assert!(correct_buff.contains(&line[..]));
         }
     }
+
+    #[test]
+    fn test_json_errors() {
+        let input = b"point p 0 x\nfoo 1\n";
+        let input = io::BufReader::new(&input[..]);
+        let mut stdout = Vec::<u8>::new();
+        let mut stderr = Vec::<u8>::new();
+        let mut commander = CliCommander::new(input, &mut stdout, &mut stderr);
+        commander.set_json(true);
+        assert!(commander.next().is_none());
+        drop(commander);
+
+        let correct_stdout = [
+            r#"{"ok":false,"code":"parse_error","error":"The pattern should be like "#,
+            r#"\"point <name> <x:i32> <y:i32> [--replace]\" but got \"point p 0 x\"","position":10}"#,
+            "\n",
+            r#"{"ok":false,"code":"parse_error","error":"\"foo\" is not a valid command.","#,
+            r#""position":0}"#,
+            "\n",
+        ]
+        .concat();
+        assert_eq!(str::from_utf8(&stdout).unwrap(), correct_stdout);
+    }
 }
@@ -1,6 +1,12 @@
 use super::*;
 use crate::command::*;
+use crate::constraint::Constraint;
+use crate::error::ClevisError;
+use crate::query::Query;
+use crate::scene::SceneFormat;
+use crate::selector::Selector;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 macro_rules! err_msg_pattern {
     () => {
@@ -8,20 +14,121 @@ macro_rules! err_msg_pattern {
     };
 }
 
+/// A shape name, a glob like `pad_*`, a regular expression like
+/// `/^pad_\d+$/`, or `@sel` for the current selection.
+const PATTERN_SELECTOR: &str = r"(?P<name>/([^/\\]|\\.)+/|@sel|[\w*?]+)";
+
+/// The command for a single name, or a `ForEach` over all shapes matched by
+/// a pattern. `args` is how the arguments after the selector are displayed.
+fn for_each_selected<F>(
+    cmd_name: &str,
+    selector: &str,
+    args: &str,
+    order: Order,
+    make: F,
+) -> Result<Box<dyn Command>, Box<dyn Error>>
+where
+    F: 'static + Fn(String) -> Box<dyn Command>,
+{
+    match Selector::parse(selector)? {
+        Selector::Name(name) => Ok(make(name)),
+        selector => Ok(Box::new(
+            ForEach::new(cmd_name, selector, args, Box::new(make)).with_order(order),
+        )),
+    }
+}
+
 lazy_static! {
     pub static ref READABLE_PATTERNS: HashMap<&'static str, &'static str> = HashMap::from([
-        ("point", "point <name> <x:i32> <y:i32>"),
+        ("point", "point <name> <x:i32> <y:i32> [--replace]"),
         (
             "rectangle",
-            "rectangle <name> <x:i32> <y:i32> <w:i32> <h:i32>"
+            "rectangle <name> <x:i32> <y:i32> <w:i32> <h:i32> [radius:i32] [--replace]"
+        ),
+        (
+            "line",
+            "line <name> (<x1:i32> <y1:i32> <x2:i32> <y2:i32> | <from:point> <to:point>) [--replace]"
+        ),
+        (
+            "circle",
+            "circle <name> (<x:i32> <y:i32> | <center:point>) <r:i32> [--replace]"
+        ),
+        (
+            "square",
+            "square <name> <x:i32> <y:i32> <l:i32> [radius:i32] [--replace]"
+        ),
+        (
+            "ngon",
+            "ngon <name> <cx:i32> <cy:i32> <r:i32> <sides:u32> [--replace]"
+        ),
+        (
+            "star",
+            "star <name> <cx:i32> <cy:i32> <r1:i32> <r2:i32> <points:u32> [--replace]"
+        ),
+        ("move", "move <selector> <dx:i32> <dy:i32>"),
+        ("undo", "undo [count:usize]"),
+        ("redo", "redo [count:usize|all]"),
+        ("undo-to", "undo-to <index:usize>"),
+        ("history", "history [tree]"),
+        ("branch", "branch list | branch switch <id:usize>"),
+        ("begin", "begin"),
+        ("commit", "commit"),
+        ("rollback", "rollback"),
+        ("delete", "delete <selector>"),
+        (
+            "connect",
+            "connect <name> <from:name> <to:name> [--replace]"
+        ),
+        ("flip", "flip <selector> horizontal|vertical [axis:i32]"),
+        (
+            "mirror",
+            "mirror <name> <new_name> <x1:i32> <y1:i32> <x2:i32> <y2:i32> [--replace]"
+        ),
+        (
+            "array-rect",
+            "array-rect <name> <rows:usize> <cols:usize> <dx:i32> <dy:i32> [--replace]"
+        ),
+        (
+            "array-polar",
+            "array-polar <name> <count:usize> <cx:i32> <cy:i32> <angle:f64> [--replace]"
+        ),
+        ("constrain", "constrain <constraint>"),
+        ("unconstrain", "unconstrain <constraint>"),
+        ("trim", "trim <line> <boundary>"),
+        ("extend", "extend <line> <boundary>"),
+        ("split", "split <line> <x:i32> <y:i32> [--replace]"),
+        ("join", "join <line> <line>"),
+        ("hull", "hull <new_name> <name>... [--replace]"),
+        ("triangulate", "triangulate <name> [--replace]"),
+        ("centroid", "centroid <name>"),
+        (
+            "offset",
+            "offset <name> <new_name> <distance:i32> [round] [--replace]"
+        ),
+        ("tag", "tag <selector> <tag>..."),
+        ("untag", "untag <selector> <tag>..."),
+        ("meta", "meta <selector> <key>=<value>..."),
+        ("select", "select <selector>..."),
+        ("select-add", "select-add <selector>..."),
+        ("select-clear", "select-clear"),
+        (
+            "select-rect",
+            "select-rect <x:i32> <y:i32> <w:i32> <h:i32>"
+        ),
+        ("find", "find [--select] <query>"),
+        ("list", "list <name> [--format text|json]"),
+        ("listAll", "listAll [--format text|json]"),
+        ("save", "save [--binary] <file>"),
+        ("load", "load <file>"),
+        (
+            "query",
+            "<field> =|!=|<|<=|>|>= <value> joined by and, or, not and (...), field is type, name, tag, cx, cy, a parameter or a metadata key"
+        ),
+        ("selector", "<name> | <glob with * and ?> | /<regex>/ | @sel"),
+        (
+            "constraint",
+            "parallel|perpendicular|equal-length|coincident <a> <b> | horizontal|vertical|fixed <a> | distance <a> <b> <d:i32>"
         ),
-        ("line", "line <name> <x1:i32> <y1:i32> <x2:i32> <y2:i32>"),
-        ("circle", "circle <name> <x:i32> <y:i32> <r:i32>"),
-        ("square", "square <name> <x:i32> <y:i32> <l:i32>"),
-        ("move", "move <name> <dx:i32> <dy:i32>"),
-        ("undo", "undo"),
-        ("redo", "redo"),
-        ("delete", "delete <name>"),
     ]);
     pub static ref HELP_INFO: HashMap<&'static str, &'static str> = HashMap::from([
         ("point", "Draw point"),
@@ -29,24 +136,110 @@ lazy_static! {
         ("line", "Draw line"),
         ("circle", "Draw circle"),
         ("square", "Draw square"),
+        ("ngon", "Draw regular polygon"),
+        ("star", "Draw star"),
         ("move", "Move a shape"),
-        ("undo", "Undo last command"),
-        ("redo", "Redo last undone command"),
+        ("undo", "Undo last command, or the last few"),
+        ("redo", "Redo last undone command, the next few or all"),
+        ("undo-to", "Undo or redo until a numbered command of the history"),
+        ("history", "List executed and undone commands, with the branches as a tree"),
+        ("branch", "List or switch to the branches of undone commands"),
+        ("begin", "Start a transaction, undone as a single step, rolled back if never committed"),
+        ("commit", "Finish the transaction"),
+        ("rollback", "Undo the commands of the transaction"),
         ("delete", "Delete a shape by its name"),
+        ("connect", "Connect two shapes with a line"),
+        ("flip", "Flip a shape in place"),
+        ("mirror", "Mirror a shape across a line into a copy"),
+        ("array-rect", "Copy a shape into a grid"),
+        ("array-polar", "Copy a shape around a center"),
+        ("constrain", "Add a geometric constraint"),
+        ("unconstrain", "Remove a geometric constraint"),
+        ("trim", "Cut a line at a boundary"),
+        ("extend", "Extend a line to a boundary"),
+        ("split", "Split a line in two at a point"),
+        ("join", "Join two collinear lines"),
+        ("hull", "Draw the convex hull of shapes"),
+        ("triangulate", "Split a polygon into triangles"),
+        ("centroid", "Report the centroid of a shape"),
+        ("offset", "Grow or shrink a shape into a copy"),
+        ("tag", "Add tags to a shape"),
+        ("untag", "Remove tags from a shape"),
+        ("meta", "Set key/value metadata of a shape, an empty value removes the key"),
+        ("select", "Select shapes"),
+        ("select-add", "Add shapes to the selection"),
+        ("select-clear", "Select nothing"),
+        ("select-rect", "Select shapes whose outline reaches into a region"),
+        ("find", "Find shapes matching a query"),
+        ("list", "Show the parameters of a shape"),
+        ("listAll", "Show the parameters of every shape, in drawing order"),
+        ("save", "Save the drawing into a scene file, compact with --binary"),
+        ("load", "Replace the drawing by a scene file"),
     ]);
 }
 
+/// Where `line` stops following the readable `pattern`, as a byte offset:
+/// the first argument of the wrong type, the first one too many, or the end
+/// of a line that is too short. Optional and alternative parts are not
+/// followed, the position is where they start.
+fn mismatch_position(pattern: &str, line: &str) -> usize {
+    lazy_static! {
+        static ref RE_WORD: Regex = Regex::new(r"\S+").unwrap();
+    }
+    let words: Vec<_> = RE_WORD.find_iter(line).collect();
+    let expected: Vec<&str> = pattern.split_whitespace().collect();
+    for (i, expected) in expected.iter().enumerate() {
+        let word = match words.get(i) {
+            Some(word) => word,
+            None => return line.trim_end().len(),
+        };
+        if expected.contains(['[', '(', '|']) || expected.ends_with("...") {
+            return word.start();
+        }
+        let fits = match expected.strip_prefix('<').and_then(|e| e.strip_suffix('>')) {
+            Some(placeholder) => match placeholder.split_once(':') {
+                Some((_, "i32")) => word.as_str().parse::<i32>().is_ok(),
+                Some((_, "u32")) => word.as_str().parse::<u32>().is_ok(),
+                Some((_, "usize")) => word.as_str().parse::<usize>().is_ok(),
+                Some((_, "f64")) => word.as_str().parse::<f64>().is_ok(),
+                _ => true,
+            },
+            None => word.as_str().eq_ignore_ascii_case(expected),
+        };
+        if !fits {
+            return word.start();
+        }
+    }
+    words.get(expected.len()).map_or(0, |word| word.start())
+}
+
+/// Errors of the parsers without a type of their own are parse errors of
+/// the line.
+pub fn parse_error(error: Box<dyn Error>, cmd_name: &str, line: &str) -> Box<dyn Error> {
+    if error.is::<ClevisError>() {
+        return error;
+    }
+    let pattern = READABLE_PATTERNS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(cmd_name))
+        .map_or("", |(_, pattern)| *pattern);
+    Box::new(ClevisError::Parse {
+        message: error.to_string(),
+        position: mismatch_position(pattern, line),
+    })
+}
+
 pub fn point(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>>
 where
     Point: Shape,
 {
     lazy_static! {
-        static ref PATTERN_CMD_POINT: String = vec!(
+        static ref PATTERN_CMD_POINT: String = [
             r"^\s*(?i:point)",
             r"(?P<name>\w+)",
             r"(?P<x>(\+|-)?[[:digit:]]+)",
-            r"(?P<y>(\+|-)?[[:digit:]]+)\s*$",
-        )
+            r"(?P<y>(\+|-)?[[:digit:]]+)(?P<replace>\s+--replace)?\s*$"
+        ]
         .join(r"\s+");
         static ref RE_CMD_POINT: Regex = Regex::new(&PATTERN_CMD_POINT).unwrap();
     }
@@ -56,12 +249,15 @@ where
         line
     );
 
-    let caps = RE_CMD_POINT.captures(&line).ok_or(&err_msg[..])?;
+    let caps = RE_CMD_POINT.captures(line).ok_or(&err_msg[..])?;
     let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
     let x = caps.name("x").ok_or(&err_msg[..])?.as_str().parse()?;
     let y = caps.name("y").ok_or(&err_msg[..])?.as_str().parse()?;
 
-    Ok(Box::new(DrawShape::new(name.to_string(), Point { x, y })))
+    Ok(Box::new(
+        DrawShape::new(name.to_string(), Point { x, y })
+            .with_replace(caps.name("replace").is_some()),
+    ))
 }
 
 pub fn rectangle(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>>
@@ -69,14 +265,14 @@ where
     Rectangle: Shape,
 {
     lazy_static! {
-        static ref PATTERN_CMD_RECTANGLE: String = vec!(
+        static ref PATTERN_CMD_RECTANGLE: String = [
             r"^\s*(?i:rectangle)",
             r"(?P<name>\w+)",
             r"(?P<x>(\+|-)?[[:digit:]]+)",
             r"(?P<y>(\+|-)?[[:digit:]]+)",
             r"(?P<w>(\+|-)?[[:digit:]]+)",
-            r"(?P<h>(\+|-)?[[:digit:]]+)\s*$",
-        )
+            r"(?P<h>(\+|-)?[[:digit:]]+)(\s+(?P<r>[[:digit:]]+))?(?P<replace>\s+--replace)?\s*$"
+        ]
         .join(r"\s+");
         static ref RE_CMD_RECTANGLE: Regex = Regex::new(&PATTERN_CMD_RECTANGLE).unwrap();
     }
@@ -86,36 +282,53 @@ where
         line
     );
 
-    let caps = RE_CMD_RECTANGLE.captures(&line).ok_or(&err_msg[..])?;
+    let caps = RE_CMD_RECTANGLE.captures(line).ok_or(&err_msg[..])?;
     let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
     let x = caps.name("x").ok_or(&err_msg[..])?.as_str().parse()?;
     let y = caps.name("y").ok_or(&err_msg[..])?.as_str().parse()?;
     let w = caps.name("w").ok_or(&err_msg[..])?.as_str().parse()?;
     let h = caps.name("h").ok_or(&err_msg[..])?.as_str().parse()?;
+    let radius = match caps.name("r") {
+        Some(r) => r.as_str().parse()?,
+        None => 0,
+    };
 
-    Ok(Box::new(DrawShape::new(
-        name.to_string(),
-        Rectangle {
-            corner: Point { x: x, y: y },
-            w,
-            h,
-        },
-    )))
+    Ok(Box::new(
+        DrawShape::new(
+            name.to_string(),
+            Rectangle {
+                corner: Point { x, y },
+                w,
+                h,
+                radius,
+            },
+        )
+        .with_replace(caps.name("replace").is_some()),
+    ))
 }
 
 pub fn line(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>>
 where
     Line: Shape,
+    LinkedLine: Shape,
 {
     lazy_static! {
-        static ref PATTERN_CMD_LINE: String = vec!(
+        static ref PATTERN_CMD_LINKED_LINE: String = [
+            r"^\s*(?i:line)",
+            r"(?P<name>\w+)",
+            r"(?P<from>[[:alpha:]_]\w*)",
+            r"(?P<to>[[:alpha:]_]\w*)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_LINKED_LINE: Regex = Regex::new(&PATTERN_CMD_LINKED_LINE).unwrap();
+        static ref PATTERN_CMD_LINE: String = [
             r"^\s*(?i:line)",
             r"(?P<name>\w+)",
             r"(?P<x1>(\+|-)?[[:digit:]]+)",
             r"(?P<y1>(\+|-)?[[:digit:]]+)",
             r"(?P<x2>(\+|-)?[[:digit:]]+)",
-            r"(?P<y2>(\+|-)?[[:digit:]]+)\s*$",
-        )
+            r"(?P<y2>(\+|-)?[[:digit:]]+)(?P<replace>\s+--replace)?\s*$"
+        ]
         .join(r"\s+");
         static ref RE_CMD_LINE: Regex = Regex::new(&PATTERN_CMD_LINE).unwrap();
     }
@@ -125,31 +338,56 @@ where
         line
     );
 
-    let caps = RE_CMD_LINE.captures(&line).ok_or(&err_msg[..])?;
+    if let Some(caps) = RE_CMD_LINKED_LINE.captures(line) {
+        let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+        let from = caps.name("from").ok_or(&err_msg[..])?.as_str();
+        let to = caps.name("to").ok_or(&err_msg[..])?.as_str();
+        return Ok(Box::new(
+            DrawShape::new(
+                name.to_string(),
+                LinkedLine::new(from.to_string(), to.to_string()),
+            )
+            .with_replace(caps.name("replace").is_some()),
+        ));
+    }
+
+    let caps = RE_CMD_LINE.captures(line).ok_or(&err_msg[..])?;
     let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
     let x1 = caps.name("x1").ok_or(&err_msg[..])?.as_str().parse()?;
     let y1 = caps.name("y1").ok_or(&err_msg[..])?.as_str().parse()?;
     let x2 = caps.name("x2").ok_or(&err_msg[..])?.as_str().parse()?;
     let y2 = caps.name("y2").ok_or(&err_msg[..])?.as_str().parse()?;
 
-    Ok(Box::new(DrawShape::new(
-        name.to_string(),
-        Line(Point { x: x1, y: y1 }, Point { x: x2, y: y2 }),
-    )))
+    Ok(Box::new(
+        DrawShape::new(
+            name.to_string(),
+            Line(Point { x: x1, y: y1 }, Point { x: x2, y: y2 }),
+        )
+        .with_replace(caps.name("replace").is_some()),
+    ))
 }
 
 pub fn circle(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>>
 where
     Circle: Shape,
+    LinkedCircle: Shape,
 {
     lazy_static! {
-        static ref PATTERN_CMD_CIRCLE: String = vec!(
+        static ref PATTERN_CMD_LINKED_CIRCLE: String = [
+            r"^\s*(?i:circle)",
+            r"(?P<name>\w+)",
+            r"(?P<center>[[:alpha:]_]\w*)",
+            r"(?P<r>(\+|-)?[[:digit:]]+)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_LINKED_CIRCLE: Regex = Regex::new(&PATTERN_CMD_LINKED_CIRCLE).unwrap();
+        static ref PATTERN_CMD_CIRCLE: String = [
             r"^\s*(?i:circle)",
             r"(?P<name>\w+)",
             r"(?P<x>(\+|-)?[[:digit:]]+)",
             r"(?P<y>(\+|-)?[[:digit:]]+)",
-            r"(?P<r>(\+|-)?[[:digit:]]+)\s*$",
-        )
+            r"(?P<r>(\+|-)?[[:digit:]]+)(?P<replace>\s+--replace)?\s*$"
+        ]
         .join(r"\s+");
         static ref RE_CMD_CIRCLE: Regex = Regex::new(&PATTERN_CMD_CIRCLE).unwrap();
     }
@@ -159,19 +397,32 @@ where
         line
     );
 
-    let caps = RE_CMD_CIRCLE.captures(&line).ok_or(&err_msg[..])?;
+    if let Some(caps) = RE_CMD_LINKED_CIRCLE.captures(line) {
+        let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+        let center = caps.name("center").ok_or(&err_msg[..])?.as_str();
+        let r = caps.name("r").ok_or(&err_msg[..])?.as_str().parse()?;
+        return Ok(Box::new(
+            DrawShape::new(name.to_string(), LinkedCircle::new(center.to_string(), r))
+                .with_replace(caps.name("replace").is_some()),
+        ));
+    }
+
+    let caps = RE_CMD_CIRCLE.captures(line).ok_or(&err_msg[..])?;
     let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
     let x = caps.name("x").ok_or(&err_msg[..])?.as_str().parse()?;
     let y = caps.name("y").ok_or(&err_msg[..])?.as_str().parse()?;
     let r = caps.name("r").ok_or(&err_msg[..])?.as_str().parse()?;
 
-    Ok(Box::new(DrawShape::new(
-        name.to_string(),
-        Circle {
-            center: Point { x, y },
-            radius: r,
-        },
-    )))
+    Ok(Box::new(
+        DrawShape::new(
+            name.to_string(),
+            Circle {
+                center: Point { x, y },
+                radius: r,
+            },
+        )
+        .with_replace(caps.name("replace").is_some()),
+    ))
 }
 
 pub fn square(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>>
@@ -179,13 +430,13 @@ where
     Square: Shape,
 {
     lazy_static! {
-        static ref PATTERN_CMD_SQUARE: String = vec!(
+        static ref PATTERN_CMD_SQUARE: String = [
             r"^\s*(?i:square)",
             r"(?P<name>\w+)",
             r"(?P<x>(\+|-)?[[:digit:]]+)",
             r"(?P<y>(\+|-)?[[:digit:]]+)",
-            r"(?P<l>(\+|-)?[[:digit:]]+)\s*$",
-        )
+            r"(?P<l>(\+|-)?[[:digit:]]+)(\s+(?P<r>[[:digit:]]+))?(?P<replace>\s+--replace)?\s*$"
+        ]
         .join(r"\s+");
         static ref RE_CMD_SQUARE: Regex = Regex::new(&PATTERN_CMD_SQUARE).unwrap();
     }
@@ -195,29 +446,122 @@ where
         line
     );
 
-    let caps = RE_CMD_SQUARE.captures(&line).ok_or(&err_msg[..])?;
+    let caps = RE_CMD_SQUARE.captures(line).ok_or(&err_msg[..])?;
     let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
     let x = caps.name("x").ok_or(&err_msg[..])?.as_str().parse()?;
     let y = caps.name("y").ok_or(&err_msg[..])?.as_str().parse()?;
     let l = caps.name("l").ok_or(&err_msg[..])?.as_str().parse()?;
+    let radius = match caps.name("r") {
+        Some(r) => r.as_str().parse()?,
+        None => 0,
+    };
 
-    Ok(Box::new(DrawShape::new(
-        name.to_string(),
-        Square {
-            corner: Point { x, y },
-            side: l,
-        },
-    )))
+    Ok(Box::new(
+        DrawShape::new(
+            name.to_string(),
+            Square {
+                corner: Point { x, y },
+                side: l,
+                radius,
+            },
+        )
+        .with_replace(caps.name("replace").is_some()),
+    ))
+}
+
+pub fn ngon(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>>
+where
+    RegularPolygon: Shape,
+{
+    lazy_static! {
+        static ref PATTERN_CMD_NGON: String = [
+            r"^\s*(?i:ngon)",
+            r"(?P<name>\w+)",
+            r"(?P<x>(\+|-)?[[:digit:]]+)",
+            r"(?P<y>(\+|-)?[[:digit:]]+)",
+            r"(?P<r>[[:digit:]]+)",
+            r"(?P<sides>[[:digit:]]+)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_NGON: Regex = Regex::new(&PATTERN_CMD_NGON).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("ngon").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_NGON.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let x = caps.name("x").ok_or(&err_msg[..])?.as_str().parse()?;
+    let y = caps.name("y").ok_or(&err_msg[..])?.as_str().parse()?;
+    let radius = caps.name("r").ok_or(&err_msg[..])?.as_str().parse()?;
+    let sides = caps.name("sides").ok_or(&err_msg[..])?.as_str().parse()?;
+    let ngon = RegularPolygon {
+        center: Point { x, y },
+        radius,
+        sides,
+        rotation: 0,
+    };
+    ngon.check()?;
+
+    Ok(Box::new(
+        DrawShape::new(name.to_string(), ngon).with_replace(caps.name("replace").is_some()),
+    ))
+}
+
+pub fn star(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>>
+where
+    Star: Shape,
+{
+    lazy_static! {
+        static ref PATTERN_CMD_STAR: String = [
+            r"^\s*(?i:star)",
+            r"(?P<name>\w+)",
+            r"(?P<x>(\+|-)?[[:digit:]]+)",
+            r"(?P<y>(\+|-)?[[:digit:]]+)",
+            r"(?P<r1>[[:digit:]]+)",
+            r"(?P<r2>[[:digit:]]+)",
+            r"(?P<points>[[:digit:]]+)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_STAR: Regex = Regex::new(&PATTERN_CMD_STAR).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("star").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_STAR.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let x = caps.name("x").ok_or(&err_msg[..])?.as_str().parse()?;
+    let y = caps.name("y").ok_or(&err_msg[..])?.as_str().parse()?;
+    let outer = caps.name("r1").ok_or(&err_msg[..])?.as_str().parse()?;
+    let inner = caps.name("r2").ok_or(&err_msg[..])?.as_str().parse()?;
+    let points = caps.name("points").ok_or(&err_msg[..])?.as_str().parse()?;
+    let star = Star {
+        center: Point { x, y },
+        outer,
+        inner,
+        points,
+        rotation: 0,
+    };
+    star.check()?;
+
+    Ok(Box::new(
+        DrawShape::new(name.to_string(), star).with_replace(caps.name("replace").is_some()),
+    ))
 }
 
 pub fn move_by(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
     lazy_static! {
-        static ref PATTERN_CMD_MOVE: String = vec!(
+        static ref PATTERN_CMD_MOVE: String = [
             r"^\s*(?i:move)",
-            r"(?P<name>\w+)",
+            PATTERN_SELECTOR,
             r"(?P<dx>(\+|-)?[[:digit:]]+)",
-            r"(?P<dy>(\+|-)?[[:digit:]]+)\s*$",
-        )
+            r"(?P<dy>(\+|-)?[[:digit:]]+)\s*$"
+        ]
         .join(r"\s+");
         static ref RE_CMD_MOVE: Regex = Regex::new(&PATTERN_CMD_MOVE).unwrap();
     }
@@ -227,18 +571,23 @@ pub fn move_by(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
         line
     );
 
-    let caps = RE_CMD_MOVE.captures(&line).ok_or(&err_msg[..])?;
+    let caps = RE_CMD_MOVE.captures(line).ok_or(&err_msg[..])?;
     let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
     let dx = caps.name("dx").ok_or(&err_msg[..])?.as_str().parse()?;
     let dy = caps.name("dy").ok_or(&err_msg[..])?.as_str().parse()?;
 
-    Ok(Box::new(MoveBy::new(name.to_string(), dx, dy)))
+    let args = format!("{} {}", dx, dy);
+    for_each_selected("move", name, &args, Order::Independent, move |name| {
+        Box::new(MoveBy::new(name, dx, dy))
+    })
 }
 
 pub fn undo(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
     lazy_static! {
-        static ref PATTERN_CMD_UNDO: String = vec!(r"^\s*(?i:undo)\s*$",).join(r"\s+");
-        static ref RE_CMD_UNDO: Regex = Regex::new(&PATTERN_CMD_UNDO).unwrap();
+        static ref PATTERN_CMD_UNDO: String =
+            [r"^\s*(?i:undo)", r"(?P<count>[[:digit:]]+)"].join(r"\s+");
+        static ref RE_CMD_UNDO: Regex =
+            Regex::new(&format!(r"^\s*(?i:undo)\s*$|{}\s*$", *PATTERN_CMD_UNDO)).unwrap();
     }
     let err_msg = format!(
         err_msg_pattern!(),
@@ -246,15 +595,21 @@ pub fn undo(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
         line
     );
 
-    RE_CMD_UNDO.captures(&line).ok_or(&err_msg[..])?;
+    let caps = RE_CMD_UNDO.captures(line).ok_or(&err_msg[..])?;
+    let count = match caps.name("count") {
+        Some(count) => count.as_str().parse()?,
+        None => 1,
+    };
 
-    Ok(Box::new(Control::Undo))
+    Ok(Box::new(Control::new(ControlKind::Undo(count))))
 }
 
 pub fn redo(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
     lazy_static! {
-        static ref PATTERN_CMD_REDO: String = vec!(r"^\s*(?i:redo)\s*$",).join(r"\s+");
-        static ref RE_CMD_REDO: Regex = Regex::new(&PATTERN_CMD_REDO).unwrap();
+        static ref PATTERN_CMD_REDO: String =
+            [r"^\s*(?i:redo)", r"(?P<count>[[:digit:]]+|(?i:all))"].join(r"\s+");
+        static ref RE_CMD_REDO: Regex =
+            Regex::new(&format!(r"^\s*(?i:redo)\s*$|{}\s*$", *PATTERN_CMD_REDO)).unwrap();
     }
     let err_msg = format!(
         err_msg_pattern!(),
@@ -262,9 +617,106 @@ pub fn redo(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
         line
     );
 
-    RE_CMD_REDO.captures(&line).ok_or(&err_msg[..])?;
+    let caps = RE_CMD_REDO.captures(line).ok_or(&err_msg[..])?;
+    let kind = match caps.name("count") {
+        Some(all) if all.as_str().eq_ignore_ascii_case("all") => ControlKind::RedoAll,
+        Some(count) => ControlKind::Redo(count.as_str().parse()?),
+        None => ControlKind::Redo(1),
+    };
+
+    Ok(Box::new(Control::new(kind)))
+}
+
+pub fn undo_to(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_UNDO_TO: String =
+            [r"^\s*(?i:undo-to)", r"(?P<index>[[:digit:]]+)\s*$"].join(r"\s+");
+        static ref RE_CMD_UNDO_TO: Regex = Regex::new(&PATTERN_CMD_UNDO_TO).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("undo-to").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_UNDO_TO.captures(line).ok_or(&err_msg[..])?;
+    let index = caps.name("index").ok_or(&err_msg[..])?.as_str().parse()?;
+
+    Ok(Box::new(Control::new(ControlKind::UndoTo(index))))
+}
+
+pub fn history(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_HISTORY: Regex =
+            Regex::new(r"^\s*(?i:history)(?P<tree>\s+(?i:tree))?\s*$").unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("history").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_HISTORY.captures(line).ok_or(&err_msg[..])?;
+    let kind = match caps.name("tree") {
+        Some(_) => ControlKind::HistoryTree,
+        None => ControlKind::History,
+    };
+
+    Ok(Box::new(Control::new(kind)))
+}
+
+pub fn branch(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_BRANCH: Regex = Regex::new(
+            r"^\s*(?i:branch)\s+((?P<list>(?i:list))|(?i:switch)\s+(?P<id>[[:digit:]]+))\s*$"
+        )
+        .unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("branch").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_BRANCH.captures(line).ok_or(&err_msg[..])?;
+    let kind = match caps.name("id") {
+        Some(id) => ControlKind::BranchSwitch(id.as_str().parse()?),
+        None => ControlKind::BranchList,
+    };
+
+    Ok(Box::new(Control::new(kind)))
+}
+
+fn transaction(line: &str, kind: TransactionKind) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_TRANSACTION: Regex =
+            Regex::new(r"^\s*(?P<cmd>(?i:begin|commit|rollback))\s*$").unwrap();
+    }
+    let cmd_name = Transaction::new(kind).to_string();
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get(&cmd_name[..]).unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_TRANSACTION.captures(line).ok_or(&err_msg[..])?;
+    if !caps["cmd"].eq_ignore_ascii_case(&cmd_name) {
+        return Err(err_msg.into());
+    }
+
+    Ok(Box::new(Transaction::new(kind)))
+}
+
+pub fn begin(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    transaction(line, TransactionKind::Begin)
+}
+
+pub fn commit(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    transaction(line, TransactionKind::Commit)
+}
 
-    Ok(Box::new(Control::Redo))
+pub fn rollback(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    transaction(line, TransactionKind::Rollback)
 }
 
 pub fn delete(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>>
@@ -272,7 +724,7 @@ where
 {
     lazy_static! {
         static ref PATTERN_CMD_DELETE: String =
-            vec!(r"^\s*(?i:delete)", r"(?P<name>\w+)\s*$",).join(r"\s+");
+            [r"^\s*(?i:delete)", PATTERN_SELECTOR].join(r"\s+") + r"\s*$";
         static ref RE_CMD_DELETE: Regex = Regex::new(&PATTERN_CMD_DELETE).unwrap();
     }
     let err_msg = format!(
@@ -281,87 +733,1065 @@ where
         line
     );
 
-    let caps = RE_CMD_DELETE.captures(&line).ok_or(&err_msg[..])?;
+    let caps = RE_CMD_DELETE.captures(line).ok_or(&err_msg[..])?;
     let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
 
-    Ok(Box::new(Delete::new(name.to_string())))
+    for_each_selected("delete", name, "", Order::DependentsFirst, |name| {
+        Box::new(Delete::new(name))
+    })
 }
 
-#[cfg(test)]
-pub mod tests {
-    use super::*;
-
-    #[test]
-    fn test_from_string() {
-        // point
-        let cmd_point = point("Point p1 2 3").unwrap();
-        assert_eq!(format!("{}", cmd_point), "p1 Point { x: 2, y: 3 }");
-        let cmd_point = point("point p1 4 5").unwrap();
-        assert_eq!(format!("{}", cmd_point), "p1 Point { x: 4, y: 5 }");
-        assert!(!point("aaa bbb cc cc").is_ok());
-
-        // rectangle
-        let cmd_rectangle = rectangle("rectangle rect 2 3 4 5").unwrap();
-        assert_eq!(
-            format!("{}", cmd_rectangle),
-            "rect Rectangle { corner: Point { x: 2, y: 3 }, w: 4, h: 5 }"
-        );
-        let cmd_rectangle = rectangle("Rectangle rect 4 5 3 2").unwrap();
-        assert_eq!(
-            format!("{}", cmd_rectangle),
-            "rect Rectangle { corner: Point { x: 4, y: 5 }, w: 3, h: 2 }"
-        );
-        assert!(!rectangle("aaa bbb cc cc").is_ok());
+pub fn connect(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>>
+where
+    Connector: Shape,
+{
+    lazy_static! {
+        static ref PATTERN_CMD_CONNECT: String = [
+            r"^\s*(?i:connect)",
+            r"(?P<name>\w+)",
+            r"(?P<from>\w+)",
+            r"(?P<to>\w+)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_CONNECT: Regex = Regex::new(&PATTERN_CMD_CONNECT).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("connect").unwrap(),
+        line
+    );
 
-        // line
-        let cmd_line = line("line line1 2 3 4 5").unwrap();
-        assert_eq!(
-            format!("{}", cmd_line),
-            "line1 Line(Point { x: 2, y: 3 }, Point { x: 4, y: 5 })"
-        );
-        let cmd_line = line("line line2 4 5 3 2").unwrap();
-        assert_eq!(
-            format!("{}", cmd_line),
-            "line2 Line(Point { x: 4, y: 5 }, Point { x: 3, y: 2 })"
-        );
-        assert!(!line("aaa bbb cc cc").is_ok());
+    let caps = RE_CMD_CONNECT.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let from = caps.name("from").ok_or(&err_msg[..])?.as_str();
+    let to = caps.name("to").ok_or(&err_msg[..])?.as_str();
 
-        // circle
-        let cmd_circle = circle("circle circle1 2 3 4").unwrap();
-        assert_eq!(
-            format!("{}", cmd_circle),
-            "circle1 Circle { center: Point { x: 2, y: 3 }, radius: 4 }"
-        );
-        let cmd_circle = circle("circle circle2 4 5 3").unwrap();
-        assert_eq!(
-            format!("{}", cmd_circle),
-            "circle2 Circle { center: Point { x: 4, y: 5 }, radius: 3 }"
-        );
-        assert!(!circle("aaa bbb cc cc").is_ok());
+    Ok(Box::new(
+        DrawShape::new(
+            name.to_string(),
+            Connector::new(from.to_string(), to.to_string()),
+        )
+        .with_replace(caps.name("replace").is_some()),
+    ))
+}
 
-        // square
-        let cmd_square = square("square square1 2 3 4").unwrap();
-        assert_eq!(
-            format!("{}", cmd_square),
-            "square1 Square { corner: Point { x: 2, y: 3 }, side: 4 }"
-        );
-        let cmd_square = square("sQuare square2 4 5 3").unwrap();
-        assert_eq!(
-            format!("{}", cmd_square),
-            "square2 Square { corner: Point { x: 4, y: 5 }, side: 3 }"
-        );
-        assert!(!square("aaa bbb cc cc").is_ok());
+pub fn flip(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_FLIP: String = [
+            r"^\s*(?i:flip)",
+            PATTERN_SELECTOR,
+            r"(?P<direction>(?i:horizontal|vertical))(\s+(?P<axis>(\+|-)?[[:digit:]]+))?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_FLIP: Regex = Regex::new(&PATTERN_CMD_FLIP).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("flip").unwrap(),
+        line
+    );
 
-        let cmd_move = move_by("move aaa 3 -5").unwrap();
-        assert_eq!(format!("{}", cmd_move), "move aaa 3 -5");
+    let caps = RE_CMD_FLIP.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let direction = caps.name("direction").ok_or(&err_msg[..])?.as_str();
+    let direction = match &direction.to_lowercase()[..] {
+        "horizontal" => FlipDirection::Horizontal,
+        _ => FlipDirection::Vertical,
+    };
+    let axis = match caps.name("axis") {
+        Some(axis) => Some(axis.as_str().parse()?),
+        None => None,
+    };
 
-        let undo = undo("undo").unwrap();
-        assert_eq!(format!("{}", undo), "undo");
-        let redo = redo("redo").unwrap();
-        assert_eq!(format!("{}", redo), "redo");
+    let args = match axis {
+        Some(axis) => format!("{} {}", direction, axis),
+        None => direction.to_string(),
+    };
+    for_each_selected("flip", name, &args, Order::Independent, move |name| {
+        Box::new(Flip::new(name, direction, axis))
+    })
+}
 
-        let delete = delete("delete name").unwrap();
-        assert_eq!(format!("{}", delete), r#"Delete "name" with deleted None"#);
+pub fn mirror(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_MIRROR: String = [
+            r"^\s*(?i:mirror)",
+            r"(?P<name>\w+)",
+            r"(?P<new_name>\w+)",
+            r"(?P<x1>(\+|-)?[[:digit:]]+)",
+            r"(?P<y1>(\+|-)?[[:digit:]]+)",
+            r"(?P<x2>(\+|-)?[[:digit:]]+)",
+            r"(?P<y2>(\+|-)?[[:digit:]]+)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_MIRROR: Regex = Regex::new(&PATTERN_CMD_MIRROR).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("mirror").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_MIRROR.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let new_name = caps.name("new_name").ok_or(&err_msg[..])?.as_str();
+    let x1 = caps.name("x1").ok_or(&err_msg[..])?.as_str().parse()?;
+    let y1 = caps.name("y1").ok_or(&err_msg[..])?.as_str().parse()?;
+    let x2 = caps.name("x2").ok_or(&err_msg[..])?.as_str().parse()?;
+    let y2 = caps.name("y2").ok_or(&err_msg[..])?.as_str().parse()?;
+
+    Ok(Box::new(
+        Mirror::new(
+            name.to_string(),
+            new_name.to_string(),
+            Line(Point { x: x1, y: y1 }, Point { x: x2, y: y2 }),
+        )
+        .with_replace(caps.name("replace").is_some()),
+    ))
+}
+
+pub fn array_rect(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_ARRAY_RECT: String = [
+            r"^\s*(?i:array-rect)",
+            r"(?P<name>\w+)",
+            r"(?P<rows>[[:digit:]]+)",
+            r"(?P<cols>[[:digit:]]+)",
+            r"(?P<dx>(\+|-)?[[:digit:]]+)",
+            r"(?P<dy>(\+|-)?[[:digit:]]+)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_ARRAY_RECT: Regex = Regex::new(&PATTERN_CMD_ARRAY_RECT).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("array-rect").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_ARRAY_RECT.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let rows = caps.name("rows").ok_or(&err_msg[..])?.as_str().parse()?;
+    let cols = caps.name("cols").ok_or(&err_msg[..])?.as_str().parse()?;
+    let dx = caps.name("dx").ok_or(&err_msg[..])?.as_str().parse()?;
+    let dy = caps.name("dy").ok_or(&err_msg[..])?.as_str().parse()?;
+
+    let layout = ArrayLayout::Rect { rows, cols, dx, dy };
+    layout.check()?;
+    Ok(Box::new(
+        Array::new(name.to_string(), layout).with_replace(caps.name("replace").is_some()),
+    ))
+}
+
+pub fn array_polar(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_ARRAY_POLAR: String = [
+            r"^\s*(?i:array-polar)",
+            r"(?P<name>\w+)",
+            r"(?P<count>[[:digit:]]+)",
+            r"(?P<cx>(\+|-)?[[:digit:]]+)",
+            r"(?P<cy>(\+|-)?[[:digit:]]+)",
+            r"(?P<angle>(\+|-)?[[:digit:]]+(\.[[:digit:]]+)?)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_ARRAY_POLAR: Regex = Regex::new(&PATTERN_CMD_ARRAY_POLAR).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("array-polar").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_ARRAY_POLAR.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let count = caps.name("count").ok_or(&err_msg[..])?.as_str().parse()?;
+    let x = caps.name("cx").ok_or(&err_msg[..])?.as_str().parse()?;
+    let y = caps.name("cy").ok_or(&err_msg[..])?.as_str().parse()?;
+    let angle = caps.name("angle").ok_or(&err_msg[..])?.as_str().parse()?;
+
+    let layout = ArrayLayout::Polar {
+        count,
+        center: Point { x, y },
+        angle,
+    };
+    layout.check()?;
+    Ok(Box::new(
+        Array::new(name.to_string(), layout).with_replace(caps.name("replace").is_some()),
+    ))
+}
+
+fn parse_constraint(line: &str, key: &str) -> Result<Constraint, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CONSTRAINT: String = [
+            r"^\s*[\w-]+",
+            r"(?P<kind>[\w-]+)",
+            r"(?P<a>\w+)(\s+(?P<b>[[:alpha:]_]\w*))?(\s+(?P<d>(\+|-)?[[:digit:]]+))?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CONSTRAINT: Regex = Regex::new(&PATTERN_CONSTRAINT).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        format!("{} <{}>", key, READABLE_PATTERNS.get("constraint").unwrap()),
+        line
+    );
+
+    let caps = RE_CONSTRAINT.captures(line).ok_or(&err_msg[..])?;
+    let kind = caps.name("kind").ok_or(&err_msg[..])?.as_str();
+    let a = caps.name("a").ok_or(&err_msg[..])?.as_str().to_string();
+    let b = caps.name("b").map(|b| b.as_str().to_string());
+    let d = caps.name("d").map(|d| d.as_str());
+
+    let constraint = match (&kind.to_lowercase()[..], b, d) {
+        ("parallel", Some(b), None) => Constraint::Parallel(a, b),
+        ("perpendicular", Some(b), None) => Constraint::Perpendicular(a, b),
+        ("equal-length", Some(b), None) => Constraint::EqualLength(a, b),
+        ("coincident", Some(b), None) => Constraint::Coincident(a, b),
+        ("horizontal", None, None) => Constraint::Horizontal(a),
+        ("vertical", None, None) => Constraint::Vertical(a),
+        ("fixed", None, None) => Constraint::Fixed(a),
+        ("distance", Some(b), Some(d)) => Constraint::Distance(a, b, d.parse()?),
+        _ => return Err(err_msg.into()),
+    };
+    Ok(constraint)
+}
+
+pub fn constrain(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_CONSTRAIN: Regex = Regex::new(r"^\s*(?i:constrain)\s").unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("constrain").unwrap(),
+        line
+    );
+
+    RE_CMD_CONSTRAIN.captures(line).ok_or(&err_msg[..])?;
+
+    Ok(Box::new(Constrain::new(parse_constraint(
+        line,
+        "constrain",
+    )?)))
+}
+
+pub fn unconstrain(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_UNCONSTRAIN: Regex = Regex::new(r"^\s*(?i:unconstrain)\s").unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("unconstrain").unwrap(),
+        line
+    );
+
+    RE_CMD_UNCONSTRAIN.captures(line).ok_or(&err_msg[..])?;
+
+    Ok(Box::new(Unconstrain::new(parse_constraint(
+        line,
+        "unconstrain",
+    )?)))
+}
+
+pub fn trim(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_TRIM: String =
+            [r"^\s*(?i:trim)", r"(?P<line>\w+)", r"(?P<boundary>\w+)\s*$"].join(r"\s+");
+        static ref RE_CMD_TRIM: Regex = Regex::new(&PATTERN_CMD_TRIM).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("trim").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_TRIM.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("line").ok_or(&err_msg[..])?.as_str();
+    let boundary = caps.name("boundary").ok_or(&err_msg[..])?.as_str();
+
+    Ok(Box::new(LineEdit::new(LineEditKind::Trim {
+        line: name.to_string(),
+        boundary: boundary.to_string(),
+    })))
+}
+
+pub fn extend(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_EXTEND: String = [
+            r"^\s*(?i:extend)",
+            r"(?P<line>\w+)",
+            r"(?P<boundary>\w+)\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_EXTEND: Regex = Regex::new(&PATTERN_CMD_EXTEND).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("extend").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_EXTEND.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("line").ok_or(&err_msg[..])?.as_str();
+    let boundary = caps.name("boundary").ok_or(&err_msg[..])?.as_str();
+
+    Ok(Box::new(LineEdit::new(LineEditKind::Extend {
+        line: name.to_string(),
+        boundary: boundary.to_string(),
+    })))
+}
+
+pub fn split(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_SPLIT: String = [
+            r"^\s*(?i:split)",
+            r"(?P<line>\w+)",
+            r"(?P<x>(\+|-)?[[:digit:]]+)",
+            r"(?P<y>(\+|-)?[[:digit:]]+)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_SPLIT: Regex = Regex::new(&PATTERN_CMD_SPLIT).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("split").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_SPLIT.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("line").ok_or(&err_msg[..])?.as_str();
+    let x = caps.name("x").ok_or(&err_msg[..])?.as_str().parse()?;
+    let y = caps.name("y").ok_or(&err_msg[..])?.as_str().parse()?;
+
+    Ok(Box::new(
+        LineEdit::new(LineEditKind::Split {
+            line: name.to_string(),
+            at: Point { x, y },
+        })
+        .with_replace(caps.name("replace").is_some()),
+    ))
+}
+
+pub fn join(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_JOIN: String =
+            [r"^\s*(?i:join)", r"(?P<first>\w+)", r"(?P<second>\w+)\s*$"].join(r"\s+");
+        static ref RE_CMD_JOIN: Regex = Regex::new(&PATTERN_CMD_JOIN).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("join").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_JOIN.captures(line).ok_or(&err_msg[..])?;
+    let first = caps.name("first").ok_or(&err_msg[..])?.as_str();
+    let second = caps.name("second").ok_or(&err_msg[..])?.as_str();
+
+    Ok(Box::new(LineEdit::new(LineEditKind::Join {
+        first: first.to_string(),
+        second: second.to_string(),
+    })))
+}
+
+pub fn hull(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_HULL: String = [
+            r"^\s*(?i:hull)",
+            r"(?P<new_name>\w+)",
+            r"(?P<names>\w+(\s+\w+)*)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_HULL: Regex = Regex::new(&PATTERN_CMD_HULL).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("hull").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_HULL.captures(line).ok_or(&err_msg[..])?;
+    let new_name = caps.name("new_name").ok_or(&err_msg[..])?.as_str();
+    let names = caps.name("names").ok_or(&err_msg[..])?.as_str();
+
+    Ok(Box::new(
+        Hull::new(
+            new_name.to_string(),
+            names.split_whitespace().map(String::from).collect(),
+        )
+        .with_replace(caps.name("replace").is_some()),
+    ))
+}
+
+pub fn triangulate(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_TRIANGULATE: String = [
+            r"^\s*(?i:triangulate)",
+            r"(?P<name>\w+)(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_TRIANGULATE: Regex = Regex::new(&PATTERN_CMD_TRIANGULATE).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("triangulate").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_TRIANGULATE.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+
+    Ok(Box::new(
+        Triangulate::new(name.to_string()).with_replace(caps.name("replace").is_some()),
+    ))
+}
+
+pub fn centroid(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_CENTROID: String =
+            [r"^\s*(?i:centroid)", r"(?P<name>\w+)\s*$"].join(r"\s+");
+        static ref RE_CMD_CENTROID: Regex = Regex::new(&PATTERN_CMD_CENTROID).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("centroid").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_CENTROID.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+
+    Ok(Box::new(Centroid::new(name.to_string())))
+}
+
+pub fn offset(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_OFFSET: String = [
+            r"^\s*(?i:offset)",
+            r"(?P<name>\w+)",
+            r"(?P<new_name>\w+)",
+            r"(?P<distance>(\+|-)?[[:digit:]]+)(\s+(?P<round>(?i:round)))?(?P<replace>\s+--replace)?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_OFFSET: Regex = Regex::new(&PATTERN_CMD_OFFSET).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("offset").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_OFFSET.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let new_name = caps.name("new_name").ok_or(&err_msg[..])?.as_str();
+    let distance = caps
+        .name("distance")
+        .ok_or(&err_msg[..])?
+        .as_str()
+        .parse()?;
+    let round = caps.name("round").is_some();
+
+    Ok(Box::new(
+        Offset::new(name.to_string(), new_name.to_string(), distance, round)
+            .with_replace(caps.name("replace").is_some()),
+    ))
+}
+
+fn edit_tags(line: &str, cmd_name: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_TAG: Regex = Regex::new(
+            &[
+                r"^\s*(?i:(?P<cmd>untag|tag))",
+                PATTERN_SELECTOR,
+                r"(?P<tags>[\w-]+(\s+[\w-]+)*)\s*$"
+            ]
+            .join(r"\s+")
+        )
+        .unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get(cmd_name).unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_TAG.captures(line).ok_or(&err_msg[..])?;
+    let cmd = caps.name("cmd").ok_or(&err_msg[..])?.as_str();
+    if !cmd.eq_ignore_ascii_case(cmd_name) {
+        return Err(err_msg.into());
+    }
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let tags = caps.name("tags").ok_or(&err_msg[..])?.as_str();
+    let tags: Vec<String> = tags.split_whitespace().map(String::from).collect();
+
+    let edit = match cmd_name {
+        "tag" => MetadataEdit::Tag(tags.clone()),
+        _ => MetadataEdit::Untag(tags.clone()),
+    };
+    let args = tags.join(" ");
+    for_each_selected(cmd_name, name, &args, Order::ByName, move |name| {
+        Box::new(EditMetadata::new(name, edit.clone()))
+    })
+}
+
+pub fn tag(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    edit_tags(line, "tag")
+}
+
+pub fn untag(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    edit_tags(line, "untag")
+}
+
+pub fn meta(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_META: String = [
+            r"^\s*(?i:meta)",
+            PATTERN_SELECTOR,
+            r"(?P<pairs>[\w.-]+=\S*(\s+[\w.-]+=\S*)*)\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_META: Regex = Regex::new(&PATTERN_CMD_META).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("meta").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_META.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+    let pairs = caps.name("pairs").ok_or(&err_msg[..])?.as_str();
+    let pairs: Vec<(String, String)> = pairs
+        .split_whitespace()
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+    let args: Vec<String> = pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    let args = args.join(" ");
+    for_each_selected("meta", name, &args, Order::ByName, move |name| {
+        Box::new(EditMetadata::new(name, MetadataEdit::Set(pairs.clone())))
+    })
+}
+
+fn select_names(line: &str, cmd_name: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_SELECT: Regex = Regex::new(
+            &[
+                r"^\s*(?i:(?P<cmd>select-add|select))",
+                r"(?P<selectors>\S+(\s+\S+)*)\s*$"
+            ]
+            .join(r"\s+")
+        )
+        .unwrap();
+        static ref RE_SELECTOR: Regex = Regex::new(&format!("^{}$", PATTERN_SELECTOR)).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get(cmd_name).unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_SELECT.captures(line).ok_or(&err_msg[..])?;
+    let cmd = caps.name("cmd").ok_or(&err_msg[..])?.as_str();
+    if !cmd.eq_ignore_ascii_case(cmd_name) {
+        return Err(err_msg.into());
+    }
+    let mut selectors = Vec::new();
+    for selector in caps
+        .name("selectors")
+        .ok_or(&err_msg[..])?
+        .as_str()
+        .split_whitespace()
+    {
+        if !RE_SELECTOR.is_match(selector) {
+            return Err(err_msg.into());
+        }
+        selectors.push(Selector::parse(selector)?);
+    }
+
+    let mode = match cmd_name {
+        "select" => SelectMode::Set(selectors),
+        _ => SelectMode::Add(selectors),
+    };
+    Ok(Box::new(Select::new(mode)))
+}
+
+pub fn select(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    select_names(line, "select")
+}
+
+pub fn select_add(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    select_names(line, "select-add")
+}
+
+pub fn select_clear(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_SELECT_CLEAR: Regex = Regex::new(r"^\s*(?i:select-clear)\s*$").unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("select-clear").unwrap(),
+        line
+    );
+
+    RE_CMD_SELECT_CLEAR.captures(line).ok_or(&err_msg[..])?;
+
+    Ok(Box::new(Select::new(SelectMode::Clear)))
+}
+
+pub fn select_rect(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_SELECT_RECT: String = [
+            r"^\s*(?i:select-rect)",
+            r"(?P<x>(\+|-)?[[:digit:]]+)",
+            r"(?P<y>(\+|-)?[[:digit:]]+)",
+            r"(?P<w>(\+|-)?[[:digit:]]+)",
+            r"(?P<h>(\+|-)?[[:digit:]]+)\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_SELECT_RECT: Regex = Regex::new(&PATTERN_CMD_SELECT_RECT).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("select-rect").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_SELECT_RECT.captures(line).ok_or(&err_msg[..])?;
+    let x = caps.name("x").ok_or(&err_msg[..])?.as_str().parse()?;
+    let y = caps.name("y").ok_or(&err_msg[..])?.as_str().parse()?;
+    let w = caps.name("w").ok_or(&err_msg[..])?.as_str().parse()?;
+    let h = caps.name("h").ok_or(&err_msg[..])?.as_str().parse()?;
+
+    Ok(Box::new(Select::new(SelectMode::Rect(Rectangle {
+        corner: Point { x, y },
+        w,
+        h,
+        radius: 0,
+    }))))
+}
+
+pub fn find(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_FIND: Regex =
+            Regex::new(r"^\s*(?i:find)(?P<select>\s+--select)?\s+(?P<query>\S.*?)\s*$").unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("find").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_FIND.captures(line).ok_or(&err_msg[..])?;
+    let select = caps.name("select").is_some();
+    let query = Query::parse(caps.name("query").ok_or(&err_msg[..])?.as_str())?;
+
+    Ok(Box::new(Find::new(query, select)))
+}
+
+fn list_format(caps: &regex::Captures) -> ListFormat {
+    match caps.name("format") {
+        Some(format) if format.as_str().eq_ignore_ascii_case("json") => ListFormat::Json,
+        _ => ListFormat::Text,
+    }
+}
+
+pub fn list(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref PATTERN_CMD_LIST: String = [
+            r"^\s*(?i:list)",
+            r"(?P<name>\w+)(\s+--format\s+(?i:(?P<format>text|json)))?\s*$"
+        ]
+        .join(r"\s+");
+        static ref RE_CMD_LIST: Regex = Regex::new(&PATTERN_CMD_LIST).unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("list").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_LIST.captures(line).ok_or(&err_msg[..])?;
+    let name = caps.name("name").ok_or(&err_msg[..])?.as_str();
+
+    Ok(Box::new(List::new(
+        Some(name.to_string()),
+        list_format(&caps),
+    )))
+}
+
+pub fn list_all(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_LIST_ALL: Regex =
+            Regex::new(r"^\s*(?i:listAll)(\s+--format\s+(?i:(?P<format>text|json)))?\s*$").unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get("listAll").unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_LIST_ALL.captures(line).ok_or(&err_msg[..])?;
+
+    Ok(Box::new(List::new(None, list_format(&caps))))
+}
+
+/// The file of `save` and `load`: the rest of the line, spaces included,
+/// and whether `--binary` comes before it.
+fn scene_path(line: &str, cmd_name: &str) -> Result<(PathBuf, bool), Box<dyn Error>> {
+    lazy_static! {
+        static ref RE_CMD_SCENE: Regex = Regex::new(
+            r"^\s*(?i:(?P<cmd>save|load))(?P<binary>\s+--binary)?\s+(?P<file>\S(.*\S)?)\s*$"
+        )
+        .unwrap();
+    }
+    let err_msg = format!(
+        err_msg_pattern!(),
+        READABLE_PATTERNS.get(cmd_name).unwrap(),
+        line
+    );
+
+    let caps = RE_CMD_SCENE.captures(line).ok_or(&err_msg[..])?;
+    let cmd = caps.name("cmd").ok_or(&err_msg[..])?.as_str();
+    if !cmd.eq_ignore_ascii_case(cmd_name) {
+        return Err(err_msg.into());
+    }
+    let path = PathBuf::from(caps.name("file").ok_or(&err_msg[..])?.as_str());
+    Ok((path, caps.name("binary").is_some()))
+}
+
+pub fn save(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    let (path, binary) = scene_path(line, "save")?;
+    let format = if binary {
+        SceneFormat::Binary
+    } else {
+        SceneFormat::Text
+    };
+    Ok(Box::new(Save::new(path, format)))
+}
+
+pub fn load(line: &str) -> Result<Box<dyn Command>, Box<dyn Error>> {
+    match scene_path(line, "load")? {
+        (path, false) => Ok(Box::new(Load::new(path))),
+        (_, true) => {
+            let err_msg = "A scene is loaded the same whatever its format, drop --binary.";
+            Err(ClevisError::validation(err_msg).into())
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_string() {
+        // point
+        let cmd_point = point("Point p1 2 3").unwrap();
+        assert_eq!(format!("{}", cmd_point), "p1 Point { x: 2, y: 3 }");
+        let cmd_point = point("point p1 4 5").unwrap();
+        assert_eq!(format!("{}", cmd_point), "p1 Point { x: 4, y: 5 }");
+        let cmd_point = point("point p1 4 5 --replace").unwrap();
+        assert_eq!(
+            format!("{}", cmd_point),
+            "p1 Point { x: 4, y: 5 } --replace"
+        );
+        assert!(point("aaa bbb cc cc").is_err());
+
+        // rectangle
+        let cmd_rectangle = rectangle("rectangle rect 2 3 4 5").unwrap();
+        assert_eq!(
+            format!("{}", cmd_rectangle),
+            "rect Rectangle { corner: Point { x: 2, y: 3 }, w: 4, h: 5, radius: 0 }"
+        );
+        let cmd_rectangle = rectangle("Rectangle rect 4 5 3 2").unwrap();
+        assert_eq!(
+            format!("{}", cmd_rectangle),
+            "rect Rectangle { corner: Point { x: 4, y: 5 }, w: 3, h: 2, radius: 0 }"
+        );
+        let cmd_rectangle = rectangle("rectangle rect 4 5 3 2 1").unwrap();
+        assert_eq!(
+            format!("{}", cmd_rectangle),
+            "rect Rectangle { corner: Point { x: 4, y: 5 }, w: 3, h: 2, radius: 1 }"
+        );
+        assert!(rectangle("aaa bbb cc cc").is_err());
+
+        // line
+        let cmd_line = line("line line1 2 3 4 5").unwrap();
+        assert_eq!(
+            format!("{}", cmd_line),
+            "line1 Line(Point { x: 2, y: 3 }, Point { x: 4, y: 5 })"
+        );
+        let cmd_line = line("line line2 4 5 3 2").unwrap();
+        assert_eq!(
+            format!("{}", cmd_line),
+            "line2 Line(Point { x: 4, y: 5 }, Point { x: 3, y: 2 })"
+        );
+        assert!(line("aaa bbb cc cc").is_err());
+        let cmd_line = line("line line3 p1 p2").unwrap();
+        assert_eq!(
+            format!("{}", cmd_line),
+            r#"line3 LinkedLine { from: "p1", to: "p2", line: Line(Point { x: 0, y: 0 }, Point { x: 0, y: 0 }) }"#
+        );
+
+        // circle
+        let cmd_circle = circle("circle circle1 2 3 4").unwrap();
+        assert_eq!(
+            format!("{}", cmd_circle),
+            "circle1 Circle { center: Point { x: 2, y: 3 }, radius: 4 }"
+        );
+        let cmd_circle = circle("circle circle2 4 5 3").unwrap();
+        assert_eq!(
+            format!("{}", cmd_circle),
+            "circle2 Circle { center: Point { x: 4, y: 5 }, radius: 3 }"
+        );
+        assert!(circle("aaa bbb cc cc").is_err());
+        let cmd_circle = circle("circle circle3 p1 50").unwrap();
+        assert_eq!(
+            format!("{}", cmd_circle),
+            r#"circle3 LinkedCircle { center: "p1", circle: Circle { center: Point { x: 0, y: 0 }, radius: 50 } }"#
+        );
+
+        // square
+        let cmd_square = square("square square1 2 3 4").unwrap();
+        assert_eq!(
+            format!("{}", cmd_square),
+            "square1 Square { corner: Point { x: 2, y: 3 }, side: 4, radius: 0 }"
+        );
+        let cmd_square = square("sQuare square2 4 5 3").unwrap();
+        assert_eq!(
+            format!("{}", cmd_square),
+            "square2 Square { corner: Point { x: 4, y: 5 }, side: 3, radius: 0 }"
+        );
+        let cmd_square = square("square square2 4 5 3 2").unwrap();
+        assert_eq!(
+            format!("{}", cmd_square),
+            "square2 Square { corner: Point { x: 4, y: 5 }, side: 3, radius: 2 }"
+        );
+
+        let cmd_ngon = ngon("ngon hex 10 20 5 6").unwrap();
+        assert_eq!(
+            format!("{}", cmd_ngon),
+            "hex RegularPolygon { center: Point { x: 10, y: 20 }, radius: 5, sides: 6, rotation: 0 }"
+        );
+        assert!(ngon("ngon hex 10 20 5 2").is_err());
+        let error = ngon("ngon hex 10 20 5 1025").err().unwrap();
+        assert_eq!(error.to_string(), "A polygon can have at most 1024 sides.");
+        let error = star("star st 10 20 5 2 3000000000").err().unwrap();
+        assert_eq!(error.to_string(), "A star can have at most 512 points.");
+        let cmd_star = star("star st 10 20 5 2 5").unwrap();
+        assert_eq!(
+            format!("{}", cmd_star),
+            "st Star { center: Point { x: 10, y: 20 }, outer: 5, inner: 2, points: 5, rotation: 0 }"
+        );
+        assert!(square("aaa bbb cc cc").is_err());
+
+        let cmd_move = move_by("move aaa 3 -5").unwrap();
+        assert_eq!(format!("{}", cmd_move), "move aaa 3 -5");
+
+        let undo = undo("undo").unwrap();
+        assert_eq!(format!("{}", undo), "undo");
+        let redo = redo("redo").unwrap();
+        assert_eq!(format!("{}", redo), "redo");
+        assert_eq!(format!("{}", super::undo("Undo 3").unwrap()), "undo 3");
+        assert_eq!(format!("{}", super::redo("redo 2").unwrap()), "redo 2");
+        assert_eq!(format!("{}", super::redo("redo ALL").unwrap()), "redo all");
+        assert!(super::redo("redo some").is_err());
+        assert_eq!(format!("{}", undo_to("undo-to 0").unwrap()), "undo-to 0");
+        assert!(undo_to("undo-to").is_err());
+        assert_eq!(format!("{}", history(" history ").unwrap()), "history");
+        assert_eq!(
+            format!("{}", history("history Tree").unwrap()),
+            "history tree"
+        );
+        assert_eq!(format!("{}", branch("branch list").unwrap()), "branch list");
+        assert_eq!(
+            format!("{}", branch("Branch switch 12").unwrap()),
+            "branch switch 12"
+        );
+        assert!(branch("branch switch").is_err());
+        assert_eq!(format!("{}", begin(" Begin ").unwrap()), "begin");
+        assert_eq!(format!("{}", commit("commit").unwrap()), "commit");
+        assert_eq!(format!("{}", rollback("ROLLBACK").unwrap()), "rollback");
+        assert!(commit("rollback").is_err());
+
+        let delete = delete("delete name").unwrap();
+        assert_eq!(format!("{}", delete), r#"Delete "name" with deleted None"#);
+
+        // selectors
+        let cmd_move = move_by("move rect_? 10 0").unwrap();
+        assert_eq!(format!("{}", cmd_move), "move rect_? 10 0");
+        let cmd_move = move_by(r"move /^pad_\d+$/ 5 5").unwrap();
+        assert_eq!(format!("{}", cmd_move), r"move /^pad_\d+$/ 5 5");
+        assert!(move_by("move /(/ 5 5").is_err());
+        let cmd_delete = super::delete("delete c*").unwrap();
+        assert_eq!(format!("{}", cmd_delete), "delete c*");
+        let cmd_flip = flip("flip * vertical 2").unwrap();
+        assert_eq!(format!("{}", cmd_flip), "flip * vertical 2");
+        let cmd_tag = tag("tag pad_* smd").unwrap();
+        assert_eq!(format!("{}", cmd_tag), "tag pad_* smd");
+        let cmd_move = move_by("move @sel 1 1").unwrap();
+        assert_eq!(format!("{}", cmd_move), "move @sel 1 1");
+
+        assert_eq!(
+            format!("{}", select("select a  pad_* /^b/").unwrap()),
+            "select a pad_* /^b/"
+        );
+        assert!(select("select-add a").is_err());
+        assert!(select("select a,b").is_err());
+        assert_eq!(
+            format!("{}", select_add("Select-Add @sel b").unwrap()),
+            "select-add @sel b"
+        );
+        assert_eq!(
+            format!("{}", select_clear("select-clear").unwrap()),
+            "select-clear"
+        );
+        assert_eq!(
+            format!("{}", select_rect("select-rect 0 0 -5 5").unwrap()),
+            "select-rect 0 0 -5 5"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                find("Find --select type=circle AND (radius>10 or tag = pad)").unwrap()
+            ),
+            "find --select type=circle and (radius>10 or tag=pad)"
+        );
+        assert_eq!(
+            find("find radius >").err().unwrap().to_string(),
+            "The query ends too early."
+        );
+        assert_eq!(format!("{}", list("List c").unwrap()), "list c");
+        assert_eq!(
+            format!("{}", list("list c --format JSON").unwrap()),
+            "list c --format json"
+        );
+        assert!(list("list c --format xml").is_err());
+        assert_eq!(format!("{}", list_all(" listall ").unwrap()), "listAll");
+        assert_eq!(
+            format!("{}", list_all("listAll --format json").unwrap()),
+            "listAll --format json"
+        );
+
+        let connect = connect("connect c1 a b").unwrap();
+        assert_eq!(
+            format!("{}", connect),
+            r#"c1 Connector { from: "a", to: "b", line: Line(Point { x: 0, y: 0 }, Point { x: 0, y: 0 }) }"#
+        );
+
+        let cmd_flip = flip("flip rect Horizontal").unwrap();
+        assert_eq!(format!("{}", cmd_flip), "flip rect horizontal");
+        let cmd_flip = flip("flip rect vertical -3").unwrap();
+        assert_eq!(format!("{}", cmd_flip), "flip rect vertical -3");
+        assert!(flip("flip rect diagonal").is_err());
+
+        let cmd_mirror = mirror("mirror a b 0 0 1 -1").unwrap();
+        assert_eq!(format!("{}", cmd_mirror), "mirror a b 0 0 1 -1");
+        let cmd_mirror = mirror("mirror a a 0 0 1 -1 --replace").unwrap();
+        assert_eq!(format!("{}", cmd_mirror), "mirror a a 0 0 1 -1 --replace");
+
+        let cmd_array = array_rect("array-rect pad 2 3 10 -5").unwrap();
+        assert_eq!(format!("{}", cmd_array), "array-rect pad 2 3 10 -5");
+        let cmd_array = array_polar("array-polar bolt 6 0 0 60").unwrap();
+        assert_eq!(format!("{}", cmd_array), "array-polar bolt 6 0 0 60");
+        let cmd_array = array_rect("array-rect pad 2 3 10 -5 --replace").unwrap();
+        assert_eq!(
+            format!("{}", cmd_array),
+            "array-rect pad 2 3 10 -5 --replace"
+        );
+        let error = array_rect("array-rect pad 1000 1000 1 1").err().unwrap();
+        assert_eq!(error.to_string(), "An array can have at most 10000 items.");
+        assert!(array_polar("array-polar bolt 10001 0 0 1").is_err());
+        let cmd_array = array_polar("array-polar bolt 8 1 2 -22.5").unwrap();
+        assert_eq!(format!("{}", cmd_array), "array-polar bolt 8 1 2 -22.5");
+
+        let cmd_constrain = constrain("constrain parallel l1 l2").unwrap();
+        assert_eq!(format!("{}", cmd_constrain), "constrain parallel l1 l2");
+        let cmd_constrain = constrain("constrain Distance a b 100").unwrap();
+        assert_eq!(format!("{}", cmd_constrain), "constrain distance a b 100");
+        let cmd_constrain = unconstrain("unconstrain fixed a").unwrap();
+        assert_eq!(format!("{}", cmd_constrain), "unconstrain fixed a");
+        assert!(constrain("constrain fixed a b").is_err());
+        assert!(constrain("constrain distance a b").is_err());
+        assert!(constrain("constrain bogus a b").is_err());
+
+        assert_eq!(format!("{}", trim("trim l1 b").unwrap()), "trim l1 b");
+        assert_eq!(format!("{}", extend("Extend l1 b").unwrap()), "extend l1 b");
+        assert_eq!(
+            format!("{}", split("split l1 3 -4").unwrap()),
+            "split l1 3 -4"
+        );
+        assert_eq!(format!("{}", join("join l1 l2").unwrap()), "join l1 l2");
+        assert_eq!(
+            format!("{}", split("split l1 3 -4 --replace").unwrap()),
+            "split l1 3 -4 --replace"
+        );
+
+        assert_eq!(
+            format!("{}", hull("hull h  a b   c").unwrap()),
+            "hull h a b c"
+        );
+        assert!(hull("hull h").is_err());
+        assert_eq!(
+            format!("{}", hull("hull h a b --replace").unwrap()),
+            "hull h a b --replace"
+        );
+        assert_eq!(
+            format!("{}", triangulate("triangulate poly --replace").unwrap()),
+            "triangulate poly --replace"
+        );
+        assert_eq!(
+            format!("{}", triangulate("triangulate poly").unwrap()),
+            "triangulate poly"
+        );
+        assert_eq!(
+            format!("{}", centroid("Centroid poly").unwrap()),
+            "centroid poly"
+        );
+        assert_eq!(
+            format!("{}", offset("offset a b -3").unwrap()),
+            "offset a b -3"
+        );
+        assert_eq!(
+            format!("{}", offset("offset a b 3 Round").unwrap()),
+            "offset a b 3 round"
+        );
+        assert!(offset("offset a b 3 square").is_err());
+        assert_eq!(
+            format!("{}", offset("offset a b 3 round --replace").unwrap()),
+            "offset a b 3 round --replace"
+        );
+
+        assert_eq!(
+            format!("{}", tag("tag p keep-out  cnc").unwrap()),
+            "tag p keep-out cnc"
+        );
+        assert!(tag("untag p a").is_err());
+        assert_eq!(format!("{}", untag("Untag p a").unwrap()), "untag p a");
+        assert_eq!(
+            format!("{}", meta("meta p part=A-1 owner=").unwrap()),
+            "meta p part=A-1 owner="
+        );
+        assert!(meta("meta p part").is_err());
+
+        assert_eq!(
+            format!("{}", save("Save  my drawing.scene ").unwrap()),
+            "save my drawing.scene"
+        );
+        assert_eq!(
+            format!("{}", load("load /tmp/a.scene").unwrap()),
+            "load /tmp/a.scene"
+        );
+        assert_eq!(
+            format!("{}", save("Save --binary big.scene").unwrap()),
+            "save --binary big.scene"
+        );
+        assert!(load("save a.scene").is_err());
+        assert!(load("load --binary a.scene").is_err());
     }
 
     #[test]
@@ -389,9 +1819,70 @@ pub mod tests {
         test!(line, "line");
         test!(circle, "circle");
         test!(square, "square");
+        test!(ngon, "ngon");
+        test!(star, "star");
         test!(move_by, "move");
         test!(undo, "undo");
         test!(redo, "redo");
+        test!(undo_to, "undo-to");
+        test!(history, "history");
+        test!(branch, "branch");
+        test!(begin, "begin");
+        test!(commit, "commit");
+        test!(rollback, "rollback");
         test!(delete, "delete");
+        test!(connect, "connect");
+        test!(flip, "flip");
+        test!(mirror, "mirror");
+        test!(array_rect, "array-rect");
+        test!(array_polar, "array-polar");
+        test!(constrain, "constrain");
+        test!(unconstrain, "unconstrain");
+        test!(trim, "trim");
+        test!(extend, "extend");
+        test!(split, "split");
+        test!(join, "join");
+        test!(hull, "hull");
+        test!(triangulate, "triangulate");
+        test!(centroid, "centroid");
+        test!(offset, "offset");
+        test!(tag, "tag");
+        test!(untag, "untag");
+        test!(meta, "meta");
+        test!(select, "select");
+        test!(select_add, "select-add");
+        test!(select_clear, "select-clear");
+        test!(select_rect, "select-rect");
+        test!(find, "find");
+        test!(list, "list");
+        test!(list_all, "listAll");
+        test!(save, "save");
+        test!(load, "load");
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let position = |line: &str| {
+            let error = point(line).err().unwrap();
+            match *parse_error(error, "point", line)
+                .downcast::<ClevisError>()
+                .unwrap()
+            {
+                ClevisError::Parse { position, .. } => position,
+                error => panic!("{:?}", error),
+            }
+        };
+        assert_eq!(position("point p 1 x"), 10);
+        assert_eq!(position("point p 1"), 9);
+        assert_eq!(position("point p 1 2 3"), 12);
+        assert_eq!(position("point p 1 99999999999"), 10);
+
+        let error = ngon("ngon n 0 0 5 2").err().unwrap();
+        let error = parse_error(error, "ngon", "ngon n 0 0 5 2");
+        assert_eq!(error.to_string(), "A polygon needs at least 3 sides.");
+        assert!(matches!(
+            error.downcast_ref::<ClevisError>(),
+            Some(ClevisError::Validation(_))
+        ));
     }
 }
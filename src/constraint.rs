@@ -0,0 +1,344 @@
+use crate::error::ClevisError;
+use crate::shape::{DataType, Line, Point, Shapes};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+/// A geometric relation between shapes that the solver keeps true.
+///
+/// When a constraint involves two shapes, the solver adjusts the second
+/// one unless it is fixed or derived from other shapes, in which case the
+/// first one is adjusted instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    Parallel(String, String),
+    Perpendicular(String, String),
+    Horizontal(String),
+    Vertical(String),
+    EqualLength(String, String),
+    Coincident(String, String),
+    Fixed(String),
+    Distance(String, String, DataType),
+}
+
+/// Rounds of adjustments before the solver gives up.
+const MAX_ITERATIONS: usize = 100;
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parallel(a, b) => write!(f, "parallel {} {}", a, b),
+            Self::Perpendicular(a, b) => write!(f, "perpendicular {} {}", a, b),
+            Self::Horizontal(a) => write!(f, "horizontal {}", a),
+            Self::Vertical(a) => write!(f, "vertical {}", a),
+            Self::EqualLength(a, b) => write!(f, "equal-length {} {}", a, b),
+            Self::Coincident(a, b) => write!(f, "coincident {} {}", a, b),
+            Self::Fixed(a) => write!(f, "fixed {}", a),
+            Self::Distance(a, b, d) => write!(f, "distance {} {} {}", a, b, d),
+        }
+    }
+}
+
+fn get_line(shapes: &Shapes, name: &str) -> Result<Line, Box<dyn Error>> {
+    let shape = shapes
+        .get(name)
+        .ok_or_else(|| ClevisError::ShapeNotFound(name.to_string()))?;
+    let err_msg = format!("Shape {:?} is not a line.", name);
+    Ok(*shape.as_line().ok_or(ClevisError::Validation(err_msg))?)
+}
+
+fn get_center(shapes: &Shapes, name: &str) -> Result<Point, Box<dyn Error>> {
+    Ok(shapes
+        .get(name)
+        .ok_or_else(|| ClevisError::ShapeNotFound(name.to_string()))?
+        .center())
+}
+
+fn direction(line: &Line) -> Option<(f64, f64)> {
+    let len = line.length();
+    if len == 0.0 {
+        return None;
+    }
+    Some((
+        (line.1.x - line.0.x) as f64 / len,
+        (line.1.y - line.0.y) as f64 / len,
+    ))
+}
+
+/// A line with the same midpoint as `line`, pointing along `(ux, uy)`.
+fn rebuild(line: &Line, (mut ux, mut uy): (f64, f64), half: f64) -> Line {
+    let dx = (line.1.x - line.0.x) as f64;
+    let dy = (line.1.y - line.0.y) as f64;
+    if dx * ux + dy * uy < 0.0 {
+        ux = -ux;
+        uy = -uy;
+    }
+    let mx = (line.0.x + line.1.x) as f64 / 2.0;
+    let my = (line.0.y + line.1.y) as f64 / 2.0;
+    Line(
+        Point {
+            x: (mx - ux * half).round() as DataType,
+            y: (my - uy * half).round() as DataType,
+        },
+        Point {
+            x: (mx + ux * half).round() as DataType,
+            y: (my + uy * half).round() as DataType,
+        },
+    )
+}
+
+impl Constraint {
+    /// Names of the constrained shapes.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Self::Horizontal(a) | Self::Vertical(a) | Self::Fixed(a) => vec![a],
+            Self::Parallel(a, b)
+            | Self::Perpendicular(a, b)
+            | Self::EqualLength(a, b)
+            | Self::Coincident(a, b)
+            | Self::Distance(a, b, _) => vec![a, b],
+        }
+    }
+
+    /// Check that the constrained shapes exist and have the right type.
+    pub fn validate(&self, shapes: &Shapes) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Parallel(..)
+            | Self::Perpendicular(..)
+            | Self::EqualLength(..)
+            | Self::Horizontal(..)
+            | Self::Vertical(..) => {
+                for name in self.names() {
+                    get_line(shapes, name)?;
+                }
+            }
+            Self::Coincident(..) | Self::Fixed(..) | Self::Distance(..) => {
+                for name in self.names() {
+                    get_center(shapes, name)?;
+                }
+            }
+        }
+        if let Self::Distance(_, _, d) = self {
+            if *d < 0 {
+                return Err(ClevisError::validation("The distance can not be negative.").into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Adjust one shape towards satisfying the constraint.
+    /// Returns whether anything changed.
+    fn apply(&self, shapes: &mut Shapes, fixed: &HashSet<String>) -> Result<bool, Box<dyn Error>> {
+        let movable = |shapes: &Shapes, name: &str| {
+            !fixed.contains(name)
+                && shapes
+                    .get(name)
+                    .is_some_and(|shape| shape.dependencies().is_empty())
+        };
+        // (reference, target): the target is the shape the solver changes
+        let pick = |shapes: &Shapes, a: &'_ str, b: &'_ str| {
+            if movable(shapes, b) {
+                Some((a.to_string(), b.to_string()))
+            } else if movable(shapes, a) {
+                Some((b.to_string(), a.to_string()))
+            } else {
+                None
+            }
+        };
+
+        let (target, new_line) = match self {
+            Self::Fixed(_) => return Ok(false),
+            Self::Horizontal(a) | Self::Vertical(a) => {
+                if !movable(shapes, a) {
+                    return Ok(false);
+                }
+                let line = get_line(shapes, a)?;
+                let new_line = if let Self::Horizontal(_) = self {
+                    let y = ((line.0.y + line.1.y) as f64 / 2.0).round() as DataType;
+                    Line(Point { x: line.0.x, y }, Point { x: line.1.x, y })
+                } else {
+                    let x = ((line.0.x + line.1.x) as f64 / 2.0).round() as DataType;
+                    Line(Point { x, y: line.0.y }, Point { x, y: line.1.y })
+                };
+                (a.clone(), new_line)
+            }
+            Self::Parallel(a, b) | Self::Perpendicular(a, b) | Self::EqualLength(a, b) => {
+                let (reference, target) = match pick(shapes, a, b) {
+                    Some(picked) => picked,
+                    None => return Ok(false),
+                };
+                let reference = get_line(shapes, &reference)?;
+                let line = get_line(shapes, &target)?;
+                let new_line = match self {
+                    Self::EqualLength(..) => match direction(&line) {
+                        Some(dir) => rebuild(&line, dir, reference.length() / 2.0),
+                        None => return Ok(false),
+                    },
+                    _ => {
+                        let (ux, uy) = match direction(&reference) {
+                            Some(dir) => dir,
+                            None => return Ok(false),
+                        };
+                        let dir = match self {
+                            Self::Perpendicular(..) => (-uy, ux),
+                            _ => (ux, uy),
+                        };
+                        rebuild(&line, dir, line.length() / 2.0)
+                    }
+                };
+                (target, new_line)
+            }
+            Self::Coincident(a, b) | Self::Distance(a, b, _) => {
+                let (reference, target) = match pick(shapes, a, b) {
+                    Some(picked) => picked,
+                    None => return Ok(false),
+                };
+                let from = get_center(shapes, &reference)?;
+                let to = get_center(shapes, &target)?;
+                let goal = match self {
+                    Self::Distance(_, _, d) => {
+                        let (mut dx, mut dy) = ((to.x - from.x) as f64, (to.y - from.y) as f64);
+                        let len = dx.hypot(dy);
+                        if len == 0.0 {
+                            (dx, dy) = (1.0, 0.0);
+                        } else {
+                            (dx, dy) = (dx / len, dy / len);
+                        }
+                        Point {
+                            x: from.x + (dx * *d as f64).round() as DataType,
+                            y: from.y + (dy * *d as f64).round() as DataType,
+                        }
+                    }
+                    _ => from,
+                };
+                if goal == to {
+                    return Ok(false);
+                }
+                let shape = shapes
+                    .get_mut(&target)
+                    .ok_or_else(|| ClevisError::ShapeNotFound(target.clone()))?;
+                shape.move_by(goal.x - to.x, goal.y - to.y);
+                return Ok(true);
+            }
+        };
+
+        if get_line(shapes, &target)? == new_line {
+            return Ok(false);
+        }
+        shapes.insert(target, Box::new(new_line));
+        Ok(true)
+    }
+}
+
+/// Adjust the constrained shapes until every constraint holds.
+/// Returns `false` if the constraints still conflict after giving up.
+pub fn solve(shapes: &mut Shapes) -> Result<bool, Box<dyn Error>> {
+    let constraints = shapes.constraints.clone();
+    let fixed: HashSet<String> = constraints
+        .iter()
+        .filter_map(|constraint| match constraint {
+            Constraint::Fixed(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for constraint in constraints.iter() {
+            changed |= constraint.apply(shapes, &fixed)?;
+        }
+        if !changed {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    fn line(x1: DataType, y1: DataType, x2: DataType, y2: DataType) -> Box<dyn Shape> {
+        Box::new(Line(Point { x: x1, y: y1 }, Point { x: x2, y: y2 }))
+    }
+
+    #[test]
+    fn test_line_constraints() {
+        let mut shapes = Shapes::from([
+            ("l1".to_string(), line(0, 0, 10, 0)),
+            ("l2".to_string(), line(0, 0, 3, 4)),
+        ]);
+
+        shapes.constraints = vec![Constraint::Perpendicular("l1".into(), "l2".into())];
+        assert!(solve(&mut shapes).unwrap());
+        assert_eq!(
+            shapes["l2"].as_line(),
+            Some(&Line(Point { x: 2, y: -1 }, Point { x: 2, y: 5 }))
+        );
+
+        shapes.constraints = vec![
+            Constraint::Parallel("l1".into(), "l2".into()),
+            Constraint::EqualLength("l1".into(), "l2".into()),
+        ];
+        assert!(solve(&mut shapes).unwrap());
+        assert_eq!(
+            shapes["l2"].as_line(),
+            Some(&Line(Point { x: -3, y: 2 }, Point { x: 7, y: 2 }))
+        );
+
+        // a fixed second line turns the constraint around
+        shapes.insert("l3".into(), line(0, 0, 0, 5));
+        shapes.constraints = vec![
+            Constraint::Fixed("l3".into()),
+            Constraint::Horizontal("l3".into()),
+            Constraint::Parallel("l1".into(), "l3".into()),
+        ];
+        assert!(solve(&mut shapes).unwrap());
+        assert_eq!(
+            shapes["l3"].as_line(),
+            Some(&Line(Point { x: 0, y: 0 }, Point { x: 0, y: 5 }))
+        );
+        assert_eq!(
+            shapes["l1"].as_line(),
+            Some(&Line(Point { x: 5, y: -5 }, Point { x: 5, y: 5 }))
+        );
+    }
+
+    #[test]
+    fn test_point_constraints() {
+        let mut shapes = Shapes::from([
+            (
+                "p1".to_string(),
+                Box::new(Point { x: 0, y: 0 }) as Box<dyn Shape>,
+            ),
+            (
+                "p2".to_string(),
+                Box::new(Point { x: 3, y: 4 }) as Box<dyn Shape>,
+            ),
+            (
+                "p3".to_string(),
+                Box::new(Point { x: 9, y: 9 }) as Box<dyn Shape>,
+            ),
+        ]);
+        shapes.constraints = vec![
+            Constraint::Distance("p1".into(), "p2".into(), 10),
+            Constraint::Coincident("p2".into(), "p3".into()),
+        ];
+        assert!(solve(&mut shapes).unwrap());
+        assert_eq!(shapes["p2"].center(), Point { x: 6, y: 8 });
+        assert_eq!(shapes["p3"].center(), Point { x: 6, y: 8 });
+
+        assert_eq!(
+            Constraint::Parallel("p1".into(), "p2".into())
+                .validate(&shapes)
+                .unwrap_err()
+                .to_string(),
+            r#"Shape "p1" is not a line."#
+        );
+        assert_eq!(
+            Constraint::Distance("p1".into(), "p2".into(), 3).to_string(),
+            "distance p1 p2 3"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::executor::ExecutionError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// The errors commands, parsers and renderers share, so callers can tell
+/// them apart without matching on the message.
+#[derive(Debug)]
+pub enum ClevisError {
+    ShapeNotFound(String),
+    /// A shape with this name is already there.
+    NameConflict(String),
+    /// A line that could not be parsed. `position` is the byte offset in the
+    /// line where it stops making sense.
+    Parse {
+        message: String,
+        position: usize,
+    },
+    /// Arguments that parse but make no sense, like a polygon of 2 sides.
+    Validation(String),
+    /// The renderer could not write its output.
+    Render(io::Error),
+    /// A scene file that can not be loaded.
+    Scene(String),
+}
+
+impl ClevisError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::Validation(message.into())
+    }
+
+    /// A stable name of the kind of error, for tools reading the responses.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ShapeNotFound(_) => "shape_not_found",
+            Self::NameConflict(_) => "name_conflict",
+            Self::Parse { .. } => "parse_error",
+            Self::Validation(_) => "validation_error",
+            Self::Render(_) => "render_io",
+            Self::Scene(_) => "scene_error",
+        }
+    }
+
+    /// Mark the I/O errors of a renderer as such.
+    pub fn from_render(error: Box<dyn Error>) -> Box<dyn Error> {
+        match error.downcast::<io::Error>() {
+            Ok(error) => Box::new(Self::Render(*error)),
+            Err(error) => error,
+        }
+    }
+}
+
+impl Error for ClevisError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Render(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ClevisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShapeNotFound(name) => write!(f, "Shape {:?} is not found.", name),
+            Self::NameConflict(name) => write!(f, "Shape {:?} already exists.", name),
+            Self::Parse { message, .. } => write!(f, "{}", message),
+            Self::Validation(message) => write!(f, "{}", message),
+            Self::Render(error) => write!(f, "{}", error),
+            Self::Scene(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// The code of any error, `error` for the ones without a type of their own.
+pub fn error_code(error: &(dyn Error + 'static)) -> &'static str {
+    if let Some(error) = error.downcast_ref::<ClevisError>() {
+        error.code()
+    } else if let Some(error) = error.downcast_ref::<ExecutionError>() {
+        error.code()
+    } else {
+        "error"
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code() {
+        let error: Box<dyn Error> = ClevisError::ShapeNotFound("a".into()).into();
+        assert_eq!(error.to_string(), r#"Shape "a" is not found."#);
+        assert_eq!(error_code(error.as_ref()), "shape_not_found");
+
+        let error = io::Error::other("disk full");
+        let error = ClevisError::from_render(error.into());
+        assert_eq!(error.to_string(), "disk full");
+        assert_eq!(error_code(error.as_ref()), "render_io");
+        let error = crate::render::FileRenderer::new("no/such/directory/screen").unwrap_err();
+        assert_eq!(error.code(), "render_io");
+
+        let error: Box<dyn Error> = ExecutionError::NoCmdToUndo.into();
+        assert_eq!(error_code(error.as_ref()), "no_command_to_undo");
+        let error: Box<dyn Error> = "Something else.".into();
+        assert_eq!(error_code(error.as_ref()), "error");
+    }
+}
@@ -1,77 +1,888 @@
-use crate::command::Command;
-use crate::shape::Shapes;
+use crate::command::{Batch, Command, MoveBy};
+use crate::constraint::solve;
+use crate::error::{error_code, ClevisError};
+use crate::outcome::{quote, Outcome};
+use crate::shape::{update_dependents, Shape, Shapes};
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 
+/// A recorded command. The id stays the same when the command moves
+/// between the line of history and its branches.
+pub struct Entry {
+    pub id: usize,
+    pub cmd: Box<dyn Command>,
+    /// What `cmd.memory()` was when it was last run.
+    pub memory: usize,
+}
+
+impl Entry {
+    /// Measure the command again after it ran, keeping `total` in step.
+    fn measure(&mut self, total: &mut usize) {
+        *total -= self.memory;
+        self.memory = self.cmd.memory();
+        *total += self.memory;
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.cmd)
+    }
+}
+
+/// Undone commands that new commands replaced, kept to switch back to.
+/// They follow the entry with id `parent`, 0 being the start of history.
+pub struct Branch {
+    pub id: usize,
+    pub parent: usize,
+    pub entries: Vec<Entry>,
+}
+
+/// How much history to keep. The oldest commands are dropped first.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct HistoryLimits {
+    /// The most commands to keep, branches included.
+    pub max_entries: Option<usize>,
+    /// A rough budget in bytes for all recorded commands, branches included.
+    pub max_memory: Option<usize>,
+}
+
 #[derive(Default)]
 pub struct Executor {
-    pub executed: Vec<Box<dyn Command>>,
-    pub undone: Vec<Box<dyn Command>>,
+    pub executed: Vec<Entry>,
+    pub undone: Vec<Entry>,
+    /// Commands run since `begin`, recorded as one step on `commit`.
+    pub transaction: Option<Vec<Box<dyn Command>>>,
+    pub branches: Vec<Branch>,
+    pub limits: HistoryLimits,
+    /// The sum of the memory of all entries.
+    memory: usize,
+    last_entry_id: usize,
+    last_branch_id: usize,
 }
 
 #[derive(Debug)]
 pub enum ExecutionError {
     NoCmdToUndo,
     NoCmdToRedo,
+    NoTransaction,
+    InTransaction,
+    BranchNotFound(usize),
+    /// The branch grows off entries that no branch holds any more.
+    BranchOrphaned(usize),
+    /// A command failed inside a transaction, which was rolled back, or
+    /// failed to roll back with the `rollback` error.
+    RolledBack {
+        error: Box<dyn Error>,
+        rollback: Option<Box<dyn Error>>,
+    },
+    /// A command failed, and putting the shapes back as they were failed
+    /// too with the `restore` error, so they are left half changed.
+    Unrestored {
+        error: Box<dyn Error>,
+        restore: Box<dyn Error>,
+    },
+    /// Undoing the command failed. It is still the last executed one and
+    /// the shapes are as they were before.
+    UndoFailed {
+        command: String,
+        source: Box<dyn Error>,
+    },
+    /// Redoing the command failed. It is still the next one to redo and
+    /// the shapes are as they were before.
+    RedoFailed {
+        command: String,
+        source: Box<dyn Error>,
+    },
+}
+impl ExecutionError {
+    /// A stable name of the kind of error, for tools reading the responses.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ExecutionError::NoCmdToUndo => "no_command_to_undo",
+            ExecutionError::NoCmdToRedo => "no_command_to_redo",
+            ExecutionError::NoTransaction => "no_transaction",
+            ExecutionError::InTransaction => "in_transaction",
+            ExecutionError::BranchNotFound(_) => "branch_not_found",
+            ExecutionError::BranchOrphaned(_) => "branch_orphaned",
+            ExecutionError::RolledBack { error, .. } => error_code(error.as_ref()),
+            ExecutionError::Unrestored { error, .. } => error_code(error.as_ref()),
+            ExecutionError::UndoFailed { .. } => "undo_failed",
+            ExecutionError::RedoFailed { .. } => "redo_failed",
+        }
+    }
+}
+impl Error for ExecutionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExecutionError::UndoFailed { source, .. }
+            | ExecutionError::RedoFailed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
 }
-impl Error for ExecutionError {}
 impl std::fmt::Display for ExecutionError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                ExecutionError::NoCmdToUndo => "No command to undo.",
-                ExecutionError::NoCmdToRedo => "No command to redo.",
+        match self {
+            ExecutionError::NoCmdToUndo => write!(f, "No command to undo."),
+            ExecutionError::NoCmdToRedo => write!(f, "No command to redo."),
+            ExecutionError::NoTransaction => write!(f, "No transaction is open."),
+            ExecutionError::InTransaction => write!(f, "A transaction is open."),
+            ExecutionError::BranchNotFound(id) => write!(f, "Branch {} is not found.", id),
+            ExecutionError::BranchOrphaned(id) => write!(f, "Branch {} has lost its parent.", id),
+            ExecutionError::RolledBack {
+                error,
+                rollback: None,
+            } => write!(f, "{} The transaction was rolled back.", error),
+            ExecutionError::RolledBack {
+                error,
+                rollback: Some(rollback),
+            } => write!(
+                f,
+                "{} Rolling back the transaction failed: {}",
+                error, rollback
+            ),
+            ExecutionError::Unrestored { error, restore } => write!(
+                f,
+                "{} Putting the shapes back failed too, they are left half changed: {}",
+                error, restore
+            ),
+            ExecutionError::UndoFailed { command, source } => write!(
+                f,
+                "Could not undo {}, nothing was changed: {}",
+                command, source
+            ),
+            ExecutionError::RedoFailed { command, source } => write!(
+                f,
+                "Could not redo {}, nothing was changed: {}",
+                command, source
+            ),
+        }
+    }
+}
+
+/// What `settle` did: the warnings for the user and the shapes it changed,
+/// as they were before.
+type Settlement = (Vec<String>, Vec<(String, Box<dyn Shape>)>);
+
+/// Bring derived shapes and constraints up to date after a change, or
+/// leave the shapes as they were if it fails.
+fn settle(shapes: &mut Shapes) -> Result<Settlement, Box<dyn Error>> {
+    // only derived and constrained shapes can change
+    let mut names: Vec<&str> = shapes
+        .iter()
+        .filter(|(_, shape)| !shape.dependencies().is_empty())
+        .map(|(name, _)| name.as_str())
+        .chain(shapes.constraints.iter().flat_map(|c| c.names()))
+        .collect();
+    names.sort();
+    names.dedup();
+    let mut before: Vec<(String, Box<dyn Shape>)> = names
+        .into_iter()
+        .filter_map(|name| Some((name.to_string(), shapes.get(name)?.clone())))
+        .collect();
+
+    let solved = update_dependents(shapes)
+        .and_then(|()| solve(shapes))
+        .and_then(|solved| update_dependents(shapes).map(|()| solved));
+    let solved = match solved {
+        Ok(solved) => solved,
+        Err(error) => {
+            swap(shapes, &mut before);
+            return Err(error);
+        }
+    };
+    before.retain(|(name, shape)| format!("{:?}", shapes[name]) != format!("{:?}", shape));
+
+    let mut warnings = Vec::new();
+    if !solved {
+        warnings.push("The constraints can not be satisfied at the same time.".to_string());
+    }
+    Ok((warnings, before))
+}
+
+/// Exchange the kept shapes with the ones of the same name in `shapes`.
+/// Doing it twice changes nothing.
+fn swap(shapes: &mut Shapes, kept: &mut [(String, Box<dyn Shape>)]) {
+    for (name, shape) in kept.iter_mut() {
+        if let Some(current) = shapes.get_mut(name) {
+            std::mem::swap(current, shape);
+        }
+    }
+}
+
+/// A command together with what `settle` changed after it, so that undo
+/// puts back exactly the shapes as they were. Both steps either go through
+/// or leave the shapes alone.
+struct Settled {
+    cmd: Box<dyn Command>,
+    changed: Vec<(String, Box<dyn Shape>)>,
+    warnings: Vec<String>,
+}
+
+impl Settled {
+    fn new(cmd: Box<dyn Command>) -> Self {
+        Self {
+            cmd,
+            changed: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Settled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.cmd)
+    }
+}
+
+impl Command for Settled {
+    fn execute(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        self.cmd.execute(shapes)?;
+        if self.cmd.is_read_only() {
+            return Ok(());
+        }
+        match settle(shapes) {
+            Ok((warnings, changed)) => {
+                self.warnings = warnings;
+                self.changed = changed;
+                Ok(())
+            }
+            Err(error) => match self.cmd.undo(shapes) {
+                Ok(()) => Err(error),
+                Err(restore) => Err(ExecutionError::Unrestored { error, restore }.into()),
+            },
+        }
+    }
+    fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        swap(shapes, &mut self.changed);
+        if let Err(error) = self.cmd.undo(shapes) {
+            swap(shapes, &mut self.changed);
+            return Err(error);
+        }
+        self.changed.clear();
+        Ok(())
+    }
+    fn after_execute(
+        &mut self,
+        executor: &mut Executor,
+        shapes: &mut Shapes,
+    ) -> Result<bool, Box<dyn Error>> {
+        self.cmd.after_execute(executor, shapes)
+    }
+    fn is_read_only(&self) -> bool {
+        self.cmd.is_read_only()
+    }
+    fn outcome(&mut self) -> Outcome {
+        let mut outcome = self.cmd.outcome();
+        for warning in self.warnings.drain(..) {
+            if !outcome.warnings.contains(&warning) {
+                outcome.warnings.push(warning);
             }
-        )
+        }
+        outcome
+    }
+    fn memory(&self) -> usize {
+        let changed = self
+            .changed
+            .iter()
+            .map(|(name, shape)| name.len() + shape.memory());
+        std::mem::size_of_val(self) + self.cmd.memory() + changed.sum::<usize>()
+    }
+    fn coalesce(&mut self, next: &dyn Command) -> bool {
+        self.changed.is_empty() && self.cmd.coalesce(next)
+    }
+    fn as_move_by(&self) -> Option<&MoveBy> {
+        match self.changed.is_empty() {
+            true => self.cmd.as_move_by(),
+            false => None,
+        }
     }
 }
 
+/// Where each entry of the history is kept, by id, to put them back after
+/// a step that failed halfway.
+struct Layout {
+    executed: Vec<usize>,
+    undone: Vec<usize>,
+    branches: Vec<(usize, usize, Vec<usize>)>,
+    last_branch_id: usize,
+}
+
 impl Executor {
     pub fn execute(
         &mut self,
-        mut cmd: Box<dyn Command>,
+        cmd: Box<dyn Command>,
         shapes: &mut Shapes,
-    ) -> Result<(), Box<dyn Error>> {
-        cmd.execute(shapes)?;
-        if cmd.after_execute(self, shapes)? {
-            self.undone.clear();
-            self.executed.push(cmd);
+    ) -> Result<Outcome, Box<dyn Error>> {
+        // undo, redo and the like keep each of their steps whole themselves
+        let mut cmd = Settled::new(cmd);
+        let result = cmd
+            .execute(shapes)
+            .and_then(|()| cmd.after_execute(self, shapes));
+        let record = match result {
+            Ok(record) => record,
+            Err(error) => return Err(self.abort(shapes, error)),
+        };
+        let outcome = cmd.outcome();
+        if record {
+            match self.transaction.as_mut() {
+                Some(commands) => commands.push(Box::new(cmd)),
+                None => self.record(Box::new(cmd)),
+            }
         }
-        Ok(())
+        Ok(outcome)
     }
-    pub fn undo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
-        let mut cmd = self.executed.pop().ok_or(ExecutionError::NoCmdToUndo)?;
-        cmd.undo(shapes)?;
-        self.undone.push(cmd);
 
-        Ok(())
+    fn record(&mut self, cmd: Box<dyn Command>) {
+        // a branch hanging off the last entry needs it to stay as it is
+        if let Some(last) = self.executed.last_mut() {
+            let forked = !self.undone.is_empty()
+                || self.branches.iter().any(|branch| branch.parent == last.id);
+            if !forked && last.cmd.coalesce(cmd.as_ref()) {
+                last.measure(&mut self.memory);
+                return;
+            }
+        }
+        self.fork();
+        self.last_entry_id += 1;
+        let memory = cmd.memory();
+        self.memory += memory;
+        self.executed.push(Entry {
+            id: self.last_entry_id,
+            cmd,
+            memory,
+        });
+        self.trim();
+    }
+
+    /// Roughly how many bytes the recorded commands take.
+    pub fn memory(&self) -> usize {
+        self.memory
     }
 
-    pub fn redo(&mut self, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
-        let mut cmd = self.undone.pop().ok_or(ExecutionError::NoCmdToRedo)?;
-        cmd.execute(shapes)?;
-        self.executed.push(cmd);
+    /// How many commands are recorded, branches included.
+    fn len(&self) -> usize {
+        let branches = self.branches.iter().map(|branch| branch.entries.len());
+        self.executed.len() + self.undone.len() + branches.sum::<usize>()
+    }
 
-        Ok(())
+    /// Drop the oldest commands until the history is within its limits.
+    fn trim(&mut self) {
+        loop {
+            let too_many = (self.limits.max_entries).is_some_and(|max| self.len() > max);
+            let too_big = (self.limits.max_memory).is_some_and(|max| self.memory > max);
+            if !too_many && !too_big {
+                break;
+            }
+            if self.executed.is_empty() {
+                // only branches at the start are left, the oldest goes first
+                match self.branches.first().map(|branch| branch.id) {
+                    Some(id) => self.drop_branches(&|branch| branch.id != id),
+                    None => break,
+                }
+                continue;
+            }
+            // the start of history is now after the dropped command
+            let dropped = self.executed.remove(0);
+            self.memory -= dropped.memory;
+            self.drop_branches(&|branch| branch.parent != 0);
+            for branch in self.branches.iter_mut() {
+                if branch.parent == dropped.id {
+                    branch.parent = 0;
+                }
+            }
+        }
+    }
+
+    /// Drop the branches `keep` turns down, and the branches off them.
+    fn drop_branches(&mut self, keep: &dyn Fn(&Branch) -> bool) {
+        let mut dropped = 0;
+        self.branches.retain(|branch| {
+            let kept = keep(branch);
+            if !kept {
+                dropped += branch.entries.iter().map(|e| e.memory).sum::<usize>();
+            }
+            kept
+        });
+        self.memory -= dropped;
+        if dropped == 0 {
+            return;
+        }
+        let ids: Vec<usize> = (self.branches.iter())
+            .flat_map(|branch| branch.entries.iter().map(|entry| entry.id))
+            .chain(self.line().map(|entry| entry.id))
+            .collect();
+        self.drop_branches(&|branch| branch.parent == 0 || ids.contains(&branch.parent));
+    }
+
+    /// Keep the undone commands as a branch, before the line moves on.
+    /// Returns the id of the new branch.
+    fn fork(&mut self) -> Option<usize> {
+        if self.undone.is_empty() {
+            return None;
+        }
+        let mut entries = std::mem::take(&mut self.undone);
+        entries.reverse();
+        self.last_branch_id += 1;
+        self.branches.push(Branch {
+            id: self.last_branch_id,
+            parent: self.executed.last().map_or(0, |entry| entry.id),
+            entries,
+        });
+        Some(self.last_branch_id)
+    }
+
+    fn layout(&self) -> Layout {
+        let ids = |entries: &[Entry]| entries.iter().map(|entry| entry.id).collect();
+        Layout {
+            executed: ids(&self.executed),
+            undone: ids(&self.undone),
+            branches: (self.branches.iter())
+                .map(|branch| (branch.id, branch.parent, ids(&branch.entries)))
+                .collect(),
+            last_branch_id: self.last_branch_id,
+        }
+    }
+
+    /// Go back to an earlier layout of the history: undo the commands
+    /// applied since, redo the ones undone since and put every entry back
+    /// where it was. Entries that can not be put back are kept as a branch.
+    fn restore(&mut self, layout: Layout, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+        let kept = (self.executed.iter())
+            .zip(layout.executed.iter())
+            .take_while(|(entry, id)| entry.id == **id)
+            .count();
+        let mut loose = HashMap::new();
+        let mut result = Ok(());
+        while self.executed.len() > kept {
+            let mut entry = self.executed.pop().unwrap();
+            if let Err(error) = entry.cmd.undo(shapes) {
+                self.executed.push(entry);
+                result = Err(error);
+                break;
+            }
+            entry.measure(&mut self.memory);
+            loose.insert(entry.id, entry);
+        }
+        loose.extend(self.undone.drain(..).map(|entry| (entry.id, entry)));
+        for branch in self.branches.drain(..) {
+            loose.extend(branch.entries.into_iter().map(|entry| (entry.id, entry)));
+        }
+        if result.is_ok() {
+            for id in layout.executed[kept..].iter() {
+                let mut entry = loose.remove(id).unwrap();
+                if let Err(error) = entry.cmd.execute(shapes) {
+                    loose.insert(entry.id, entry);
+                    result = Err(error);
+                    break;
+                }
+                entry.measure(&mut self.memory);
+                self.executed.push(entry);
+            }
+        }
+
+        let mut take = |ids: &[usize]| -> Vec<Entry> {
+            ids.iter().filter_map(|id| loose.remove(id)).collect()
+        };
+        self.undone = take(&layout.undone);
+        self.branches = (layout.branches.iter())
+            .map(|(id, parent, ids)| Branch {
+                id: *id,
+                parent: *parent,
+                entries: take(ids),
+            })
+            .filter(|branch| !branch.entries.is_empty())
+            .collect();
+        self.last_branch_id = layout.last_branch_id;
+        if !loose.is_empty() {
+            let mut entries: Vec<Entry> = loose.into_values().collect();
+            entries.sort_by_key(|entry| entry.id);
+            self.last_branch_id += 1;
+            self.branches.push(Branch {
+                id: self.last_branch_id,
+                parent: self.executed.last().map_or(0, |entry| entry.id),
+                entries,
+            });
+        }
+        result
+    }
+
+    /// A command failed: roll back the open transaction, if any. Misused
+    /// undo, redo or transaction commands leave it open.
+    fn abort(&mut self, shapes: &mut Shapes, error: Box<dyn Error>) -> Box<dyn Error> {
+        let misused = match error.downcast_ref::<ExecutionError>() {
+            Some(ExecutionError::Unrestored { .. }) => false,
+            Some(_) => true,
+            None => false,
+        };
+        if self.transaction.is_none() || misused {
+            return error;
+        }
+        let rollback = self.rollback(shapes).err();
+        Box::new(ExecutionError::RolledBack { error, rollback })
+    }
+
+    pub fn begin(&mut self) -> Result<Outcome, Box<dyn Error>> {
+        if self.transaction.is_some() {
+            return Err(ExecutionError::InTransaction.into());
+        }
+        self.transaction = Some(Vec::new());
+        Ok(Outcome::message("Began a transaction.".to_string()))
+    }
+
+    /// Record the commands of the transaction as a single undo step.
+    pub fn commit(&mut self) -> Result<Outcome, Box<dyn Error>> {
+        let commands = self
+            .transaction
+            .take()
+            .ok_or(ExecutionError::NoTransaction)?;
+        let count = commands.len();
+        if count > 0 {
+            self.record(Box::new(Batch::new(commands)));
+        }
+        Ok(Outcome::message(format!("Committed {} commands.", count)))
+    }
+
+    /// Undo the commands of the transaction and close it.
+    pub fn rollback(&mut self, shapes: &mut Shapes) -> Result<Outcome, Box<dyn Error>> {
+        let commands = self
+            .transaction
+            .take()
+            .ok_or(ExecutionError::NoTransaction)?;
+        let count = commands.len();
+        let mut batch = Batch::new(commands);
+        match batch.undo(shapes) {
+            Ok(()) => Ok(Outcome::message(format!("Rolled back {} commands.", count))),
+            Err(error) => {
+                // still open, so the rollback can be tried again
+                self.transaction = Some(batch.into_commands());
+                Err(error)
+            }
+        }
+    }
+
+    pub fn undo(&mut self, shapes: &mut Shapes) -> Result<Outcome, Box<dyn Error>> {
+        if self.transaction.is_some() {
+            return Err(ExecutionError::InTransaction.into());
+        }
+        // the entry moves only once the undo went through
+        let entry = self
+            .executed
+            .last_mut()
+            .ok_or(ExecutionError::NoCmdToUndo)?;
+        entry
+            .cmd
+            .undo(shapes)
+            .map_err(|source| ExecutionError::UndoFailed {
+                command: entry.to_string(),
+                source,
+            })?;
+        entry.measure(&mut self.memory);
+        let outcome = Outcome::message(format!("Undid {}.", entry));
+        self.undone.extend(self.executed.pop());
+
+        Ok(outcome)
+    }
+
+    pub fn redo(&mut self, shapes: &mut Shapes) -> Result<Outcome, Box<dyn Error>> {
+        if self.transaction.is_some() {
+            return Err(ExecutionError::InTransaction.into());
+        }
+        let entry = self.undone.last_mut().ok_or(ExecutionError::NoCmdToRedo)?;
+        entry
+            .cmd
+            .execute(shapes)
+            .map_err(|source| ExecutionError::RedoFailed {
+                command: entry.to_string(),
+                source,
+            })?;
+        entry.measure(&mut self.memory);
+        let mut outcome = Outcome::message(format!("Redid {}.", entry));
+        outcome.warnings = entry.cmd.outcome().warnings;
+        self.executed.extend(self.undone.pop());
+
+        Ok(outcome)
+    }
+
+    /// Undo or redo `count` commands, one at a time. If one of them fails,
+    /// the ones before it are taken back again.
+    fn step_many(
+        &mut self,
+        count: usize,
+        shapes: &mut Shapes,
+        undo: bool,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        let step = |executor: &mut Self, shapes: &mut Shapes| {
+            if undo {
+                executor.undo(shapes)
+            } else {
+                executor.redo(shapes)
+            }
+        };
+        let (available, done, verb) = if undo {
+            (self.executed.len(), "undone", "Undid")
+        } else {
+            (self.undone.len(), "redone", "Redid")
+        };
+        if count == 1 {
+            return step(self, shapes);
+        }
+        if count == 0 {
+            let message = format!("No commands were {}, nothing was changed.", done);
+            return Ok(Outcome::message(message));
+        }
+        if count > available {
+            let err_msg = format!("Only {} commands can be {}.", available, done);
+            return Err(ClevisError::validation(err_msg).into());
+        }
+        let layout = self.layout();
+        let mut outcome = Outcome::message(format!("{} {} commands.", verb, count));
+        for _ in 0..count {
+            match step(self, shapes) {
+                Ok(step) => {
+                    for warning in step.warnings {
+                        if !outcome.warnings.contains(&warning) {
+                            outcome.warnings.push(warning);
+                        }
+                    }
+                }
+                Err(error) => {
+                    if let Err(restore) = self.restore(layout, shapes) {
+                        log::error!("{}", error);
+                        return Err(restore);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(outcome)
+    }
+
+    pub fn undo_many(
+        &mut self,
+        count: usize,
+        shapes: &mut Shapes,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        self.step_many(count, shapes, true)
+    }
+
+    pub fn redo_many(
+        &mut self,
+        count: usize,
+        shapes: &mut Shapes,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        self.step_many(count, shapes, false)
+    }
+
+    /// Undo or redo until the command numbered `index` in the history is
+    /// the last one applied. Index 0 undoes everything.
+    pub fn undo_to(
+        &mut self,
+        index: usize,
+        shapes: &mut Shapes,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        let applied = self.executed.len();
+        if index > applied + self.undone.len() {
+            let err_msg = format!("History index {} is out of range.", index);
+            return Err(ClevisError::validation(err_msg).into());
+        }
+        if index == applied {
+            let message = format!("Already at history index {}, nothing was changed.", index);
+            Ok(Outcome::message(message))
+        } else if index < applied {
+            self.undo_many(applied - index, shapes)
+        } else {
+            self.redo_many(index - applied, shapes)
+        }
+    }
+
+    /// The applied entries from the oldest, followed by the undone ones in
+    /// the order they would be redone.
+    fn line(&self) -> impl Iterator<Item = &Entry> {
+        self.executed.iter().chain(self.undone.iter().rev())
+    }
+
+    fn find_entry(&self, id: usize) -> Option<&Entry> {
+        let branches = self
+            .branches
+            .iter()
+            .flat_map(|branch| branch.entries.iter());
+        self.line().chain(branches).find(|entry| entry.id == id)
+    }
+
+    /// The applied commands numbered from the oldest, followed by the
+    /// undone ones in the order they would be redone.
+    pub fn history(&self) -> Outcome {
+        let mut lines = Vec::new();
+        for (i, entry) in self.line().enumerate() {
+            let mark = if i < self.executed.len() {
+                ""
+            } else {
+                " (undone)"
+            };
+            lines.push(format!("{:>3} {}{}", i + 1, entry, mark));
+        }
+        let quoted = |entries: Vec<&Entry>| -> String {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|entry| quote(&entry.to_string()))
+                .collect();
+            entries.join(",")
+        };
+        let data = format!(
+            r#"{{"executed":[{}],"undone":[{}]}}"#,
+            quoted(self.executed.iter().collect()),
+            quoted(self.undone.iter().rev().collect())
+        );
+        Outcome::message(lines.join("\n")).with_data(data)
+    }
+
+    /// The history with every branch indented under the command it
+    /// follows.
+    pub fn history_tree(&self) -> Outcome {
+        let mut lines = Vec::new();
+        self.branch_lines(0, 0, 1, &mut lines);
+        for (i, entry) in self.line().enumerate() {
+            let mark = if i < self.executed.len() {
+                ""
+            } else {
+                " (undone)"
+            };
+            lines.push(format!("{:>3} {}{}", i + 1, entry, mark));
+            self.branch_lines(entry.id, i + 1, 1, &mut lines);
+        }
+        Outcome::message(lines.join("\n"))
+    }
+
+    fn branch_lines(&self, parent: usize, depth: usize, indent: usize, lines: &mut Vec<String>) {
+        let pad = "    ".repeat(indent);
+        for branch in self
+            .branches
+            .iter()
+            .filter(|branch| branch.parent == parent)
+        {
+            lines.push(format!("{}branch {}:", pad, branch.id));
+            for (i, entry) in branch.entries.iter().enumerate() {
+                lines.push(format!("{}{:>3} {}", pad, depth + i + 1, entry));
+                self.branch_lines(entry.id, depth + i + 1, indent + 1, lines);
+            }
+        }
+    }
+
+    pub fn branch_list(&self) -> Outcome {
+        if self.branches.is_empty() {
+            return Outcome::message("No branches.".to_string()).with_data("[]".to_string());
+        }
+        let mut lines = Vec::new();
+        let mut objects = Vec::new();
+        for branch in self.branches.iter() {
+            let parent = self.find_entry(branch.parent);
+            let commands: Vec<String> = branch.entries.iter().map(|e| e.to_string()).collect();
+            lines.push(format!(
+                "branch {} {}: {}",
+                branch.id,
+                parent.map_or("at the start".to_string(), |entry| format!(
+                    "after {}",
+                    entry
+                )),
+                commands.join("; ")
+            ));
+            let commands: Vec<String> = commands.iter().map(|cmd| quote(cmd)).collect();
+            objects.push(format!(
+                r#"{{"id":{},"after":{},"commands":[{}]}}"#,
+                branch.id,
+                parent.map_or("null".to_string(), |entry| quote(&entry.to_string())),
+                commands.join(",")
+            ));
+        }
+        Outcome::message(lines.join("\n")).with_data(format!("[{}]", objects.join(",")))
+    }
+
+    /// Go back to where a branch splits off and replay it. The commands it
+    /// replaces become a new branch. If any step fails, the history and
+    /// the shapes are put back as they were.
+    pub fn switch_branch(
+        &mut self,
+        id: usize,
+        shapes: &mut Shapes,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        if self.transaction.is_some() {
+            return Err(ExecutionError::InTransaction.into());
+        }
+        let layout = self.layout();
+        self.switch_to(id, shapes).map_err(|error| {
+            if let Err(restore) = self.restore(layout, shapes) {
+                log::error!("{}", error);
+                return restore;
+            }
+            error
+        })
+    }
+
+    fn switch_to(&mut self, id: usize, shapes: &mut Shapes) -> Result<Outcome, Box<dyn Error>> {
+        let branch = self.branches.iter().find(|branch| branch.id == id);
+        let parent = branch.ok_or(ExecutionError::BranchNotFound(id))?.parent;
+
+        // a branch off another branch needs that one in the line first
+        if parent != 0 && !self.line().any(|entry| entry.id == parent) {
+            let outer = self
+                .branches
+                .iter()
+                .find(|branch| branch.entries.iter().any(|entry| entry.id == parent))
+                .map(|branch| branch.id)
+                .ok_or(ExecutionError::BranchOrphaned(id))?;
+            self.switch_to(outer, shapes)?;
+        }
+
+        let depth = self
+            .line()
+            .position(|entry| entry.id == parent)
+            .map_or(0, |i| i + 1);
+        let mut outcome = self.undo_to(depth, shapes)?;
+        let replaced = self.fork();
+        let index = self
+            .branches
+            .iter()
+            .position(|branch| branch.id == id)
+            .unwrap();
+        let mut entries = self.branches.remove(index).entries;
+        entries.reverse();
+        self.undone = entries;
+        outcome
+            .warnings
+            .extend(self.redo_many(self.undone.len(), shapes)?.warnings);
+
+        outcome.message = Some(match replaced {
+            Some(replaced) => format!(
+                "Switched to branch {}, the replaced commands are branch {}.",
+                id, replaced
+            ),
+            None => format!("Switched to branch {}.", id),
+        });
+        Ok(outcome)
     }
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::command::Control;
     use crate::command::*;
+    use crate::constraint::Constraint;
+    use crate::query::Query;
     use crate::render::tests::get_writer_render_result;
+    use crate::selector::Selector;
     use crate::shape::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
 
     #[test]
     fn test_redo_undo() {
         let mut shapes = Shapes::new();
         let mut executor = Executor::default();
 
-        let undo = Box::new(Control::Undo);
+        let undo = Box::new(Control::new(ControlKind::Undo(1)));
         assert_eq!(
             executor.execute(undo, &mut shapes).unwrap_err().to_string(),
             "No command to undo."
@@ -85,18 +896,19 @@ pub mod tests {
             "\np1 Point { x: 0, y: 0 }\n"
         );
 
-        let undo = Box::new(Control::Undo);
-        executor.execute(undo, &mut shapes).unwrap();
+        let undo = Box::new(Control::new(ControlKind::Undo(1)));
+        let outcome = executor.execute(undo, &mut shapes).unwrap();
+        assert_eq!(outcome.message.unwrap(), "Undid p1 Point { x: 0, y: 0 }.");
         assert_eq!(get_writer_render_result(&shapes), "\n");
 
-        let redo = Box::new(Control::Redo);
+        let redo = Box::new(Control::new(ControlKind::Redo(1)));
         executor.execute(redo, &mut shapes).unwrap();
         assert_eq!(
             get_writer_render_result(&shapes),
             "\np1 Point { x: 0, y: 0 }\n"
         );
 
-        let undo = Box::new(Control::Undo);
+        let undo = Box::new(Control::new(ControlKind::Undo(1)));
         executor.execute(undo, &mut shapes).unwrap();
         assert_eq!(get_writer_render_result(&shapes), "\n");
 
@@ -107,7 +919,7 @@ pub mod tests {
             "\np1 Point { x: 0, y: 0 }\n"
         );
 
-        let redo = Box::new(Control::Redo);
+        let redo = Box::new(Control::new(ControlKind::Redo(1)));
         assert_eq!(
             executor.execute(redo, &mut shapes).unwrap_err().to_string(),
             "No command to redo."
@@ -117,4 +929,808 @@ pub mod tests {
             "\np1 Point { x: 0, y: 0 }\n"
         );
     }
+
+    #[test]
+    fn test_connector_follows_shapes() {
+        let mut shapes = Shapes::new();
+        let mut executor = Executor::default();
+
+        let square = Square {
+            corner: Point { x: 0, y: 0 },
+            side: 10,
+            radius: 0,
+        };
+        let point = Point { x: 50, y: 5 };
+        executor
+            .execute(Box::new(DrawShape::new("a".into(), square)), &mut shapes)
+            .unwrap();
+        executor
+            .execute(Box::new(DrawShape::new("b".into(), point)), &mut shapes)
+            .unwrap();
+        let connector = Connector::new("a".into(), "b".into());
+        executor
+            .execute(Box::new(DrawShape::new("c".into(), connector)), &mut shapes)
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", shapes["c"]),
+            r#"Connector { from: "a", to: "b", line: Line(Point { x: 10, y: 5 }, Point { x: 50, y: 5 }) }"#
+        );
+
+        executor
+            .execute(Box::new(MoveBy::new("b".into(), 0, 45)), &mut shapes)
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", shapes["c"]),
+            r#"Connector { from: "a", to: "b", line: Line(Point { x: 10, y: 10 }, Point { x: 50, y: 50 }) }"#
+        );
+
+        assert_eq!(
+            executor
+                .execute(Box::new(Delete::new("b".into())), &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            r#"Shape "b" is referenced by ["c"]."#
+        );
+
+        executor
+            .execute(Box::new(Delete::new("c".into())), &mut shapes)
+            .unwrap();
+        executor
+            .execute(Box::new(Delete::new("b".into())), &mut shapes)
+            .unwrap();
+        executor.undo(&mut shapes).unwrap();
+        executor.undo(&mut shapes).unwrap();
+        executor.undo(&mut shapes).unwrap();
+        assert_eq!(
+            format!("{:?}", shapes["c"]),
+            r#"Connector { from: "a", to: "b", line: Line(Point { x: 10, y: 5 }, Point { x: 50, y: 5 }) }"#
+        );
+    }
+
+    #[test]
+    fn test_shapes_follow_points() {
+        let mut shapes = Shapes::new();
+        let mut executor = Executor::default();
+
+        let p1 = Point { x: 0, y: 0 };
+        let p2 = Point { x: 10, y: 0 };
+        executor
+            .execute(Box::new(DrawShape::new("p1".into(), p1)), &mut shapes)
+            .unwrap();
+        executor
+            .execute(Box::new(DrawShape::new("p2".into(), p2)), &mut shapes)
+            .unwrap();
+        let line = LinkedLine::new("p1".into(), "p2".into());
+        executor
+            .execute(Box::new(DrawShape::new("l1".into(), line)), &mut shapes)
+            .unwrap();
+        let circle = LinkedCircle::new("p2".into(), 5);
+        executor
+            .execute(Box::new(DrawShape::new("c1".into(), circle)), &mut shapes)
+            .unwrap();
+
+        executor
+            .execute(Box::new(MoveBy::new("p2".into(), 0, 10)), &mut shapes)
+            .unwrap();
+        assert_eq!(shapes["l1"].center(), Point { x: 5, y: 5 });
+        assert_eq!(shapes["c1"].center(), Point { x: 10, y: 10 });
+
+        assert_eq!(
+            executor
+                .execute(Box::new(MoveBy::new("c1".into(), 1, 1)), &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            r#"Shape "c1" follows ["p2"]."#
+        );
+        assert_eq!(
+            executor
+                .execute(Box::new(Delete::new("p2".into())), &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            r#"Shape "p2" is referenced by ["c1", "l1"]."#
+        );
+
+        executor.undo(&mut shapes).unwrap();
+        assert_eq!(shapes["c1"].center(), Point { x: 10, y: 0 });
+
+        let not_a_point = LinkedCircle::new("l1".into(), 5);
+        assert_eq!(
+            executor
+                .execute(
+                    Box::new(DrawShape::new("c2".into(), not_a_point)),
+                    &mut shapes
+                )
+                .unwrap_err()
+                .to_string(),
+            r#"Shape "l1" is not a point."#
+        );
+    }
+
+    #[test]
+    fn test_constraints() {
+        use crate::constraint::Constraint;
+
+        let mut shapes = Shapes::new();
+        let mut executor = Executor::default();
+
+        let l1 = Line(Point { x: 0, y: 0 }, Point { x: 10, y: 10 });
+        let l2 = Line(Point { x: 0, y: 20 }, Point { x: 10, y: 20 });
+        executor
+            .execute(Box::new(DrawShape::new("l1".into(), l1)), &mut shapes)
+            .unwrap();
+        executor
+            .execute(Box::new(DrawShape::new("l2".into(), l2)), &mut shapes)
+            .unwrap();
+
+        let parallel = Constraint::Parallel("l1".into(), "l2".into());
+        executor
+            .execute(Box::new(Constrain::new(parallel.clone())), &mut shapes)
+            .unwrap();
+        assert_eq!(
+            shapes["l2"].as_line(),
+            Some(&Line(Point { x: 1, y: 16 }, Point { x: 9, y: 24 }))
+        );
+        assert!(executor
+            .execute(Box::new(Constrain::new(parallel.clone())), &mut shapes)
+            .is_err());
+        assert_eq!(
+            executor
+                .execute(Box::new(Delete::new("l1".into())), &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            r#"Shape "l1" is referenced by ["parallel l1 l2"]."#
+        );
+
+        // the constraint is enforced again after l1 changes
+        let flip = Flip::new("l1".into(), FlipDirection::Horizontal, None);
+        executor.execute(Box::new(flip), &mut shapes).unwrap();
+        assert_eq!(
+            shapes["l2"].as_line(),
+            Some(&Line(Point { x: 9, y: 16 }, Point { x: 1, y: 24 }))
+        );
+
+        executor.undo(&mut shapes).unwrap();
+        executor.undo(&mut shapes).unwrap();
+        assert!(shapes.constraints.is_empty());
+        assert_eq!(shapes["l2"].as_line(), Some(&l2));
+
+        executor.redo(&mut shapes).unwrap();
+        executor
+            .execute(Box::new(Unconstrain::new(parallel)), &mut shapes)
+            .unwrap();
+        assert!(shapes.constraints.is_empty());
+        executor.undo(&mut shapes).unwrap();
+        assert_eq!(shapes.constraints.len(), 1);
+
+        // undoing a move puts back what the solver moved, without solving
+        let a = Point { x: 0, y: 0 };
+        let b = Point { x: 10, y: 0 };
+        executor
+            .execute(Box::new(DrawShape::new("a".into(), a)), &mut shapes)
+            .unwrap();
+        executor
+            .execute(Box::new(DrawShape::new("b".into(), b)), &mut shapes)
+            .unwrap();
+        let distance = Constraint::Distance("a".into(), "b".into(), 10);
+        executor
+            .execute(Box::new(Constrain::new(distance)), &mut shapes)
+            .unwrap();
+        let move_by = MoveBy::new("b".into(), 0, 5);
+        executor.execute(Box::new(move_by), &mut shapes).unwrap();
+        assert_eq!(shapes["b"].center(), Point { x: 9, y: 4 });
+        executor.undo(&mut shapes).unwrap();
+        assert_eq!(shapes["b"].center(), b);
+        executor.redo(&mut shapes).unwrap();
+        assert_eq!(shapes["b"].center(), Point { x: 9, y: 4 });
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut shapes = Shapes::new();
+        let mut executor = Executor::default();
+        let begin = || Box::new(Transaction::new(TransactionKind::Begin));
+
+        executor.execute(begin(), &mut shapes).unwrap();
+        assert_eq!(
+            executor
+                .execute(begin(), &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            "A transaction is open."
+        );
+        let point = DrawShape::new("a".into(), Point::default());
+        executor.execute(Box::new(point), &mut shapes).unwrap();
+        executor
+            .execute(Box::new(MoveBy::new("a".into(), 1, 1)), &mut shapes)
+            .unwrap();
+        let commit = Box::new(Transaction::new(TransactionKind::Commit));
+        let outcome = executor.execute(commit, &mut shapes).unwrap();
+        assert_eq!(outcome.message.unwrap(), "Committed 2 commands.");
+        assert_eq!(executor.executed.len(), 1);
+        assert_eq!(
+            executor.executed[0].to_string(),
+            "begin; a Point { x: 0, y: 0 }; move a 1 1; commit"
+        );
+
+        executor.undo(&mut shapes).unwrap();
+        assert!(shapes.is_empty());
+        executor.redo(&mut shapes).unwrap();
+        assert_eq!(shapes["a"].center(), Point { x: 1, y: 1 });
+
+        // a failure rolls back the commands before it
+        executor.execute(begin(), &mut shapes).unwrap();
+        executor
+            .execute(Box::new(MoveBy::new("a".into(), 5, 5)), &mut shapes)
+            .unwrap();
+        assert_eq!(
+            executor
+                .execute(Box::new(MoveBy::new("b".into(), 1, 1)), &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            r#"Shape "b" is not found. The transaction was rolled back."#
+        );
+        assert!(executor.transaction.is_none());
+        assert_eq!(shapes["a"].center(), Point { x: 1, y: 1 });
+        assert_eq!(executor.executed.len(), 1);
+
+        executor.execute(begin(), &mut shapes).unwrap();
+        executor
+            .execute(Box::new(Delete::new("a".into())), &mut shapes)
+            .unwrap();
+        let rollback = Box::new(Transaction::new(TransactionKind::Rollback));
+        executor.execute(rollback, &mut shapes).unwrap();
+        assert!(shapes.contains_key("a"));
+        let rollback = Box::new(Transaction::new(TransactionKind::Rollback));
+        assert_eq!(
+            executor
+                .execute(rollback, &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            "No transaction is open."
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let mut shapes = Shapes::new();
+        let mut executor = Executor::default();
+        for i in 0..4 {
+            let point = DrawShape::new(format!("p{}", i), Point { x: i, y: 0 });
+            executor.execute(Box::new(point), &mut shapes).unwrap();
+        }
+
+        let mut run = |kind: ControlKind, shapes: &mut Shapes| {
+            executor.execute(Box::new(Control::new(kind)), shapes)
+        };
+        let outcome = run(ControlKind::Undo(3), &mut shapes).unwrap();
+        assert_eq!(outcome.message.unwrap(), "Undid 3 commands.");
+        assert_eq!(shapes.len(), 1);
+        run(ControlKind::Redo(1), &mut shapes).unwrap();
+        let history = run(ControlKind::History, &mut shapes).unwrap();
+        assert_eq!(
+            history.message.unwrap(),
+            [
+                "  1 p0 Point { x: 0, y: 0 }",
+                "  2 p1 Point { x: 1, y: 0 }",
+                "  3 p2 Point { x: 2, y: 0 } (undone)",
+                "  4 p3 Point { x: 3, y: 0 } (undone)",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            history.data.unwrap(),
+            r#"{"executed":["p0 Point { x: 0, y: 0 }","p1 Point { x: 1, y: 0 }"],"undone":["p2 Point { x: 2, y: 0 }","p3 Point { x: 3, y: 0 }"]}"#
+        );
+
+        assert_eq!(
+            run(ControlKind::Redo(3), &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            "Only 2 commands can be redone."
+        );
+        run(ControlKind::UndoTo(3), &mut shapes).unwrap();
+        assert_eq!(shapes.len(), 3);
+        run(ControlKind::UndoTo(0), &mut shapes).unwrap();
+        assert!(shapes.is_empty());
+        run(ControlKind::RedoAll, &mut shapes).unwrap();
+        assert_eq!(shapes.len(), 4);
+        let outcome = run(ControlKind::RedoAll, &mut shapes).unwrap();
+        assert_eq!(
+            outcome.message.unwrap(),
+            "No commands were redone, nothing was changed."
+        );
+        let outcome = run(ControlKind::Undo(0), &mut shapes).unwrap();
+        assert_eq!(
+            outcome.message.unwrap(),
+            "No commands were undone, nothing was changed."
+        );
+        let outcome = run(ControlKind::UndoTo(4), &mut shapes).unwrap();
+        assert_eq!(
+            outcome.message.unwrap(),
+            "Already at history index 4, nothing was changed."
+        );
+        assert_eq!(
+            run(ControlKind::UndoTo(5), &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            "History index 5 is out of range."
+        );
+    }
+
+    #[test]
+    fn test_branches() {
+        let mut shapes = Shapes::new();
+        let mut executor = Executor::default();
+        let mut run =
+            |cmd: Box<dyn Command>, shapes: &mut Shapes| executor.execute(cmd, shapes).unwrap();
+        let control = |kind| Box::new(Control::new(kind));
+        let move_by = |name: &str, dx| Box::new(MoveBy::new(name.into(), dx, 0));
+
+        run(
+            Box::new(DrawShape::new("a".into(), Point::default())),
+            &mut shapes,
+        );
+        let b = Point { x: 0, y: 5 };
+        run(Box::new(DrawShape::new("b".into(), b)), &mut shapes);
+        run(move_by("a", 1), &mut shapes);
+        run(move_by("b", 2), &mut shapes);
+        run(control(ControlKind::Undo(2)), &mut shapes);
+        // the undone moves are kept as branch 1
+        run(move_by("a", 10), &mut shapes);
+        run(control(ControlKind::Undo(1)), &mut shapes);
+        run(move_by("a", 20), &mut shapes);
+        assert_eq!(shapes["a"].center(), Point { x: 20, y: 0 });
+
+        let list = run(control(ControlKind::BranchList), &mut shapes);
+        assert_eq!(
+            list.message.unwrap(),
+            "branch 1 after b Point { x: 0, y: 5 }: move a 1 0; move b 2 0\n\
+             branch 2 after b Point { x: 0, y: 5 }: move a 10 0"
+        );
+
+        let outcome = run(control(ControlKind::BranchSwitch(1)), &mut shapes);
+        assert_eq!(
+            outcome.message.unwrap(),
+            "Switched to branch 1, the replaced commands are branch 3."
+        );
+        assert_eq!(shapes["a"].center(), Point { x: 1, y: 0 });
+        assert_eq!(shapes["b"].center(), Point { x: 2, y: 5 });
+        run(control(ControlKind::Undo(1)), &mut shapes);
+        run(move_by("a", 5), &mut shapes);
+
+        let tree = run(control(ControlKind::HistoryTree), &mut shapes);
+        assert_eq!(
+            tree.message.unwrap(),
+            [
+                "  1 a Point { x: 0, y: 0 }",
+                "  2 b Point { x: 0, y: 5 }",
+                "    branch 2:",
+                "      3 move a 10 0",
+                "    branch 3:",
+                "      3 move a 20 0",
+                "  3 move a 1 0",
+                "    branch 4:",
+                "      4 move b 2 0",
+                "  4 move a 5 0",
+            ]
+            .join("\n")
+        );
+
+        run(control(ControlKind::BranchSwitch(2)), &mut shapes);
+        assert_eq!(shapes["a"].center(), Point { x: 10, y: 0 });
+        assert_eq!(executor.branches.len(), 3);
+        assert_eq!(
+            executor
+                .execute(control(ControlKind::BranchSwitch(2)), &mut shapes)
+                .unwrap_err()
+                .to_string(),
+            "Branch 2 is not found."
+        );
+
+        // branch 4 follows "move a 1 0", which is on a branch again now
+        executor
+            .execute(control(ControlKind::BranchSwitch(4)), &mut shapes)
+            .unwrap();
+        assert_eq!(shapes["a"].center(), Point { x: 1, y: 0 });
+        assert_eq!(shapes["b"].center(), Point { x: 2, y: 5 });
+        assert_eq!(executor.executed.len(), 4);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut shapes = Shapes::new();
+        let mut executor = Executor::default();
+        executor.limits.max_entries = Some(3);
+        for i in 0..5 {
+            let point = DrawShape::new(format!("p{}", i), Point { x: i, y: 0 });
+            executor.execute(Box::new(point), &mut shapes).unwrap();
+        }
+        assert_eq!(executor.executed.len(), 3);
+        assert_eq!(executor.executed[0].to_string(), "p2 Point { x: 2, y: 0 }");
+
+        // consecutive moves of a shape are undone together
+        for (name, dx) in [("p4", 1), ("p4", 2), ("p3", 1), ("p4", 4)] {
+            let cmd = MoveBy::new(name.into(), dx, 0);
+            executor.execute(Box::new(cmd), &mut shapes).unwrap();
+        }
+        let history: Vec<String> = executor.executed.iter().map(|e| e.to_string()).collect();
+        assert_eq!(history, ["move p4 3 0", "move p3 1 0", "move p4 4 0"]);
+        executor.undo(&mut shapes).unwrap();
+        executor.undo(&mut shapes).unwrap();
+        executor.undo(&mut shapes).unwrap();
+        assert_eq!(shapes["p4"].center(), Point { x: 4, y: 0 });
+
+        // dropping the oldest command drops the branches at the start
+        executor.limits.max_entries = None;
+        executor
+            .execute(Box::new(Delete::new("p0".into())), &mut shapes)
+            .unwrap();
+        assert_eq!(executor.branches.len(), 1);
+        let budget = executor.memory();
+        executor.limits.max_memory = Some(budget);
+        executor
+            .execute(Box::new(Delete::new("p1".into())), &mut shapes)
+            .unwrap();
+        assert!(executor.memory() <= budget);
+        assert_eq!(executor.executed.len(), 1);
+        assert!(executor.branches.is_empty());
+
+        // the commands on branches count towards the limits too
+        let mut executor = Executor::default();
+        executor.limits.max_entries = Some(2);
+        let mut run = |cmd: Box<dyn Command>, shapes: &mut Shapes| {
+            executor.execute(cmd, shapes).unwrap();
+        };
+        run(
+            Box::new(DrawShape::new("a".into(), Point::default())),
+            &mut shapes,
+        );
+        run(Box::new(Control::new(ControlKind::Undo(1))), &mut shapes);
+        run(
+            Box::new(DrawShape::new("b".into(), Point::default())),
+            &mut shapes,
+        );
+        let vertices = vec![Point::default(); 1000];
+        let polygon = DrawShape::new("b".into(), Polygon { vertices }).with_replace(true);
+        run(Box::new(polygon), &mut shapes);
+        assert_eq!(executor.executed.len(), 1);
+        assert!(executor.branches.is_empty());
+        assert!(executor.memory() > 1000 * std::mem::size_of::<Point>());
+    }
+
+    /// A command that fails on demand without touching the shapes.
+    struct Flaky {
+        fail_execute: Rc<Cell<bool>>,
+        fail_undo: Rc<Cell<bool>>,
+    }
+
+    impl fmt::Display for Flaky {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "flaky")
+        }
+    }
+
+    impl Command for Flaky {
+        fn execute(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+            match self.fail_execute.get() {
+                true => Err("Injected failure.".into()),
+                false => Ok(()),
+            }
+        }
+        fn undo(&mut self, _shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+            match self.fail_undo.get() {
+                true => Err("Injected failure.".into()),
+                false => Ok(()),
+            }
+        }
+        fn after_execute(
+            &mut self,
+            _executor: &mut Executor,
+            _shapes: &mut Shapes,
+        ) -> Result<bool, Box<dyn Error>> {
+            Ok(true)
+        }
+    }
+
+    /// Everything about the shapes, in an order that does not change.
+    fn state(shapes: &Shapes) -> String {
+        let mut lines: Vec<String> = shapes
+            .iter()
+            .map(|(name, shape)| format!("{} {:?} {:?}", name, shape, shapes.metadata.get(name)))
+            .collect();
+        lines.sort();
+        lines.push(format!("{:?}", shapes.constraints));
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_failed_undo_redo() {
+        let get_shapes = || {
+            let mut shapes = Shapes::from([
+                (
+                    "p".to_string(),
+                    Box::new(Point { x: 0, y: 0 }) as Box<dyn Shape>,
+                ),
+                (
+                    "l".to_string(),
+                    Box::new(Line(Point { x: 0, y: 0 }, Point { x: 10, y: 0 })) as Box<dyn Shape>,
+                ),
+                (
+                    "m".to_string(),
+                    Box::new(Line(Point { x: 5, y: -5 }, Point { x: 6, y: 5 })) as Box<dyn Shape>,
+                ),
+                (
+                    "r".to_string(),
+                    Box::new(Rectangle {
+                        corner: Point { x: 2, y: 2 },
+                        w: 4,
+                        h: 3,
+                        radius: 0,
+                    }) as Box<dyn Shape>,
+                ),
+                (
+                    "poly".to_string(),
+                    Box::new(Polygon {
+                        vertices: vec![
+                            Point { x: 0, y: 0 },
+                            Point { x: 8, y: 0 },
+                            Point { x: 8, y: 8 },
+                            Point { x: 0, y: 8 },
+                        ],
+                    }) as Box<dyn Shape>,
+                ),
+            ]);
+            shapes.insert(
+                "n".into(),
+                Box::new(Line(Point { x: 0, y: 20 }, Point { x: 10, y: 20 })),
+            );
+            shapes.insert(
+                "e".into(),
+                Box::new(Line(Point { x: 3, y: -5 }, Point { x: 3, y: -2 })),
+            );
+            shapes.insert(
+                "j1".into(),
+                Box::new(Line(Point { x: 0, y: 30 }, Point { x: 5, y: 30 })),
+            );
+            shapes.insert(
+                "j2".into(),
+                Box::new(Line(Point { x: 5, y: 30 }, Point { x: 9, y: 30 })),
+            );
+            shapes.constraints.push(Constraint::Horizontal("n".into()));
+            let mut metadata = Metadata::default();
+            metadata.tags.insert("pad".into());
+            shapes.metadata.insert("p".into(), metadata);
+            shapes
+        };
+        fn scene_path() -> std::path::PathBuf {
+            crate::journal::tests::temp_path("test_failed_undo_redo.scene")
+        }
+        let scene = Shapes::from([(
+            "s".to_string(),
+            Box::new(Point { x: 7, y: 7 }) as Box<dyn Shape>,
+        )]);
+        crate::scene::save(&scene, &scene_path(), crate::scene::SceneFormat::Text).unwrap();
+        let makes: Vec<fn() -> Box<dyn Command>> = vec![
+            || Box::new(DrawShape::new("q".into(), Point { x: 1, y: 1 })),
+            || {
+                Box::new(DrawShape::new(
+                    "c".into(),
+                    Connector::new("p".into(), "r".into()),
+                ))
+            },
+            || Box::new(MoveBy::new("p".into(), 1, 2)),
+            || Box::new(Delete::new("p".into())),
+            || Box::new(Flip::new("m".into(), FlipDirection::Horizontal, None)),
+            || {
+                let axis = Line(Point { x: 0, y: 0 }, Point { x: 0, y: 10 });
+                Box::new(Mirror::new("r".into(), "r2".into(), axis))
+            },
+            || {
+                let layout = ArrayLayout::Rect {
+                    rows: 1,
+                    cols: 3,
+                    dx: 5,
+                    dy: 0,
+                };
+                Box::new(Array::new("p".into(), layout))
+            },
+            || Box::new(Constrain::new(Constraint::Vertical("m".into()))),
+            || Box::new(Unconstrain::new(Constraint::Horizontal("n".into()))),
+            || {
+                Box::new(LineEdit::new(LineEditKind::Split {
+                    line: "l".into(),
+                    at: Point { x: 4, y: 0 },
+                }))
+            },
+            || {
+                Box::new(LineEdit::new(LineEditKind::Trim {
+                    line: "l".into(),
+                    boundary: "m".into(),
+                }))
+            },
+            || {
+                Box::new(LineEdit::new(LineEditKind::Extend {
+                    line: "e".into(),
+                    boundary: "l".into(),
+                }))
+            },
+            || {
+                Box::new(LineEdit::new(LineEditKind::Join {
+                    first: "j1".into(),
+                    second: "j2".into(),
+                }))
+            },
+            || Box::new(Hull::new("h".into(), vec!["p".into(), "r".into()])),
+            || Box::new(Triangulate::new("poly".into())),
+            || Box::new(Offset::new("r".into(), "r3".into(), 1, false)),
+            || {
+                let edit = MetadataEdit::Tag(vec!["via".into()]);
+                Box::new(EditMetadata::new("p".into(), edit))
+            },
+            || {
+                Box::new(ForEach::new(
+                    "move",
+                    Selector::parse("?").unwrap(),
+                    "1 1",
+                    Box::new(|name| Box::new(MoveBy::new(name, 1, 1))),
+                ))
+            },
+            || Box::new(Load::new(scene_path())),
+            || {
+                let selectors = vec![Selector::parse("p").unwrap()];
+                Box::new(Select::new(SelectMode::Set(selectors)))
+            },
+            || Box::new(Find::new(Query::parse("type=point").unwrap(), true)),
+        ];
+
+        for make in makes {
+            let mut shapes = get_shapes();
+            let mut executor = Executor::default();
+            let fail_execute = Rc::new(Cell::new(false));
+            let fail_undo = Rc::new(Cell::new(false));
+            // undo runs backwards, so the command is undone before the
+            // first flaky fails, and redone before the last one fails
+            let batch = Batch::new(vec![
+                Box::new(Flaky {
+                    fail_execute: Rc::new(Cell::new(false)),
+                    fail_undo: fail_undo.clone(),
+                }),
+                make(),
+                Box::new(Flaky {
+                    fail_execute: fail_execute.clone(),
+                    fail_undo: Rc::new(Cell::new(false)),
+                }),
+            ]);
+            let name = batch.to_string();
+            let before = state(&shapes);
+            executor.execute(Box::new(batch), &mut shapes).unwrap();
+            let after = state(&shapes);
+            // select and find leave the shapes alone
+            if !name.contains("select") && !name.contains("find") {
+                assert_ne!(before, after, "{}", name);
+            }
+
+            fail_undo.set(true);
+            let error = executor.undo(&mut shapes).unwrap_err();
+            assert!(
+                matches!(
+                    error.downcast_ref::<ExecutionError>(),
+                    Some(ExecutionError::UndoFailed { .. })
+                ),
+                "{}",
+                name
+            );
+            assert_eq!(state(&shapes), after, "{}", name);
+            assert_eq!((executor.executed.len(), executor.undone.len()), (1, 0));
+            fail_undo.set(false);
+            executor.undo(&mut shapes).unwrap();
+            assert_eq!(state(&shapes), before, "{}", name);
+
+            fail_execute.set(true);
+            let error = executor.redo(&mut shapes).unwrap_err();
+            assert!(
+                matches!(
+                    error.downcast_ref::<ExecutionError>(),
+                    Some(ExecutionError::RedoFailed { .. })
+                ),
+                "{}",
+                name
+            );
+            assert_eq!(state(&shapes), before, "{}", name);
+            assert_eq!((executor.executed.len(), executor.undone.len()), (0, 1));
+            fail_execute.set(false);
+            executor.redo(&mut shapes).unwrap();
+            assert_eq!(state(&shapes), after, "{}", name);
+        }
+        std::fs::remove_file(scene_path()).unwrap();
+    }
+
+    #[test]
+    fn test_unrestored() {
+        let flaky = |fail_execute: bool, fail_undo: bool| -> Box<dyn Command> {
+            Box::new(Flaky {
+                fail_execute: Rc::new(Cell::new(fail_execute)),
+                fail_undo: Rc::new(Cell::new(fail_undo)),
+            })
+        };
+        let mut shapes = Shapes::new();
+        let mut executor = Executor::default();
+        // the second one fails and undoing the first one fails too
+        let batch = Batch::new(vec![flaky(false, true), flaky(true, false)]);
+        let error = executor.execute(Box::new(batch), &mut shapes).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ExecutionError>(),
+            Some(ExecutionError::Unrestored { .. })
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Injected failure. Putting the shapes back failed too, they are left half changed: \
+             Injected failure."
+        );
+        assert!(executor.executed.is_empty());
+
+        // it still rolls back the transaction it happened in
+        executor.begin().unwrap();
+        let batch = Batch::new(vec![flaky(false, true), flaky(true, false)]);
+        let error = executor.execute(Box::new(batch), &mut shapes).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("The transaction was rolled back."));
+        assert!(executor.transaction.is_none());
+    }
+
+    #[test]
+    fn test_failed_control() {
+        let mut shapes = Shapes::new();
+        let mut executor = Executor::default();
+        let fail_undo = Rc::new(Cell::new(false));
+        let flaky = Box::new(Flaky {
+            fail_execute: Rc::new(Cell::new(false)),
+            fail_undo: fail_undo.clone(),
+        });
+        executor.execute(flaky, &mut shapes).unwrap();
+        let cmd = Box::new(DrawShape::new("a".into(), Point::default()));
+        executor.execute(cmd, &mut shapes).unwrap();
+
+        fail_undo.set(true);
+        let undo = Box::new(Control::new(ControlKind::Undo(2)));
+        assert_eq!(
+            executor.execute(undo, &mut shapes).unwrap_err().to_string(),
+            "Could not undo flaky, nothing was changed: Injected failure."
+        );
+        // the first undo is taken back again, so nothing changed at all
+        assert_eq!((executor.executed.len(), executor.undone.len()), (2, 0));
+        assert_eq!(
+            get_writer_render_result(&shapes),
+            "\na Point { x: 0, y: 0 }\n"
+        );
+
+        fail_undo.set(false);
+        let undo = Box::new(Control::new(ControlKind::Undo(2)));
+        executor.execute(undo, &mut shapes).unwrap();
+        assert_eq!((executor.executed.len(), executor.undone.len()), (0, 2));
+
+        // the undone commands become branch 1, which fails to replay
+        let cmd = Box::new(DrawShape::new("b".into(), Point { x: 1, y: 1 }));
+        executor.execute(cmd, &mut shapes).unwrap();
+        let fail_execute = Rc::new(Cell::new(true));
+        let flaky = Box::new(Flaky {
+            fail_execute: fail_execute.clone(),
+            fail_undo: Rc::new(Cell::new(false)),
+        });
+        executor.branches[0].entries[0].cmd = flaky;
+        let tree = executor.history_tree().message;
+        let switch = Box::new(Control::new(ControlKind::BranchSwitch(1)));
+        assert!(executor.execute(switch, &mut shapes).is_err());
+        assert_eq!(executor.history_tree().message, tree);
+        assert_eq!(
+            get_writer_render_result(&shapes),
+            "\nb Point { x: 1, y: 1 }\n"
+        );
+
+        fail_execute.set(false);
+        let switch = Box::new(Control::new(ControlKind::BranchSwitch(1)));
+        executor.execute(switch, &mut shapes).unwrap();
+        assert_eq!(executor.executed.len(), 2);
+    }
 }
@@ -0,0 +1,274 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// What a journal holds for a command that went through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    /// The command line, as typed.
+    Line(String),
+    /// A scene loaded from `path`. Replaying it loads the copy of the file
+    /// in `snapshot` instead, as the file may have changed since.
+    Scene { path: PathBuf, snapshot: PathBuf },
+}
+
+impl Entry {
+    /// A scene takes a header line and an indented line with its snapshot,
+    /// so a scene cut short by a crash can be told.
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Self::Line(line) => writeln!(out, "{}", line.trim()),
+            Self::Scene { path, snapshot } => {
+                writeln!(out, "scene {}\n  {}", path.display(), snapshot.display())
+            }
+        }
+    }
+
+    /// The complete entries at the start of `text`, and how many bytes they
+    /// take. An entry cut short by a crash, and whatever follows, is left out.
+    fn read_all(text: &str) -> (Vec<Self>, usize) {
+        let complete = &text[..text.rfind('\n').map_or(0, |end| end + 1)];
+        let mut lines = complete.split_inclusive('\n');
+        let (mut entries, mut kept) = (Vec::new(), 0);
+        while let Some(line) = lines.next() {
+            let mut size = line.len();
+            let entry = match line.trim_end().strip_prefix("scene ") {
+                Some(path) => {
+                    let snapshot = match lines.next() {
+                        Some(snapshot) if snapshot.starts_with("  ") => snapshot,
+                        _ => break,
+                    };
+                    size += snapshot.len();
+                    Self::Scene {
+                        path: PathBuf::from(path),
+                        snapshot: PathBuf::from(snapshot[2..].trim_end()),
+                    }
+                }
+                None => Self::Line(line.trim_end().to_string()),
+            };
+            entries.push(entry);
+            kept += size;
+        }
+        (entries, kept)
+    }
+}
+
+/// Where the scene of the entry at `index` is kept for the journal `path`.
+fn snapshot_path(path: &Path, index: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}.scene", path.display(), index))
+}
+
+/// An append-only record of a session, so the session can be replayed
+/// after a crash. Undo and redo are commands like any other, so replaying
+/// the entries also restores the history. Loaded scenes are copied next to
+/// the journal as they are. The file is locked while a session uses it,
+/// and removed with the copies when the session ends normally.
+///
+/// Entries are written straight to the file, not buffered, so they outlive
+/// the process. They are not synced to the disk one by one, which would
+/// make every command wait for it.
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+    /// How many entries the journal holds.
+    entries: usize,
+}
+
+impl Journal {
+    /// Take the journal at `path` for this session, leaving its content as
+    /// it is. Fails when another session holds it.
+    pub fn lock(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        if let Err(error) = file.try_lock() {
+            return Err(match error {
+                fs::TryLockError::WouldBlock => io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("{} is in use by another session.", path.display()),
+                ),
+                fs::TryLockError::Error(error) => error,
+            });
+        }
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            entries: 0,
+        })
+    }
+
+    fn read(&mut self) -> io::Result<String> {
+        let mut text = String::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    /// The entries left behind by a session that did not end.
+    pub fn pending(&mut self) -> io::Result<Vec<Entry>> {
+        Ok(Entry::read_all(&self.read()?).0)
+    }
+
+    /// Remove the copies of the scenes loaded by `entries`. The ones
+    /// already gone do not matter.
+    fn remove_snapshots(entries: &[Entry]) -> io::Result<()> {
+        for entry in entries {
+            if let Entry::Scene { snapshot, .. } = entry {
+                match fs::remove_file(snapshot) {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the pending entries in the journal `old`, moving the copies of
+    /// their scenes along, before `start` drops them.
+    pub fn set_aside(&mut self, old: &Path) -> io::Result<()> {
+        let mut out = Vec::new();
+        for (index, entry) in self.pending()?.into_iter().enumerate() {
+            let entry = match entry {
+                Entry::Scene { path, snapshot } => {
+                    let kept = snapshot_path(old, index);
+                    fs::rename(&snapshot, &kept)?;
+                    Entry::Scene {
+                        path,
+                        snapshot: kept,
+                    }
+                }
+                line => line,
+            };
+            entry.write(&mut out)?;
+        }
+        fs::write(old, out)
+    }
+
+    /// Start recording this session. With `resume`, the pending entries
+    /// are kept and the new ones follow them, otherwise they are dropped.
+    pub fn start(&mut self, resume: bool) -> io::Result<()> {
+        let (entries, kept) = Entry::read_all(&self.read()?);
+        let kept = match resume {
+            true => kept,
+            false => {
+                Self::remove_snapshots(&entries)?;
+                0
+            }
+        };
+        self.entries = if resume { entries.len() } else { 0 };
+        self.file.set_len(kept as u64)
+    }
+
+    /// Copy the scene file at `path` for the next entry, which has to be
+    /// the returned one.
+    pub fn snapshot(&self, path: &Path) -> io::Result<Entry> {
+        let snapshot = snapshot_path(&self.path, self.entries);
+        fs::copy(path, &snapshot)?;
+        Ok(Entry::Scene {
+            path: path.to_path_buf(),
+            snapshot,
+        })
+    }
+
+    /// Record a command that went through.
+    pub fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        entry.write(&mut bytes)?;
+        self.file.write_all(&bytes)?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// The session ended normally, there is nothing to recover.
+    pub fn finish(mut self) -> io::Result<()> {
+        Self::remove_snapshots(&self.pending()?)?;
+        fs::remove_file(&self.path)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("clevis-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_journal() {
+        let path = temp_path("test_journal");
+        let scene_file = temp_path("test_journal a b.scene");
+        let old = temp_path("test_journal.old");
+        let line = |line: &str| Entry::Line(line.to_string());
+        fs::write(&scene_file, "clevis-scene 1\npoint p x=0 y=0\n").unwrap();
+
+        let mut journal = Journal::lock(&path).unwrap();
+        assert!(journal.pending().unwrap().is_empty());
+        journal.start(false).unwrap();
+        journal.append(&line("point p 0 0")).unwrap();
+        let scene = journal.snapshot(&scene_file).unwrap();
+        journal.append(&scene).unwrap();
+        journal.append(&line("  move p 1 1\n")).unwrap();
+        // the journal belongs to one session at a time
+        assert_eq!(
+            Journal::lock(&path).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+        drop(journal);
+
+        // the copy stays as it was loaded
+        fs::remove_file(&scene_file).unwrap();
+        let snapshot = snapshot_path(&path, 1);
+        assert_eq!(
+            scene,
+            Entry::Scene {
+                path: scene_file.clone(),
+                snapshot: snapshot.clone()
+            }
+        );
+        assert!(fs::read_to_string(&snapshot).unwrap().contains("point p"));
+
+        // a crash in the middle of a scene
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"scene c.scene\n").unwrap();
+        let mut journal = Journal::lock(&path).unwrap();
+        let pending = [line("point p 0 0"), scene.clone(), line("move p 1 1")];
+        assert_eq!(journal.pending().unwrap(), pending);
+
+        journal.start(true).unwrap();
+        journal.append(&line("undo")).unwrap();
+        assert_eq!(journal.pending().unwrap()[3..], [line("undo")]);
+        drop(journal);
+
+        // an unfinished journal that is not replayed keeps its scenes
+        let mut journal = Journal::lock(&path).unwrap();
+        journal.set_aside(&old).unwrap();
+        journal.start(false).unwrap();
+        assert!(!snapshot.exists());
+        let kept = snapshot_path(&old, 1);
+        let mut old_journal = Journal::lock(&old).unwrap();
+        assert_eq!(
+            old_journal.pending().unwrap()[1],
+            Entry::Scene {
+                path: scene_file.clone(),
+                snapshot: kept.clone()
+            }
+        );
+        assert!(kept.exists());
+        old_journal.finish().unwrap();
+        assert!(!kept.exists() && !old.exists());
+
+        journal.append(&line("redo")).unwrap();
+        assert_eq!(journal.pending().unwrap(), [line("redo")]);
+        fs::write(&scene_file, "clevis-scene 1\n").unwrap();
+        let scene = journal.snapshot(&scene_file).unwrap();
+        journal.append(&scene).unwrap();
+        assert!(snapshot.exists());
+        journal.finish().unwrap();
+        assert!(!path.exists() && !snapshot.exists());
+        fs::remove_file(&scene_file).unwrap();
+    }
+}
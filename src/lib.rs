@@ -2,7 +2,14 @@ pub mod app;
 pub mod args;
 pub mod command;
 pub mod commander;
+pub mod constraint;
+pub mod error;
 pub mod executor;
+pub mod journal;
 pub mod log;
+pub mod outcome;
+pub mod query;
 pub mod render;
+pub mod scene;
+pub mod selector;
 pub mod shape;
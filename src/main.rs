@@ -1,5 +1,6 @@
 use std::error::Error;
-use std::io::Write;
+use std::io::{self, Write};
+use std::path::PathBuf;
 
 use clap::Parser;
 
@@ -7,6 +8,7 @@ use clevis::app::App;
 use clevis::args::Args;
 use clevis::commander::cli_commander::HELP_INFO;
 use clevis::commander::CliCommander;
+use clevis::journal::Journal;
 
 fn init_logger() {
     env_logger::Builder::new()
@@ -37,19 +39,75 @@ fn init_logger() {
         .init();
 }
 
+/// Ask a yes or no question on the terminal, no being the default.
+fn confirm(question: &str) -> io::Result<bool> {
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Offer to replay the journal of a session that did not end, then journal
+/// this one. A journal that is not replayed is kept aside as `<file>.old`.
+/// Tools using `--json` are not asked, they replay with `--recover`.
+/// Tells whether the journal is replayed.
+fn open_journal(
+    args: &Args,
+    commander: &mut CliCommander<io::BufReader<io::Stdin>, io::Stdout, io::Stderr>,
+) -> Result<bool, Box<dyn Error>> {
+    let path = &args.journal;
+    let mut journal = Journal::lock(path)?;
+    let pending = journal.pending()?;
+    let resume = match (pending.is_empty(), args.recover, args.json) {
+        (true, _, _) => false,
+        (false, true, _) => true,
+        (false, false, true) => false,
+        (false, false, false) => confirm(&format!(
+            "{} holds {} commands of a session that did not end. Replay them?",
+            path.display(),
+            pending.len()
+        ))?,
+    };
+    if resume {
+        eprintln!("Replaying {} commands.", pending.len());
+        commander.replay(pending);
+    } else if !pending.is_empty() {
+        let old = PathBuf::from(format!("{}.old", path.display()));
+        journal.set_aside(&old)?;
+        eprintln!("The unfinished journal is kept as {}.", old.display());
+    }
+    journal.start(resume)?;
+    commander.set_journal(journal);
+    Ok(resume)
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
     init_logger();
-    println!("Available commands:");
-    for (cmd, info) in HELP_INFO.iter() {
-        println!("    {:15}{}", cmd, info);
+    if !args.json {
+        println!("Available commands:");
+        for (cmd, info) in HELP_INFO.iter() {
+            println!("    {:15}{}", cmd, info);
+        }
     }
 
-    let mut app: App = args.try_into()?;
+    let mut commander = CliCommander::default();
+    commander.set_json(args.json);
+    let resumed = !args.no_journal && open_journal(&args, &mut commander)?;
+    if let Some(path) = &args.open {
+        if resumed {
+            eprintln!(
+                "{} is not opened, the replayed session brings its own drawing.",
+                path.display()
+            );
+        } else {
+            commander.open(path);
+        }
+    }
 
-    let commander = CliCommander::default();
-    app.run(commander);
+    let mut app: App = args.try_into()?;
+    app.serve(commander);
 
     Ok(())
 }
@@ -0,0 +1,123 @@
+use crate::error::{error_code, ClevisError};
+use std::error::Error;
+use std::path::PathBuf;
+
+/// What a command has to tell the user once it ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Outcome {
+    /// A line for people, like "move applied to 3 shapes."
+    pub message: Option<String>,
+    /// The answer of a query as a JSON value, for tools.
+    pub data: Option<String>,
+    /// Things that went wrong without failing the command.
+    pub warnings: Vec<String>,
+    /// How a journal records the command, when not by the line typed.
+    pub journal: Option<Journaled>,
+}
+
+/// What replays a command after a crash, for commands whose line would not
+/// do the same again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Journaled {
+    /// Nothing, the command did not change the drawing.
+    Nothing,
+    /// The scene loaded from `path`, which the journal keeps a copy of.
+    Scene { path: PathBuf },
+}
+
+impl Outcome {
+    pub fn message(message: String) -> Self {
+        Self {
+            message: Some(message),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_data(mut self, data: String) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn with_journal(mut self, journal: Journaled) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.message.is_none() && self.data.is_none() && self.warnings.is_empty()
+    }
+
+    /// The outcome as a JSON response.
+    pub fn to_json(&self) -> String {
+        let warnings: Vec<String> = self.warnings.iter().map(|w| quote(w)).collect();
+        format!(
+            r#"{{"ok":true,"message":{},"data":{},"warnings":[{}]}}"#,
+            self.message.as_deref().map_or("null".to_string(), quote),
+            self.data.as_deref().unwrap_or("null"),
+            warnings.join(",")
+        )
+    }
+}
+
+/// A failed command as a JSON response. Parse errors also tell where in
+/// the line they are.
+pub fn error_json(error: &(dyn Error + 'static)) -> String {
+    let position = match error.downcast_ref::<ClevisError>() {
+        Some(ClevisError::Parse { position, .. }) => format!(r#","position":{}"#, position),
+        _ => String::new(),
+    };
+    format!(
+        r#"{{"ok":false,"code":{},"error":{}{}}}"#,
+        quote(error_code(error)),
+        quote(&error.to_string()),
+        position
+    )
+}
+
+/// A JSON string literal.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let mut outcome = Outcome::message("Found \"a\".".into()).with_data(r#"["a"]"#.into());
+        outcome.warnings.push("tab\there".into());
+        assert_eq!(
+            outcome.to_json(),
+            r#"{"ok":true,"message":"Found \"a\".","data":["a"],"warnings":["tab\u0009here"]}"#
+        );
+        assert_eq!(
+            Outcome::default().to_json(),
+            r#"{"ok":true,"message":null,"data":null,"warnings":[]}"#
+        );
+        let error: Box<dyn Error> = "Something went wrong.".into();
+        assert_eq!(
+            error_json(error.as_ref()),
+            r#"{"ok":false,"code":"error","error":"Something went wrong."}"#
+        );
+        let error = ClevisError::Parse {
+            message: "Bad line.".into(),
+            position: 6,
+        };
+        assert_eq!(
+            error_json(&error),
+            r#"{"ok":false,"code":"parse_error","error":"Bad line.","position":6}"#
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use crate::selector::Selector;
+use crate::shape::{Metadata, Shape, Shapes};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Eq => "=",
+                Self::Ne => "!=",
+                Self::Lt => "<",
+                Self::Le => "<=",
+                Self::Gt => ">",
+                Self::Ge => ">=",
+            }
+        )
+    }
+}
+
+/// A filter over shapes, like `type=circle and radius>10 and tag=pad`.
+///
+/// A field is one of `type`, `name`, `tag`, `cx` and `cy` (the center), a
+/// parameter of the shape such as `radius`, or a metadata key. Numbers are
+/// compared as numbers, anything else as text, and `=` accepts globs.
+/// Values with spaces or operators go in double quotes, with `\"` and `\\`
+/// for a quote and a backslash inside. A shape without the field does not
+/// match the comparison.
+#[derive(Debug, Clone)]
+pub enum Query {
+    Compare(String, Op, String),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+/// Where a shape stands for a query: its name, geometry and metadata.
+struct Subject<'a> {
+    name: &'a str,
+    shape: &'a dyn Shape,
+    metadata: Option<&'a Metadata>,
+}
+
+impl Subject<'_> {
+    fn values(&self, field: &str) -> Vec<String> {
+        match field {
+            "type" => return vec![self.shape.kind().to_string()],
+            "name" => return vec![self.name.to_string()],
+            "cx" => return vec![self.shape.center().x.to_string()],
+            "cy" => return vec![self.shape.center().y.to_string()],
+            "tag" => {
+                return self
+                    .metadata
+                    .map(|metadata| metadata.tags.iter().cloned().collect())
+                    .unwrap_or_default()
+            }
+            _ => (),
+        }
+        if let Some((_, value)) = self
+            .shape
+            .params()
+            .into_iter()
+            .find(|(key, _)| *key == field)
+        {
+            return vec![value.to_string()];
+        }
+        self.metadata
+            .and_then(|metadata| metadata.values.get(field))
+            .map(|value| vec![value.clone()])
+            .unwrap_or_default()
+    }
+}
+
+fn compare(actual: &str, op: Op, expected: &str) -> bool {
+    if op == Op::Eq || op == Op::Ne {
+        let equal = match Selector::parse(expected) {
+            Ok(Selector::Glob(_, regex)) => regex.is_match(actual),
+            _ => match (actual.parse::<f64>(), expected.parse::<f64>()) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => actual == expected,
+            },
+        };
+        return equal == (op == Op::Eq);
+    }
+    let ordering = match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(a), Ok(b)) => match a.partial_cmp(&b) {
+            Some(ordering) => ordering,
+            None => return false,
+        },
+        _ => actual.cmp(expected),
+    };
+    match op {
+        Op::Lt => ordering.is_lt(),
+        Op::Le => ordering.is_le(),
+        Op::Gt => ordering.is_gt(),
+        _ => ordering.is_ge(),
+    }
+}
+
+impl Query {
+    pub fn parse(expr: &str) -> Result<Self, Box<dyn Error>> {
+        lazy_static! {
+            static ref RE_TOKEN: Regex =
+                Regex::new(r#""(?:[^"\\]|\\.)*"?|\(|\)|<=|>=|!=|=|<|>|[^\s()<>=!"]+"#).unwrap();
+        }
+        let tokens: Vec<&str> = RE_TOKEN.find_iter(expr).map(|m| m.as_str()).collect();
+        let mut parser = Parser { tokens, pos: 0 };
+        let query = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {:?} in the query.", token).into());
+        }
+        Ok(query)
+    }
+
+    fn matches(&self, subject: &Subject) -> bool {
+        match self {
+            Self::Compare(field, op, expected) => subject
+                .values(field)
+                .iter()
+                .any(|actual| compare(actual, *op, expected)),
+            Self::Not(query) => !query.matches(subject),
+            Self::And(a, b) => a.matches(subject) && b.matches(subject),
+            Self::Or(a, b) => a.matches(subject) || b.matches(subject),
+        }
+    }
+
+    /// The names of the matching shapes, sorted.
+    pub fn find(&self, shapes: &Shapes) -> Vec<String> {
+        let mut names: Vec<String> = shapes
+            .iter()
+            .filter(|(name, shape)| {
+                self.matches(&Subject {
+                    name,
+                    shape: shape.as_ref(),
+                    metadata: shapes.metadata.get(*name),
+                })
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let group = |f: &mut fmt::Formatter<'_>, query: &Query| match query {
+            Self::Or(..) => write!(f, "({})", query),
+            _ => write!(f, "{}", query),
+        };
+        match self {
+            Self::Compare(field, op, value) => write!(f, "{}{}{}", field, op, quoted(value)),
+            Self::Not(query) => match **query {
+                Self::Compare(..) | Self::Not(..) => write!(f, "not {}", query),
+                _ => write!(f, "not ({})", query),
+            },
+            Self::And(a, b) => {
+                group(f, a)?;
+                write!(f, " and ")?;
+                group(f, b)
+            }
+            Self::Or(a, b) => write!(f, "{} or {}", a, b),
+        }
+    }
+}
+
+/// A value as it is written in a query, in quotes where it has to be.
+fn quoted(value: &str) -> String {
+    let plain = !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || "()<>=!\"\\".contains(c));
+    if plain {
+        return value.to_string();
+    }
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{}\"", escaped)
+}
+
+/// The value of a token, without the quotes and escapes if it has them.
+fn unquote(token: &str) -> Result<String, Box<dyn Error>> {
+    let inner = match token.strip_prefix('"') {
+        Some(inner) => inner,
+        None => return Ok(token.to_string()),
+    };
+    let mut value = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.extend(chars.next()),
+            '"' => return Ok(value),
+            c => value.push(c),
+        }
+    }
+    Err("A closing quote is missing in the query.".into())
+}
+
+/// Recursive descent over the tokens: `or` binds looser than `and`, which
+/// binds looser than `not`.
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<&'a str, Box<dyn Error>> {
+        let token = self.peek().ok_or("The query ends too early.")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self
+            .peek()
+            .is_some_and(|token| token.eq_ignore_ascii_case(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Query, Box<dyn Error>> {
+        let mut query = self.and()?;
+        while self.keyword("or") {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, Box<dyn Error>> {
+        let mut query = self.not()?;
+        while self.keyword("and") {
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> Result<Query, Box<dyn Error>> {
+        if self.keyword("not") {
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        if self.keyword("(") {
+            let query = self.or()?;
+            if !self.keyword(")") {
+                return Err("A \")\" is missing in the query.".into());
+            }
+            return Ok(query);
+        }
+        let field = self.next()?;
+        let op = match self.next()? {
+            "=" => Op::Eq,
+            "!=" => Op::Ne,
+            "<" => Op::Lt,
+            "<=" => Op::Le,
+            ">" => Op::Gt,
+            ">=" => Op::Ge,
+            token => {
+                let err_msg = format!("Expected a comparison after {:?}, got {:?}.", field, token);
+                return Err(err_msg.into());
+            }
+        };
+        let value = unquote(self.next()?)?;
+        Ok(Query::Compare(field.to_lowercase(), op, value))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    fn get_shapes() -> Shapes {
+        let circle = |x, radius| -> Box<dyn Shape> {
+            Box::new(Circle {
+                center: Point { x, y: 0 },
+                radius,
+            })
+        };
+        let mut shapes = Shapes::from([
+            ("pad_1".to_string(), circle(0, 12)),
+            ("pad_2".to_string(), circle(30, 8)),
+            ("hole".to_string(), circle(60, 20)),
+            (
+                "edge".to_string(),
+                Box::new(Line(Point { x: 0, y: 0 }, Point { x: 3, y: 4 })) as Box<dyn Shape>,
+            ),
+        ]);
+        for name in ["pad_1", "pad_2"] {
+            let mut metadata = Metadata::default();
+            metadata.tags.insert("pad".into());
+            metadata.values.insert("owner".into(), "kim".into());
+            shapes.metadata.insert(name.into(), metadata);
+        }
+        let metadata = shapes.metadata.entry("hole".into()).or_default();
+        metadata
+            .values
+            .insert("note".into(), "drill (m3) last".into());
+        shapes
+    }
+
+    #[test]
+    fn test_find() {
+        let shapes = get_shapes();
+        let find = |expr: &str| Query::parse(expr).unwrap().find(&shapes);
+
+        assert_eq!(find("type=circle and radius>10 and tag=pad"), ["pad_1"]);
+        assert_eq!(find("type = circle and not tag=pad"), ["hole"]);
+        assert_eq!(find("length=5 or name=pad_*"), ["edge", "pad_1", "pad_2"]);
+        assert_eq!(
+            find("(cx>=30 or owner!=kim) and type=circle"),
+            ["hole", "pad_2"]
+        );
+        assert!(find("owner=bob").is_empty());
+        assert_eq!(find(r#"note="drill (m3) last""#), ["hole"]);
+        assert_eq!(find(r#"note="drill*" and type=circle"#), ["hole"]);
+        assert_eq!(
+            find(r#"note!="drill" or owner="kim""#),
+            ["hole", "pad_1", "pad_2"]
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let query = Query::parse("(a=1 or b<2) and not c>=3").unwrap();
+        assert_eq!(query.to_string(), "(a=1 or b<2) and not c>=3");
+        let query = Query::parse(r#"note="a \"b\" c" or note="" or note=x\y"#).unwrap();
+        assert_eq!(
+            query.to_string(),
+            r#"note="a \"b\" c" or note="" or note="x\\y""#
+        );
+        assert_eq!(
+            Query::parse(&query.to_string()).unwrap().to_string(),
+            query.to_string()
+        );
+
+        for (expr, err_msg) in [
+            ("radius", "The query ends too early."),
+            (
+                "radius 10",
+                r#"Expected a comparison after "radius", got "10"."#,
+            ),
+            ("(a=1", r#"A ")" is missing in the query."#),
+            ("a=1 b=2", r#"Unexpected "b" in the query."#),
+            (r#"note="a b"#, "A closing quote is missing in the query."),
+        ] {
+            assert_eq!(Query::parse(expr).unwrap_err().to_string(), err_msg);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::ClevisError;
+use crate::selector::Selection;
 use crate::shape::*;
 use crate::shape::{Shape, Shapes};
 use std::borrow::Borrow;
@@ -7,13 +9,32 @@ pub trait Renderer: Send {
     fn init_frame(&mut self) -> Result<(), Box<dyn Error>>;
     fn finish_frame(&mut self) -> Result<(), Box<dyn Error>>;
     fn render(&mut self, name: &str, shape: &dyn Shape) -> Result<(), Box<dyn Error>>;
+    /// Draw a frame of all the shapes. Failed writes come out as
+    /// `ClevisError::Render`.
     fn render_shapes(&mut self, shapes: &Shapes) -> Result<(), Box<dyn Error>> {
-        self.init_frame()?;
-        for (name, shape) in shapes {
-            self.render(name, shape.borrow())?;
-        }
-        self.finish_frame()?;
+        let mut frame = || -> Result<(), Box<dyn Error>> {
+            self.init_frame()?;
+            for (name, shape) in shapes {
+                self.render(name, shape.borrow())?;
+                if let Some(metadata) = shapes.metadata.get(name) {
+                    self.render_metadata(name, metadata)?;
+                }
+            }
+            self.finish_frame()
+        };
+        frame().map_err(ClevisError::from_render)
+    }
+
+    /// Tell the renderer which shapes are selected, before rendering them.
+    /// Renderers that can not highlight shapes ignore it.
+    fn set_selection(&mut self, selection: &Selection) {
+        let _ = selection;
+    }
 
+    /// Export the tags and key/value metadata of a shape, after the shape
+    /// itself. Renderers without a place for metadata ignore it.
+    fn render_metadata(&mut self, name: &str, metadata: &Metadata) -> Result<(), Box<dyn Error>> {
+        let _ = (name, metadata);
         Ok(())
     }
 
@@ -23,8 +44,20 @@ pub trait Renderer: Send {
 
     fn draw_circle(&mut self, circle: &Circle) -> Result<(), Box<dyn Error>>;
 
+    fn draw_polygon(&mut self, vertices: &[Point]) -> Result<(), Box<dyn Error>> {
+        // default implementation, may be slow.
+        for (i, p1) in vertices.iter().enumerate() {
+            let p2 = vertices[(i + 1) % vertices.len()];
+            self.draw_line(&Line(*p1, p2))?;
+        }
+        Ok(())
+    }
+
     fn draw_rectangle(&mut self, rectangle: &Rectangle) -> Result<(), Box<dyn Error>> {
         // default implementation, may be slow.
+        if rectangle.radius > 0 {
+            return self.draw_polygon(&rounded_rectangle_path(rectangle));
+        }
         let p1 = rectangle.corner;
         let p2 = Point {
             x: rectangle.corner.x + rectangle.w,
@@ -47,12 +80,48 @@ pub trait Renderer: Send {
 
     fn draw_square(&mut self, square: &Square) -> Result<(), Box<dyn Error>> {
         // default implementation, may be slow.
-        self.draw_rectangle(&Rectangle {
-            corner: square.corner,
-            w: square.side,
-            h: square.side,
-        })
+        self.draw_rectangle(&square.as_rectangle())
+    }
+
+    fn draw_regular_polygon(&mut self, polygon: &RegularPolygon) -> Result<(), Box<dyn Error>> {
+        // default implementation, may be slow.
+        self.draw_polygon(&polygon.vertices())
     }
+
+    fn draw_star(&mut self, star: &Star) -> Result<(), Box<dyn Error>> {
+        // default implementation, may be slow.
+        self.draw_polygon(&star.vertices())
+    }
+}
+
+/// Segments used to approximate each rounded corner.
+const CORNER_SEGMENTS: u32 = 8;
+
+/// The outline of a rounded rectangle, with each corner approximated by
+/// `CORNER_SEGMENTS` straight segments.
+pub fn rounded_rectangle_path(rectangle: &Rectangle) -> Vec<Point> {
+    let rect = rectangle.normalized();
+    let r = rect.radius.clamp(0, rect.w.min(rect.h) / 2);
+    let Point { x, y } = rect.corner;
+    let centers = [
+        (x + rect.w - r, y + rect.h - r),
+        (x + r, y + rect.h - r),
+        (x + r, y + r),
+        (x + rect.w - r, y + r),
+    ];
+    let mut path = Vec::new();
+    for (quarter, (cx, cy)) in centers.into_iter().enumerate() {
+        for i in 0..=CORNER_SEGMENTS {
+            let angle =
+                std::f64::consts::FRAC_PI_2 * (quarter as f64 + i as f64 / CORNER_SEGMENTS as f64);
+            path.push(Point {
+                x: cx + (r as f64 * angle.cos()).round() as DataType,
+                y: cy + (r as f64 * angle.sin()).round() as DataType,
+            });
+        }
+    }
+    path.dedup();
+    path
 }
 
 mod dummy_renderer;
@@ -115,11 +184,28 @@ pub mod tests {
         fn draw_square(&mut self, square: &Square) -> Result<(), Box<dyn Error>> {
             draw_shape_to_writer(self, square)
         }
+
+        fn draw_regular_polygon(&mut self, polygon: &RegularPolygon) -> Result<(), Box<dyn Error>> {
+            draw_shape_to_writer(self, polygon)
+        }
+
+        fn draw_star(&mut self, star: &Star) -> Result<(), Box<dyn Error>> {
+            draw_shape_to_writer(self, star)
+        }
+
+        fn draw_polygon(&mut self, vertices: &[Point]) -> Result<(), Box<dyn Error>> {
+            draw_shape_to_writer(
+                self,
+                &Polygon {
+                    vertices: vertices.to_vec(),
+                },
+            )
+        }
     }
 
     pub fn get_writer_render_result(shapes: &Shapes) -> String {
         let mut buff = Vec::<u8>::new();
-        buff.render_shapes(&shapes).unwrap();
+        buff.render_shapes(shapes).unwrap();
         String::from_utf8(buff).unwrap()
     }
 
@@ -145,7 +231,7 @@ pub mod tests {
             ),
             (
                 std::any::type_name::<Square>().into(),
-                "Square { corner: Point { x: 0, y: 0 }, side: 0 }".into(),
+                "Square { corner: Point { x: 0, y: 0 }, side: 0, radius: 0 }".into(),
             ),
             (
                 std::any::type_name::<Circle>().into(),
@@ -157,7 +243,7 @@ pub mod tests {
             ),
             (
                 std::any::type_name::<Rectangle>().into(),
-                "Rectangle { corner: Point { x: 0, y: 0 }, w: 0, h: 0 }".into(),
+                "Rectangle { corner: Point { x: 0, y: 0 }, w: 0, h: 0, radius: 0 }".into(),
             ),
         ])
     }
@@ -39,4 +39,14 @@ impl Renderer for DummyRenderer {
     fn draw_square(&mut self, square: &Square) -> Result<(), Box<dyn Error>> {
         Ok(())
     }
+
+    #[allow(unused_variables)]
+    fn draw_regular_polygon(&mut self, polygon: &RegularPolygon) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn draw_star(&mut self, star: &Star) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
 }
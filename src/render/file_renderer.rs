@@ -1,8 +1,8 @@
 use super::{Error, Renderer, Shape};
+use crate::error::ClevisError;
 use crate::shape::*;
 use std::fmt::Debug;
 use std::fs::File;
-use std::io;
 use std::io::prelude::*;
 
 #[derive(Debug)]
@@ -12,10 +12,10 @@ pub struct FileRenderer {
 }
 
 impl FileRenderer {
-    pub fn new(filename: &str) -> Result<FileRenderer, io::Error> {
+    pub fn new(filename: &str) -> Result<FileRenderer, ClevisError> {
         Ok(FileRenderer {
             filename: filename.to_string(),
-            file: File::create(filename)?,
+            file: File::create(filename).map_err(ClevisError::Render)?,
         })
     }
 
@@ -67,11 +67,19 @@ impl Renderer for FileRenderer {
     fn draw_square(&mut self, square: &Square) -> Result<(), Box<dyn Error>> {
         self.draw_shape(square)
     }
-}
 
-impl Drop for FileRenderer {
-    fn drop(&mut self) {
-        std::fs::remove_file(&self.filename).unwrap();
+    fn draw_regular_polygon(&mut self, polygon: &RegularPolygon) -> Result<(), Box<dyn Error>> {
+        self.draw_shape(polygon)
+    }
+
+    fn draw_star(&mut self, star: &Star) -> Result<(), Box<dyn Error>> {
+        self.draw_shape(star)
+    }
+
+    fn draw_polygon(&mut self, vertices: &[Point]) -> Result<(), Box<dyn Error>> {
+        self.draw_shape(&Polygon {
+            vertices: vertices.to_vec(),
+        })
     }
 }
 
@@ -110,5 +118,9 @@ pub mod tests {
             // use std::{thread, time};
             // thread::sleep(time::Duration::from_secs(1));
         }
+
+        // the output outlives the renderer
+        drop(render);
+        std::fs::remove_file(screen_file_name).unwrap();
     }
 }
@@ -1,4 +1,6 @@
 use super::{Error, Renderer, Shape};
+use crate::error::ClevisError;
+use crate::selector::Selection;
 use crate::shape::*;
 use std::fs::File;
 use std::io;
@@ -36,14 +38,38 @@ macro_rules! HTML_TEMPLATE {
         ctx.strokeStyle = 'rgb(0, 0, 0)';
         ctx.lineWidth = 2;
 
+        // tags and key/value metadata of the shapes, by name
+        var shapeData = {{}};
+
+        function data(name, tags, values) {{
+            shapeData[name] = {{ tags: tags, values: values }};
+        }}
+
+        // draw selected shapes in another color
+        function highlight(draw) {{
+            ctx.strokeStyle = 'rgb(0, 120, 255)';
+            draw();
+            ctx.strokeStyle = 'rgb(0, 0, 0)';
+        }}
+
         function point(x, y) {{
             ctx.beginPath();
             ctx.arc(x, y, 1, 0, 2 * Math.PI, false);
             ctx.stroke();
         }}
 
-        function rectangle(x, y, w, h) {{
-            ctx.strokeRect(x, y, w, h);
+        function rectangle(x, y, w, h, r) {{
+            if (w < 0) {{ x += w; w = -w; }}
+            if (h < 0) {{ y += h; h = -h; }}
+            r = Math.min(r, w / 2, h / 2);
+            ctx.beginPath();
+            ctx.moveTo(x + r, y);
+            ctx.arcTo(x + w, y, x + w, y + h, r);
+            ctx.arcTo(x + w, y + h, x, y + h, r);
+            ctx.arcTo(x, y + h, x, y, r);
+            ctx.arcTo(x, y, x + w, y, r);
+            ctx.closePath();
+            ctx.stroke();
         }}
 
         function circle(x, y, r) {{
@@ -52,8 +78,18 @@ macro_rules! HTML_TEMPLATE {
             ctx.stroke();
         }}
 
-        function square(x, y, l) {{
-            ctx.strokeRect(x, y, l, l);
+        function square(x, y, l, r) {{
+            rectangle(x, y, l, l, r);
+        }}
+
+        function polygon(points) {{
+            ctx.beginPath();
+            ctx.moveTo(points[0][0], points[0][1]);
+            for (var i = 1; i < points.length; i++) {{
+                ctx.lineTo(points[i][0], points[i][1]);
+            }}
+            ctx.closePath();
+            ctx.stroke();
         }}
 
         function line(x1, y1, x2, y2) {{
@@ -75,6 +111,7 @@ pub struct HtmlRenderer {
     file: File,
     filename: String,
     auto_refresh: bool,
+    selection: Selection,
 }
 
 impl HtmlRenderer {
@@ -86,17 +123,21 @@ impl HtmlRenderer {
         format!("{}.js", self.filename)
     }
 
-    pub fn new(filename: &str, auto_refresh: bool) -> Result<HtmlRenderer, io::Error> {
+    pub fn new(filename: &str, auto_refresh: bool) -> Result<HtmlRenderer, ClevisError> {
         let render = HtmlRenderer {
             filename: filename.to_string(),
-            file: File::create(format!("{}.js", filename))?,
+            file: File::create(format!("{}.js", filename)).map_err(ClevisError::Render)?,
             auto_refresh,
+            selection: Selection::new(),
         };
-        File::create(render.html_file_path())?
-            .write_all(format!(HTML_TEMPLATE!(), render.js_file_path()).as_bytes())?;
+        File::create(render.html_file_path())
+            .and_then(|mut file| {
+                file.write_all(format!(HTML_TEMPLATE!(), render.js_file_path()).as_bytes())
+            })
+            .map_err(ClevisError::Render)?;
 
         if auto_refresh {
-            render.fresh()?;
+            render.fresh().map_err(ClevisError::Render)?;
         }
 
         Ok(render)
@@ -135,12 +176,47 @@ impl Renderer for HtmlRenderer {
         Ok(())
     }
     fn render(&mut self, name: &str, shape: &dyn Shape) -> Result<(), Box<dyn Error>> {
+        let selected = self.selection.contains(name);
+        if selected {
+            self.file.write_all(b"highlight(() => { ")?;
+        }
         shape.draw_on(self)?;
+        if selected {
+            self.file.write_all(b" });")?;
+        }
         self.file.write_all(format!(" // {} \n", name).as_bytes())?;
 
         Ok(())
     }
 
+    fn set_selection(&mut self, selection: &Selection) {
+        self.selection = selection.clone();
+    }
+
+    fn render_metadata(&mut self, name: &str, metadata: &Metadata) -> Result<(), Box<dyn Error>> {
+        // Debug escapes strings in a way JavaScript understands
+        let tags: Vec<String> = metadata
+            .tags
+            .iter()
+            .map(|tag| format!("{:?}", tag))
+            .collect();
+        let values: Vec<String> = metadata
+            .values
+            .iter()
+            .map(|(key, value)| format!("{:?}: {:?}", key, value))
+            .collect();
+        self.file.write_all(
+            format!(
+                "data({:?}, [{}], {{{}}});\n",
+                name,
+                tags.join(", "),
+                values.join(", ")
+            )
+            .as_bytes(),
+        )?;
+        Ok(())
+    }
+
     fn draw_point(&mut self, point: &Point) -> Result<(), Box<dyn Error>> {
         self.file
             .write_all(format!("point({}, {});", point.x, point.y).as_bytes())?;
@@ -161,8 +237,8 @@ impl Renderer for HtmlRenderer {
     fn draw_rectangle(&mut self, rectangle: &Rectangle) -> Result<(), Box<dyn Error>> {
         self.file.write_all(
             format!(
-                "rectangle({}, {}, {}, {});",
-                rectangle.corner.x, rectangle.corner.y, rectangle.w, rectangle.h
+                "rectangle({}, {}, {}, {}, {});",
+                rectangle.corner.x, rectangle.corner.y, rectangle.w, rectangle.h, rectangle.radius
             )
             .as_bytes(),
         )?;
@@ -183,13 +259,23 @@ impl Renderer for HtmlRenderer {
     fn draw_square(&mut self, square: &Square) -> Result<(), Box<dyn Error>> {
         self.file.write_all(
             format!(
-                "square({}, {}, {});",
-                square.corner.x, square.corner.y, square.side
+                "square({}, {}, {}, {});",
+                square.corner.x, square.corner.y, square.side, square.radius
             )
             .as_bytes(),
         )?;
         Ok(())
     }
+
+    fn draw_polygon(&mut self, vertices: &[Point]) -> Result<(), Box<dyn Error>> {
+        let points: Vec<String> = vertices
+            .iter()
+            .map(|p| format!("[{}, {}]", p.x, p.y))
+            .collect();
+        self.file
+            .write_all(format!("polygon([{}]);", points.join(", ")).as_bytes())?;
+        Ok(())
+    }
 }
 
 impl Drop for HtmlRenderer {
@@ -218,9 +304,9 @@ pub mod tests {
         let answer = HashSet::from([
             "point(0, 0); // clevis::shape::Point ",
             "circle(0, 0, 0); // clevis::shape::Circle ",
-            "square(0, 0, 0); // clevis::shape::Square ",
+            "square(0, 0, 0, 0); // clevis::shape::Square ",
             "line(0, 0, 0, 0); // clevis::shape::Line ",
-            "rectangle(0, 0, 0, 0); // clevis::shape::Rectangle ",
+            "rectangle(0, 0, 0, 0, 0); // clevis::shape::Rectangle ",
         ]);
         let mut shapes = Shapes::new();
         for (n, s) in full_shapes {
@@ -238,4 +324,37 @@ pub mod tests {
             // thread::sleep(time::Duration::from_secs(1));
         }
     }
+
+    #[test]
+    fn test_html_renderer_metadata_and_selection() {
+        use crate::shape::*;
+        use std::fs;
+
+        let screen_file_name =
+            "crate::render::html_renderer::tests::test_html_renderer_metadata_and_selection";
+        let mut render = HtmlRenderer::new(screen_file_name, false).unwrap();
+
+        let mut shapes = Shapes::from([(
+            "p".to_string(),
+            Box::new(Point::default()) as Box<dyn Shape>,
+        )]);
+        let mut metadata = Metadata::default();
+        metadata.tags.insert("keep-out".into());
+        metadata.values.insert("owner".into(), "Kim \"K\"".into());
+        shapes.metadata.insert("p".into(), metadata);
+        render.render_shapes(&shapes).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(render.js_file_path()).unwrap(),
+            "point(0, 0); // p \ndata(\"p\", [\"keep-out\"], {\"owner\": \"Kim \\\"K\\\"\"});\n"
+        );
+
+        shapes.metadata.clear();
+        render.set_selection(&Selection::from(["p".to_string()]));
+        render.render_shapes(&shapes).unwrap();
+        assert_eq!(
+            fs::read_to_string(render.js_file_path()).unwrap(),
+            "highlight(() => { point(0, 0); }); // p \n"
+        );
+    }
 }
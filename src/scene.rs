@@ -0,0 +1,693 @@
+use crate::constraint::Constraint;
+use crate::error::ClevisError;
+use crate::shape::*;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+mod binary;
+pub use binary::{read_binary, write_binary, BINARY_MAGIC};
+
+/// The first word of every scene file.
+pub const SCENE_HEADER: &str = "clevis-scene";
+/// The version of the scene format written by `write_text`. Files of a
+/// newer version are refused rather than half understood.
+pub const SCENE_VERSION: u32 = 1;
+
+/// Write every shape of `shapes`, its metadata and the constraints as a
+/// scene file. The format is line based and meant to be read and edited by
+/// people:
+///
+/// ```text
+/// clevis-scene 1
+/// # comments and blank lines are ignored
+/// point a x=0 y=0
+/// rectangle r x=2 y=3 w=4 h=5 radius=0
+/// line m x1=0 y1=0 x2=4 y2=0
+/// line l from=a to=b
+/// circle c center=a radius=3
+/// connector k from=a to=r
+/// polygon h vertices=0,0;4,0;4,3
+/// tag a corner pad
+/// meta a note=first
+/// constrain horizontal m
+/// ```
+///
+/// The first line names the format and its version. Each shape follows as
+/// its kind, its name and its attributes, in drawing order from the bottom
+/// up, which loading the file keeps.
+/// Shapes linked to other shapes keep the names they follow instead of
+/// their geometry. Tags, key/value metadata and constraints come last,
+/// written like the commands that make them.
+pub fn write_text(shapes: &Shapes, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "{} {}", SCENE_HEADER, SCENE_VERSION)?;
+    let ordered = shapes.ordered();
+    for (name, shape) in ordered.iter() {
+        let (layout, values) = record(*shape)?;
+        let (kind, fields) = LAYOUTS[layout];
+        write!(out, "{} {}", kind, name)?;
+        for ((key, _), value) in fields.iter().zip(values) {
+            match value {
+                Value::Number(number) => write!(out, " {}={}", key, number)?,
+                Value::Name(name) => write!(out, " {}={}", key, name)?,
+                Value::Vertices(vertices) => {
+                    let vertices: Vec<String> = vertices
+                        .iter()
+                        .map(|vertex| format!("{},{}", vertex.x, vertex.y))
+                        .collect();
+                    write!(out, " {}={}", key, vertices.join(";"))?;
+                }
+            }
+        }
+        writeln!(out)?;
+    }
+    for (name, _) in ordered.iter() {
+        let metadata = match shapes.metadata.get(*name) {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+        if !metadata.tags.is_empty() {
+            let tags: Vec<&str> = metadata.tags.iter().map(String::as_str).collect();
+            writeln!(out, "tag {} {}", name, tags.join(" "))?;
+        }
+        if !metadata.values.is_empty() {
+            let pairs: Vec<String> = metadata
+                .values
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            writeln!(out, "meta {} {}", name, pairs.join(" "))?;
+        }
+    }
+    for constraint in shapes.constraints.iter() {
+        writeln!(out, "constrain {}", constraint)?;
+    }
+    Ok(())
+}
+
+/// The type of an attribute of a shape.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Field {
+    Number,
+    /// The name of a shape this one follows.
+    Name,
+    /// A list of points, written `x,y;x,y;...` in text.
+    Vertices,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Number(i64),
+    Name(String),
+    Vertices(Vec<Point>),
+}
+
+use Field::{Name, Number, Vertices};
+
+/// Every kind of shape a scene holds, with the attributes it is saved with
+/// in the order they are written. Shapes linked to other shapes have their
+/// own layout. The position in this list is the code of the layout in
+/// binary scenes, so layouts are only ever added at the end.
+const LAYOUTS: &[(&str, &[(&str, Field)])] = &[
+    ("point", &[("x", Number), ("y", Number)]),
+    (
+        "rectangle",
+        &[
+            ("x", Number),
+            ("y", Number),
+            ("w", Number),
+            ("h", Number),
+            ("radius", Number),
+        ],
+    ),
+    (
+        "line",
+        &[
+            ("x1", Number),
+            ("y1", Number),
+            ("x2", Number),
+            ("y2", Number),
+        ],
+    ),
+    ("line", &[("from", Name), ("to", Name)]),
+    (
+        "circle",
+        &[("x", Number), ("y", Number), ("radius", Number)],
+    ),
+    ("circle", &[("center", Name), ("radius", Number)]),
+    (
+        "square",
+        &[
+            ("x", Number),
+            ("y", Number),
+            ("side", Number),
+            ("radius", Number),
+        ],
+    ),
+    (
+        "ngon",
+        &[
+            ("x", Number),
+            ("y", Number),
+            ("radius", Number),
+            ("sides", Number),
+            ("rotation", Number),
+        ],
+    ),
+    (
+        "star",
+        &[
+            ("x", Number),
+            ("y", Number),
+            ("outer", Number),
+            ("inner", Number),
+            ("points", Number),
+            ("rotation", Number),
+        ],
+    ),
+    ("polygon", &[("vertices", Vertices)]),
+    ("connector", &[("from", Name), ("to", Name)]),
+];
+
+fn is_linked(fields: &[(&str, Field)]) -> bool {
+    fields.iter().any(|(_, field)| *field == Name)
+}
+
+/// The layout of a shape and its values in that layout.
+fn record(shape: &dyn Shape) -> io::Result<(usize, Vec<Value>)> {
+    let mut links = shape.dependencies().into_iter();
+    let linked = links.len() > 0;
+    let layout = LAYOUTS
+        .iter()
+        .position(|(kind, fields)| *kind == shape.kind() && is_linked(fields) == linked)
+        .ok_or_else(|| {
+            let err_msg = format!("A {:?} shape can not be saved.", shape.kind());
+            io::Error::other(err_msg)
+        })?;
+    let params = shape.params();
+    let values = LAYOUTS[layout]
+        .1
+        .iter()
+        .map(|(key, field)| match field {
+            Number => Value::Number(
+                params
+                    .iter()
+                    .find(|(param, _)| param == key)
+                    .map_or(0, |(_, value)| *value as i64),
+            ),
+            Name => Value::Name(links.next().unwrap_or_default()),
+            Vertices => Value::Vertices(shape.outline()),
+        })
+        .collect();
+    Ok((layout, values))
+}
+
+/// A scene that does not make sense where it is read.
+fn scene_error(message: impl Into<String>) -> ClevisError {
+    ClevisError::Scene(message.into())
+}
+
+/// The values of a record, taken in the order of its layout.
+struct Values(std::vec::IntoIter<Value>);
+
+impl Values {
+    fn number<T: TryFrom<i64>>(&mut self) -> Result<T, ClevisError> {
+        match self.0.next() {
+            Some(Value::Number(number)) => T::try_from(number)
+                .map_err(|_| scene_error(format!("The number {} is out of range.", number))),
+            _ => Err(scene_error("A number is missing.")),
+        }
+    }
+
+    fn point(&mut self) -> Result<Point, ClevisError> {
+        Ok(Point {
+            x: self.number()?,
+            y: self.number()?,
+        })
+    }
+
+    fn name(&mut self) -> Result<String, ClevisError> {
+        match self.0.next() {
+            Some(Value::Name(name)) => Ok(name),
+            _ => Err(scene_error("A name is missing.")),
+        }
+    }
+
+    fn vertices(&mut self) -> Result<Vec<Point>, ClevisError> {
+        match self.0.next() {
+            Some(Value::Vertices(vertices)) => Ok(vertices),
+            _ => Err(scene_error("The vertices are missing.")),
+        }
+    }
+}
+
+/// Rebuild a shape from its layout and the values `record` gave for it.
+fn build(layout: usize, values: Vec<Value>) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+    let (kind, fields) = LAYOUTS[layout];
+    let mut values = Values(values.into_iter());
+    let shape: Box<dyn Shape> = match (kind, is_linked(fields)) {
+        ("point", false) => Box::new(values.point()?),
+        ("rectangle", false) => Box::new(Rectangle {
+            corner: values.point()?,
+            w: values.number()?,
+            h: values.number()?,
+            radius: values.number()?,
+        }),
+        ("line", false) => Box::new(Line(values.point()?, values.point()?)),
+        ("line", true) => Box::new(LinkedLine::new(values.name()?, values.name()?)),
+        ("circle", false) => Box::new(Circle {
+            center: values.point()?,
+            radius: values.number()?,
+        }),
+        ("circle", true) => Box::new(LinkedCircle::new(values.name()?, values.number()?)),
+        ("square", false) => Box::new(Square {
+            corner: values.point()?,
+            side: values.number()?,
+            radius: values.number()?,
+        }),
+        ("ngon", false) => Box::new(RegularPolygon {
+            center: values.point()?,
+            radius: values.number()?,
+            sides: values.number()?,
+            rotation: values.number()?,
+        }),
+        ("star", false) => Box::new(Star {
+            center: values.point()?,
+            outer: values.number()?,
+            inner: values.number()?,
+            points: values.number()?,
+            rotation: values.number()?,
+        }),
+        ("polygon", false) => Box::new(Polygon {
+            vertices: values.vertices()?,
+        }),
+        ("connector", true) => Box::new(Connector::new(values.name()?, values.name()?)),
+        _ => {
+            let err_msg = format!("A {:?} shape can not be loaded.", kind);
+            return Err(scene_error(err_msg).into());
+        }
+    };
+    // the same checks as for shapes drawn by commands
+    shape.check()?;
+    Ok(shape)
+}
+
+fn parse_vertices(text: &str) -> Result<Vec<Point>, Box<dyn Error>> {
+    text.split(';')
+        .map(|vertex| {
+            let (x, y) = vertex
+                .split_once(',')
+                .ok_or_else(|| scene_error(format!("The vertex {:?} is not like x,y.", vertex)))?;
+            let err_msg =
+                || scene_error(format!("The vertex {:?} is not a pair of numbers.", vertex));
+            Ok(Point {
+                x: x.parse().map_err(|_| err_msg())?,
+                y: y.parse().map_err(|_| err_msg())?,
+            })
+        })
+        .collect()
+}
+
+/// Rebuild a shape from its kind and `key=value` attributes. The layout
+/// is the one of the kind that the attributes fit best.
+fn parse_shape(kind: &str, mut attrs: Vec<(&str, &str)>) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+    let fits = |fields: &[(&str, Field)]| {
+        fields
+            .iter()
+            .filter(|(key, _)| attrs.iter().any(|(k, _)| k == key))
+            .count()
+    };
+    let layout = (0..LAYOUTS.len())
+        .filter(|layout| LAYOUTS[*layout].0 == kind)
+        .rev()
+        .max_by_key(|layout| fits(LAYOUTS[*layout].1))
+        .ok_or_else(|| scene_error(format!("There is no {:?} shape.", kind)))?;
+
+    let mut values = Vec::new();
+    for (key, field) in LAYOUTS[layout].1 {
+        let index = attrs
+            .iter()
+            .position(|(k, _)| k == key)
+            .ok_or_else(|| scene_error(format!("The attribute {:?} is missing.", key)))?;
+        let (_, text) = attrs.remove(index);
+        values.push(match field {
+            Number => Value::Number(text.parse().map_err(|_| {
+                let err_msg = format!("The attribute {:?} is not a number: {:?}.", key, text);
+                scene_error(err_msg)
+            })?),
+            Name => Value::Name(text.to_string()),
+            Vertices => Value::Vertices(parse_vertices(text)?),
+        });
+    }
+    if let Some((key, _)) = attrs.first() {
+        return Err(scene_error(format!("The attribute {:?} is not known.", key)).into());
+    }
+    build(layout, values)
+}
+
+fn parse_constraint(text: &str) -> Result<Constraint, Box<dyn Error>> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let name = |word: &&str| word.to_string();
+    Ok(match &words[..] {
+        ["parallel", a, b] => Constraint::Parallel(name(a), name(b)),
+        ["perpendicular", a, b] => Constraint::Perpendicular(name(a), name(b)),
+        ["equal-length", a, b] => Constraint::EqualLength(name(a), name(b)),
+        ["coincident", a, b] => Constraint::Coincident(name(a), name(b)),
+        ["horizontal", a] => Constraint::Horizontal(name(a)),
+        ["vertical", a] => Constraint::Vertical(name(a)),
+        ["fixed", a] => Constraint::Fixed(name(a)),
+        ["distance", a, b, d] => {
+            let d = d
+                .parse()
+                .map_err(|_| scene_error(format!("The distance {:?} is not a number.", d)))?;
+            Constraint::Distance(name(a), name(b), d)
+        }
+        _ => return Err(scene_error(format!("{:?} is not a constraint.", text)).into()),
+    })
+}
+
+/// Apply one line of a scene file, other than the header.
+fn read_line(shapes: &mut Shapes, line: &str) -> Result<(), Box<dyn Error>> {
+    let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if keyword == "constrain" {
+        shapes.constraints.push(parse_constraint(rest)?);
+        return Ok(());
+    }
+    let mut words = rest.split_whitespace();
+    let name = words
+        .next()
+        .ok_or_else(|| scene_error("The shape name is missing."))?;
+    let words: Vec<&str> = words.collect();
+    match keyword {
+        "tag" | "meta" => {
+            if !shapes.contains_key(name) {
+                return Err(ClevisError::ShapeNotFound(name.to_string()).into());
+            }
+            let metadata = shapes.metadata.entry(name.to_string()).or_default();
+            for word in words {
+                match (keyword, word.split_once('=')) {
+                    ("tag", _) => metadata.tags.insert(word.to_string()),
+                    (_, Some((key, value))) => metadata
+                        .values
+                        .insert(key.to_string(), value.to_string())
+                        .is_none(),
+                    (_, None) => {
+                        let err_msg = format!("{:?} is not like key=value.", word);
+                        return Err(scene_error(err_msg).into());
+                    }
+                };
+            }
+        }
+        kind => {
+            if shapes.contains_key(name) {
+                return Err(ClevisError::NameConflict(name.to_string()).into());
+            }
+            let attrs = words
+                .iter()
+                .map(|word| {
+                    word.split_once('=')
+                        .ok_or_else(|| scene_error(format!("{:?} is not like key=value.", word)))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let shape = parse_shape(kind, attrs)?;
+            shapes.insert(name.to_string(), shape);
+        }
+    }
+    Ok(())
+}
+
+/// Read a scene written by `write_text`. Nothing is returned unless the
+/// whole scene makes sense: every line parses, every link and constraint
+/// names a shape of the scene.
+pub fn read_text(text: &str) -> Result<Shapes, Box<dyn Error>> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    let header = lines.next().map_or("", |(_, line)| line);
+    let version = match header.split_whitespace().collect::<Vec<_>>()[..] {
+        [SCENE_HEADER, version] => version.parse::<u32>().ok(),
+        _ => None,
+    };
+    match version {
+        Some(SCENE_VERSION) => {}
+        Some(version) => {
+            let err_msg = format!(
+                "Scene version {} is not supported, only version {} is.",
+                version, SCENE_VERSION
+            );
+            return Err(ClevisError::Scene(err_msg).into());
+        }
+        None => {
+            let err_msg = format!(
+                "This is not a scene, it does not start with {:?}.",
+                SCENE_HEADER
+            );
+            return Err(ClevisError::Scene(err_msg).into());
+        }
+    }
+
+    let mut shapes = Shapes::new();
+    for (number, line) in lines {
+        read_line(&mut shapes, line)
+            .map_err(|error| ClevisError::Scene(format!("Line {}: {}", number, error)))?;
+    }
+    check(&mut shapes)?;
+    Ok(shapes)
+}
+
+/// Make sure every link and constraint of a scene that was just read names
+/// a shape of the scene, and compute the geometry of the linked shapes.
+fn check(shapes: &mut Shapes) -> Result<(), ClevisError> {
+    let check = |shapes: &mut Shapes| -> Result<(), Box<dyn Error>> {
+        for constraint in shapes.constraints.iter() {
+            constraint.validate(shapes)?;
+        }
+        update_dependents(shapes)
+    };
+    check(shapes).map_err(|error| ClevisError::Scene(error.to_string()))
+}
+
+/// How a scene is written to a file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SceneFormat {
+    /// Lines of text, see `write_text`.
+    Text,
+    /// Compact and fast for very large drawings, see `write_binary`.
+    Binary,
+}
+
+/// Save the scene into `path`. The file is replaced only once the whole
+/// scene is written, a failure leaves the previous file as it was.
+pub fn save(shapes: &Shapes, path: &Path, format: SceneFormat) -> io::Result<()> {
+    let temp = PathBuf::from(format!("{}.tmp", path.display()));
+    let mut out = BufWriter::new(File::create(&temp)?);
+    let written = match format {
+        SceneFormat::Text => write_text(shapes, &mut out),
+        SceneFormat::Binary => write_binary(shapes, &mut out),
+    };
+    let written = written.and_then(|_| out.flush());
+    drop(out);
+    match written {
+        Ok(()) => fs::rename(&temp, path),
+        Err(error) => {
+            let _ = fs::remove_file(&temp);
+            Err(error)
+        }
+    }
+}
+
+/// Load the scene saved in `path`, in either format.
+pub fn load(path: &Path) -> Result<Shapes, Box<dyn Error>> {
+    let mut input = BufReader::new(File::open(path)?);
+    if input.fill_buf()?.starts_with(BINARY_MAGIC) {
+        Ok(read_binary(input)?)
+    } else {
+        let mut text = String::new();
+        input.read_to_string(&mut text)?;
+        read_text(&text)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::journal::tests::temp_path;
+
+    pub fn get_scene() -> Shapes {
+        let mut shapes = Shapes::from([
+            (
+                "a".to_string(),
+                Box::new(Point { x: 0, y: 0 }) as Box<dyn Shape>,
+            ),
+            (
+                "b".to_string(),
+                Box::new(Point { x: 4, y: 3 }) as Box<dyn Shape>,
+            ),
+            (
+                "r".to_string(),
+                Box::new(Rectangle {
+                    corner: Point { x: 2, y: 3 },
+                    w: 4,
+                    h: 5,
+                    radius: 1,
+                }) as Box<dyn Shape>,
+            ),
+            (
+                "m".to_string(),
+                Box::new(Line(Point { x: -1, y: 0 }, Point { x: 4, y: 0 })) as Box<dyn Shape>,
+            ),
+            (
+                "l".to_string(),
+                Box::new(LinkedLine::new("a".into(), "b".into())) as Box<dyn Shape>,
+            ),
+            (
+                "c".to_string(),
+                Box::new(LinkedCircle::new("a".into(), 3)) as Box<dyn Shape>,
+            ),
+            (
+                "k".to_string(),
+                Box::new(Connector::new("b".into(), "r".into())) as Box<dyn Shape>,
+            ),
+            (
+                "s".to_string(),
+                Box::new(Star {
+                    center: Point { x: 1, y: 1 },
+                    outer: 5,
+                    inner: 2,
+                    points: 5,
+                    rotation: 30,
+                }) as Box<dyn Shape>,
+            ),
+            (
+                "h".to_string(),
+                Box::new(Polygon {
+                    vertices: vec![
+                        Point { x: 0, y: 0 },
+                        Point { x: 4, y: 0 },
+                        Point { x: 4, y: 3 },
+                    ],
+                }) as Box<dyn Shape>,
+            ),
+        ]);
+        update_dependents(&mut shapes).unwrap();
+        let metadata = shapes.metadata.entry("a".into()).or_default();
+        metadata.tags.insert("pad".into());
+        metadata.tags.insert("corner".into());
+        metadata.values.insert("note".into(), "first".into());
+        shapes.constraints.push(Constraint::Horizontal("m".into()));
+        shapes
+            .constraints
+            .push(Constraint::Distance("a".into(), "b".into(), 5));
+        shapes
+    }
+
+    fn to_text(shapes: &Shapes) -> String {
+        let mut out = Vec::new();
+        write_text(shapes, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let shapes = get_scene();
+        let text = to_text(&shapes);
+        assert_eq!(
+            text,
+            [
+                "clevis-scene 1",
+                "point a x=0 y=0",
+                "point b x=4 y=3",
+                "rectangle r x=2 y=3 w=4 h=5 radius=1",
+                "line m x1=-1 y1=0 x2=4 y2=0",
+                "line l from=a to=b",
+                "circle c center=a radius=3",
+                "connector k from=b to=r",
+                "star s x=1 y=1 outer=5 inner=2 points=5 rotation=30",
+                "polygon h vertices=0,0;4,0;4,3",
+                "tag a corner pad",
+                "meta a note=first",
+                "constrain horizontal m",
+                "constrain distance a b 5",
+                "",
+            ]
+            .join("\n")
+        );
+
+        let loaded = read_text(&text).unwrap();
+        assert_eq!(to_text(&loaded), text);
+        for (name, shape) in shapes.iter() {
+            assert_eq!(format!("{:?}", loaded[name]), format!("{:?}", shape));
+        }
+        assert_eq!(loaded.metadata, shapes.metadata);
+        assert_eq!(loaded.constraints, shapes.constraints);
+
+        let path = temp_path("test_text_round_trip");
+        save(&shapes, &path, SceneFormat::Text).unwrap();
+        assert_eq!(to_text(&load(&path).unwrap()), text);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let read = |text: &str| read_text(text).unwrap_err().to_string();
+        assert_eq!(
+            read("point a x=0 y=0"),
+            r#"This is not a scene, it does not start with "clevis-scene"."#
+        );
+        assert_eq!(
+            read("clevis-scene 2\npoint a x=0 y=0"),
+            "Scene version 2 is not supported, only version 1 is."
+        );
+        assert_eq!(
+            read("clevis-scene 1\n# points\n\npoint a x=0"),
+            r#"Line 4: The attribute "y" is missing."#
+        );
+        assert_eq!(
+            read("clevis-scene 1\npoint a x=0 y=0 z=1"),
+            r#"Line 2: The attribute "z" is not known."#
+        );
+        assert_eq!(
+            read("clevis-scene 1\npoint a x=0 y=zero"),
+            r#"Line 2: The attribute "y" is not a number: "zero"."#
+        );
+        assert_eq!(
+            read("clevis-scene 1\npoint a x=0 y=0\npoint a x=1 y=1"),
+            r#"Line 3: Shape "a" already exists."#
+        );
+        assert_eq!(
+            read("clevis-scene 1\nblob a x=0"),
+            r#"Line 2: There is no "blob" shape."#
+        );
+        assert_eq!(
+            read("clevis-scene 1\ntag a pad"),
+            r#"Line 2: Shape "a" is not found."#
+        );
+        assert_eq!(
+            read("clevis-scene 1\nline l from=a to=b"),
+            r#"Shape "a" is not found."#
+        );
+        assert_eq!(
+            read("clevis-scene 1\npoint a x=0 y=0\nconstrain parallel a"),
+            r#"Line 3: "parallel a" is not a constraint."#
+        );
+        assert_eq!(
+            read("clevis-scene 1\nngon n x=0 y=0 radius=5 sides=2 rotation=0"),
+            "Line 2: A polygon needs at least 3 sides."
+        );
+        assert_eq!(
+            read("clevis-scene 1\nstar s x=0 y=0 outer=5 inner=2 points=1 rotation=0"),
+            "Line 2: A star needs at least 2 points."
+        );
+        assert_eq!(
+            read("clevis-scene 1\nrectangle r x=0 y=0 w=4 h=5 radius=-1"),
+            "Line 2: The corner radius can not be negative."
+        );
+
+        let error = read_text("clevis-scene 1\nblob a").unwrap_err();
+        assert_eq!(crate::error::error_code(error.as_ref()), "scene_error");
+    }
+}
@@ -0,0 +1,392 @@
+use super::{build, check, parse_constraint, record, Field, Value, LAYOUTS};
+use crate::error::ClevisError;
+use crate::shape::{Point, Shapes};
+use std::error::Error;
+use std::io::{self, Read, Write};
+
+/// The first bytes of every binary scene. The first one is not ASCII, so a
+/// binary scene is never taken for a text one.
+pub const BINARY_MAGIC: &[u8] = b"\x89clevis\n";
+/// The version of the binary format written by `write_binary`.
+pub const BINARY_VERSION: u32 = 1;
+
+/// The most shapes room is made for before they are read.
+const MAX_RESERVED: u64 = 1 << 20;
+
+/// A reader or writer that keeps the CRC-32 of the bytes going through it.
+struct Checked<T> {
+    inner: T,
+    crc: u32,
+}
+
+impl<T> Checked<T> {
+    fn new(inner: T) -> Self {
+        Self { inner, crc: 0 }
+    }
+}
+
+impl<W: Write> Write for Checked<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc = crc32(self.crc, &buf[..written]);
+        Ok(written)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Checked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.crc = crc32(self.crc, &buf[..read]);
+        Ok(read)
+    }
+}
+
+/// The CRC-32 of zip and png, continued from the `crc` of the bytes before.
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    0xEDB8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    let mut crc = !crc;
+    for byte in bytes {
+        crc = TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+fn write_unsigned(out: &mut impl Write, mut number: u64) -> io::Result<()> {
+    let mut bytes = [0; 10];
+    let mut len = 0;
+    loop {
+        let byte = (number & 0x7F) as u8;
+        number >>= 7;
+        if number == 0 {
+            bytes[len] = byte;
+            len += 1;
+            break;
+        }
+        bytes[len] = byte | 0x80;
+        len += 1;
+    }
+    out.write_all(&bytes[..len])
+}
+
+fn write_signed(out: &mut impl Write, number: i64) -> io::Result<()> {
+    // zigzag, so small negative numbers stay short too
+    write_unsigned(out, ((number << 1) ^ (number >> 63)) as u64)
+}
+
+fn write_string(out: &mut impl Write, text: &str) -> io::Result<()> {
+    write_unsigned(out, text.len() as u64)?;
+    out.write_all(text.as_bytes())
+}
+
+/// Write every shape of `shapes`, its metadata and the constraints as a
+/// binary scene, a few bytes per shape. The scene is streamed to `out`,
+/// which should be buffered. The format is:
+///
+/// ```text
+/// magic        "\x89clevis\n"
+/// version      4 bytes, little endian
+/// shapes       count, then for each: layout, name, attributes of the layout
+/// metadata     count, then for each: name, tag count, tags,
+///              value count, key and value of each
+/// constraints  count, then each as its text, like `horizontal m`
+/// checksum     4 bytes, little endian: CRC-32 of everything before
+/// ```
+///
+/// Counts, lengths and layouts are unsigned LEB128 numbers, the layout
+/// being the position in `LAYOUTS`. Numeric attributes are zigzag LEB128,
+/// names and text are a length and UTF-8 bytes, vertices a count and the
+/// coordinates of each. Shapes and their metadata come in drawing order.
+pub fn write_binary(shapes: &Shapes, out: &mut impl Write) -> io::Result<()> {
+    let mut out = Checked::new(out);
+    out.write_all(BINARY_MAGIC)?;
+    out.write_all(&BINARY_VERSION.to_le_bytes())?;
+
+    let ordered = shapes.ordered();
+    write_unsigned(&mut out, ordered.len() as u64)?;
+    for (name, shape) in ordered.iter() {
+        let (layout, values) = record(*shape)?;
+        write_unsigned(&mut out, layout as u64)?;
+        write_string(&mut out, name)?;
+        for value in values {
+            match value {
+                Value::Number(number) => write_signed(&mut out, number)?,
+                Value::Name(name) => write_string(&mut out, &name)?,
+                Value::Vertices(vertices) => {
+                    write_unsigned(&mut out, vertices.len() as u64)?;
+                    for vertex in vertices {
+                        write_signed(&mut out, vertex.x as i64)?;
+                        write_signed(&mut out, vertex.y as i64)?;
+                    }
+                }
+            }
+        }
+    }
+
+    let metadata: Vec<_> = (ordered.iter())
+        .filter_map(|(name, _)| Some((*name, shapes.metadata.get(*name)?)))
+        .collect();
+    write_unsigned(&mut out, metadata.len() as u64)?;
+    for (name, metadata) in metadata {
+        write_string(&mut out, name)?;
+        write_unsigned(&mut out, metadata.tags.len() as u64)?;
+        for tag in metadata.tags.iter() {
+            write_string(&mut out, tag)?;
+        }
+        write_unsigned(&mut out, metadata.values.len() as u64)?;
+        for (key, value) in metadata.values.iter() {
+            write_string(&mut out, key)?;
+            write_string(&mut out, value)?;
+        }
+    }
+
+    write_unsigned(&mut out, shapes.constraints.len() as u64)?;
+    for constraint in shapes.constraints.iter() {
+        write_string(&mut out, &constraint.to_string())?;
+    }
+
+    let crc = out.crc;
+    out.write_all(&crc.to_le_bytes())
+}
+
+fn read_unsigned(input: &mut impl Read) -> Result<u64, Box<dyn Error>> {
+    let mut number = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        number |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(number);
+        }
+    }
+    Err("A number is too long.".into())
+}
+
+fn read_signed(input: &mut impl Read) -> Result<i64, Box<dyn Error>> {
+    let number = read_unsigned(input)?;
+    Ok((number >> 1) as i64 ^ -((number & 1) as i64))
+}
+
+fn read_string(input: &mut impl Read) -> Result<String, Box<dyn Error>> {
+    let len = read_unsigned(input)?;
+    // the length may be damaged, the bytes tell how many there really are
+    let mut bytes = Vec::new();
+    input.take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn read_shapes(input: &mut impl Read, shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+    let count = read_unsigned(input)?;
+    // a damaged count must not reserve all the memory there is
+    shapes.reserve(count.min(MAX_RESERVED) as usize);
+    for _ in 0..count {
+        let layout = read_unsigned(input)? as usize;
+        let (_, fields) = LAYOUTS
+            .get(layout)
+            .ok_or_else(|| format!("There is no shape layout {}.", layout))?;
+        let name = read_string(input)?;
+        let mut values = Vec::new();
+        for (_, field) in fields.iter() {
+            values.push(match field {
+                Field::Number => Value::Number(read_signed(input)?),
+                Field::Name => Value::Name(read_string(input)?),
+                Field::Vertices => {
+                    let mut vertices = Vec::new();
+                    for _ in 0..read_unsigned(input)? {
+                        vertices.push(Point {
+                            x: read_signed(input)?.try_into()?,
+                            y: read_signed(input)?.try_into()?,
+                        });
+                    }
+                    Value::Vertices(vertices)
+                }
+            });
+        }
+        let shape = build(layout, values)?;
+        if shapes.insert(name.clone(), shape).is_some() {
+            return Err(ClevisError::NameConflict(name).into());
+        }
+    }
+
+    for _ in 0..read_unsigned(input)? {
+        let name = read_string(input)?;
+        if !shapes.contains_key(&name) {
+            return Err(ClevisError::ShapeNotFound(name).into());
+        }
+        let metadata = shapes.metadata.entry(name).or_default();
+        for _ in 0..read_unsigned(input)? {
+            metadata.tags.insert(read_string(input)?);
+        }
+        for _ in 0..read_unsigned(input)? {
+            let key = read_string(input)?;
+            metadata.values.insert(key, read_string(input)?);
+        }
+    }
+
+    for _ in 0..read_unsigned(input)? {
+        let constraint = parse_constraint(&read_string(input)?)?;
+        shapes.constraints.push(constraint);
+    }
+    Ok(())
+}
+
+/// Read a scene written by `write_binary`, streaming from `input`, which
+/// should be buffered. The checksum is verified before anything is
+/// returned, so a damaged file is refused rather than loaded in part.
+pub fn read_binary(input: impl Read) -> Result<Shapes, ClevisError> {
+    let mut input = Checked::new(input);
+    let cut_short = || ClevisError::Scene("The scene file is cut short.".into());
+    let corrupted = |error: Box<dyn Error>| match error.downcast_ref::<io::Error>() {
+        Some(io_error) if io_error.kind() == io::ErrorKind::UnexpectedEof => cut_short(),
+        _ => ClevisError::Scene(format!("The scene file is corrupted: {}", error)),
+    };
+
+    let mut header = [0; 12];
+    input.read_exact(&mut header).map_err(|_| cut_short())?;
+    if !header.starts_with(BINARY_MAGIC) {
+        return Err(ClevisError::Scene("This is not a binary scene.".into()));
+    }
+    let version = u32::from_le_bytes(header[8..].try_into().unwrap());
+    if version != BINARY_VERSION {
+        let err_msg = format!(
+            "Binary scene version {} is not supported, only version {} is.",
+            version, BINARY_VERSION
+        );
+        return Err(ClevisError::Scene(err_msg));
+    }
+
+    let mut shapes = Shapes::new();
+    read_shapes(&mut input, &mut shapes).map_err(corrupted)?;
+
+    let crc = input.crc;
+    let mut checksum = [0; 4];
+    input
+        .inner
+        .read_exact(&mut checksum)
+        .map_err(|_| cut_short())?;
+    let mut rest = [0];
+    let trailing = input
+        .inner
+        .read(&mut rest)
+        .map_err(|error| corrupted(error.into()))?;
+    if u32::from_le_bytes(checksum) != crc || trailing != 0 {
+        let err_msg = "The scene file is corrupted, its checksum does not match.";
+        return Err(ClevisError::Scene(err_msg.into()));
+    }
+
+    check(&mut shapes)?;
+    Ok(shapes)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::super::tests::get_scene;
+    use super::super::write_text;
+    use super::*;
+
+    fn to_binary(shapes: &Shapes) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_binary(shapes, &mut out).unwrap();
+        out
+    }
+
+    fn to_text(shapes: &Shapes) -> String {
+        let mut out = Vec::new();
+        write_text(shapes, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let mut out = Vec::new();
+        for number in [0, 1, -1, 63, -64, 64, i32::MAX as i64, i64::MIN, i64::MAX] {
+            write_signed(&mut out, number).unwrap();
+        }
+        assert_eq!(&out[..5], [0, 2, 1, 126, 127]);
+        let mut input = &out[..];
+        for number in [0, 1, -1, 63, -64, 64, i32::MAX as i64, i64::MIN, i64::MAX] {
+            assert_eq!(read_signed(&mut input).unwrap(), number);
+        }
+        assert!(input.is_empty());
+        assert!(read_unsigned(&mut &[0xFF; 11][..]).is_err());
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let shapes = get_scene();
+        let binary = to_binary(&shapes);
+        assert!(binary.starts_with(BINARY_MAGIC));
+        let loaded = read_binary(&binary[..]).unwrap();
+        assert_eq!(to_text(&loaded), to_text(&shapes));
+        assert_eq!(
+            read_binary(&to_binary(&Shapes::new())[..]).unwrap().len(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_damaged() {
+        let binary = to_binary(&get_scene());
+        // any changed byte and any missing end is noticed
+        for index in 0..binary.len() {
+            let mut damaged = binary.clone();
+            damaged[index] ^= 0x10;
+            assert!(read_binary(&damaged[..]).is_err(), "byte {}", index);
+            assert!(read_binary(&binary[..index]).is_err(), "length {}", index);
+        }
+
+        let read = |bytes: &[u8]| read_binary(bytes).unwrap_err().to_string();
+        assert_eq!(
+            read(&binary[..binary.len() - 2]),
+            "The scene file is cut short."
+        );
+        let mut damaged = binary.clone();
+        *damaged.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            read(&damaged),
+            "The scene file is corrupted, its checksum does not match."
+        );
+        let mut longer = binary.clone();
+        longer.push(0);
+        assert_eq!(
+            read(&longer),
+            "The scene file is corrupted, its checksum does not match."
+        );
+        assert_eq!(read(b"clevis-scene 1\n"), "This is not a binary scene.");
+        let mut newer = binary.clone();
+        newer[8] = 2;
+        assert_eq!(
+            read(&newer),
+            "Binary scene version 2 is not supported, only version 1 is."
+        );
+    }
+}
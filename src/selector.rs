@@ -0,0 +1,125 @@
+use crate::error::ClevisError;
+use crate::shape::Shapes;
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+/// Names of the selected shapes.
+pub type Selection = BTreeSet<String>;
+
+/// Picks the shapes a command applies to. A selector is resolved against
+/// the shapes when the command runs, not when it is parsed.
+#[derive(Debug, Clone)]
+pub enum Selector {
+    /// A single shape, which does not need to exist yet.
+    Name(String),
+    /// A name pattern where `*` matches any run of characters and `?` any
+    /// single character, written as is: `pad_*`.
+    Glob(String, Regex),
+    /// A regular expression between slashes: `/^pad_\d+$/`.
+    Regex(Regex),
+    /// The current selection, written `@sel`. Holds the selected names once
+    /// the command has seen the selection.
+    Selection(Vec<String>),
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Self, Box<dyn Error>> {
+        if selector == "@sel" {
+            return Ok(Self::Selection(Vec::new()));
+        }
+        if let Some(pattern) = selector
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            return Ok(Self::Regex(Regex::new(pattern)?));
+        }
+        if selector.contains(['*', '?']) {
+            let pattern: String = selector
+                .chars()
+                .map(|c| match c {
+                    '*' => ".*".to_string(),
+                    '?' => ".".to_string(),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect();
+            let regex = Regex::new(&format!("^{}$", pattern))?;
+            return Ok(Self::Glob(selector.to_string(), regex));
+        }
+        Ok(Self::Name(selector.to_string()))
+    }
+
+    /// The names of the selected shapes, sorted. Patterns that match
+    /// nothing are an error.
+    pub fn resolve(&self, shapes: &Shapes) -> Result<Vec<String>, Box<dyn Error>> {
+        let regex = match self {
+            Self::Name(name) => return Ok(vec![name.clone()]),
+            Self::Glob(_, regex) | Self::Regex(regex) => regex,
+            Self::Selection(names) => {
+                let names: Vec<String> = names
+                    .iter()
+                    .filter(|name| shapes.contains_key(*name))
+                    .cloned()
+                    .collect();
+                if names.is_empty() {
+                    return Err(ClevisError::validation("Nothing is selected.").into());
+                }
+                return Ok(names);
+            }
+        };
+        let mut names: Vec<String> = shapes
+            .keys()
+            .filter(|name| regex.is_match(name))
+            .cloned()
+            .collect();
+        if names.is_empty() {
+            return Err(ClevisError::Validation(format!("No shape matches {}.", self)).into());
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(name) | Self::Glob(name, _) => write!(f, "{}", name),
+            Self::Regex(regex) => write!(f, "/{}/", regex.as_str()),
+            Self::Selection(_) => write!(f, "@sel"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shape::*;
+
+    #[test]
+    fn test_resolve() {
+        let mut shapes = Shapes::new();
+        for name in ["pad_1", "pad_2", "pad_10", "rect_a", "c"] {
+            shapes.insert(name.into(), Box::new(Point::default()));
+        }
+        let resolve = |selector: &str| Selector::parse(selector).unwrap().resolve(&shapes);
+
+        assert_eq!(resolve("pad_*").unwrap(), ["pad_1", "pad_10", "pad_2"]);
+        assert_eq!(resolve("pad_?").unwrap(), ["pad_1", "pad_2"]);
+        assert_eq!(resolve(r"/^pad_\d{2}$/").unwrap(), ["pad_10"]);
+        assert_eq!(resolve("/_/").unwrap().len(), 4);
+        assert_eq!(resolve("missing").unwrap(), ["missing"]);
+        assert_eq!(
+            resolve("x*").unwrap_err().to_string(),
+            "No shape matches x*."
+        );
+        assert!(Selector::parse("/(/").is_err());
+        assert_eq!(
+            resolve("@sel").unwrap_err().to_string(),
+            "Nothing is selected."
+        );
+        let selection = Selector::Selection(vec!["c".into(), "gone".into()]);
+        assert_eq!(selection.resolve(&shapes).unwrap(), ["c"]);
+        assert_eq!(Selector::parse("/^a.$/").unwrap().to_string(), "/^a.$/");
+    }
+}
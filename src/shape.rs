@@ -1,17 +1,421 @@
-use crate::render::Renderer;
-use std::collections::HashMap;
+use crate::constraint::Constraint;
+use crate::error::ClevisError;
+use crate::render::{rounded_rectangle_path, Renderer};
+use std::collections::{hash_map, BTreeMap, BTreeSet, HashMap, HashSet};
 use std::error::Error;
 use std::fmt::Debug;
+use std::ops::Deref;
 
-pub trait Shape: Debug + Send {
+pub trait Shape: Debug + Send + ShapeClone {
     fn move_by(&mut self, x: DataType, y: DataType);
     fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>>;
+    fn center(&self) -> Point;
+
+    /// The kind of shape, named like the command that draws it.
+    fn kind(&self) -> &'static str;
+
+    /// The numeric parameters of the shape by name, for queries and listings.
+    fn params(&self) -> Vec<(&'static str, f64)>;
+
+    /// Mirror the shape across the line through `axis.0` and `axis.1`.
+    /// Axis aligned shapes become the bounding box of their mirrored corners,
+    /// which is exact for horizontal, vertical and diagonal axes only.
+    fn reflect(&mut self, axis: &Line);
+
+    /// A copy of the shape mirrored across any axis. Shapes that `reflect`
+    /// can not mirror exactly become polygons.
+    fn mirrored(&self, axis: &Line) -> Box<dyn Shape> {
+        let mut shape = self.clone_box();
+        shape.reflect(axis);
+        shape
+    }
+
+    /// The point where a connector heading to `toward` should attach.
+    fn anchor(&self, toward: Point) -> Point {
+        let _ = toward;
+        self.center()
+    }
+
+    /// Where the infinite extension of `line` crosses the outline of this
+    /// shape, as parameters `t` with `line.0` at `t = 0` and `line.1` at `t = 1`.
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        let _ = line;
+        Vec::new()
+    }
+
+    /// Points spanning the shape, curves approximated by straight segments.
+    fn outline(&self) -> Vec<Point> {
+        vec![self.center()]
+    }
+
+    /// A copy of the shape grown outward by `distance`, or shrunk for a
+    /// negative distance. `round` asks for rounded instead of sharp corners
+    /// where the shape supports both.
+    fn offset(&self, distance: DataType, round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        let _ = (distance, round);
+        Err(ClevisError::validation("This shape can not be offset.").into())
+    }
+
+    fn as_point(&self) -> Option<&Point> {
+        None
+    }
+    fn as_line(&self) -> Option<&Line> {
+        None
+    }
+
+    /// Refuse parameters the shape can not be drawn with. Commands and
+    /// scene files both go through this.
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Roughly how many bytes the shape takes, its own allocations included.
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    /// Names of the shapes this shape is derived from.
+    fn dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Recompute derived geometry from the shapes listed in `dependencies()`.
+    fn update(&mut self, shapes: &Shapes) -> Result<(), Box<dyn Error>> {
+        let _ = shapes;
+        Ok(())
+    }
+}
+
+pub trait ShapeClone {
+    fn clone_box(&self) -> Box<dyn Shape>;
+}
+impl<T> ShapeClone for T
+where
+    T: 'static + Shape + Clone,
+{
+    fn clone_box(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+}
+impl Clone for Box<dyn Shape> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Tags and key/value pairs attached to a shape by the user.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Metadata {
+    pub tags: BTreeSet<String>,
+    pub values: BTreeMap<String, String>,
+}
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.values.is_empty()
+    }
+
+    /// Roughly how many bytes the tags and values take.
+    pub fn memory(&self) -> usize {
+        let tags = self.tags.iter().map(|tag| tag.len());
+        let values = (self.values.iter()).map(|(key, value)| key.len() + value.len());
+        std::mem::size_of_val(self) + tags.sum::<usize>() + values.sum::<usize>()
+    }
+}
+
+/// All shapes of a drawing by name, plus the constraints between them and
+/// the metadata of each shape. Dereferences to the underlying map of shapes
+/// for reading, changes go through the methods below to keep the drawing
+/// order.
+#[derive(Debug, Clone, Default)]
+pub struct Shapes {
+    shapes: HashMap<String, Box<dyn Shape>>,
+    /// The place of each shape in the drawing order, later ones on top.
+    ranks: HashMap<String, usize>,
+    /// The place of the next new shape.
+    next_rank: usize,
+    pub constraints: Vec<Constraint>,
+    pub metadata: HashMap<String, Metadata>,
+}
+impl Shapes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace a shape. A new name goes on top of the drawing.
+    pub fn insert(&mut self, name: String, shape: Box<dyn Shape>) -> Option<Box<dyn Shape>> {
+        if !self.ranks.contains_key(&name) {
+            self.ranks.insert(name.clone(), self.next_rank);
+            self.next_rank += 1;
+        }
+        self.shapes.insert(name, shape)
+    }
+
+    /// Put a shape back at the `rank` it was removed from by `take`.
+    pub fn insert_at(
+        &mut self,
+        name: String,
+        shape: Box<dyn Shape>,
+        rank: usize,
+    ) -> Option<Box<dyn Shape>> {
+        self.ranks.insert(name.clone(), rank);
+        self.shapes.insert(name, shape)
+    }
+
+    /// Make room for `additional` more shapes.
+    pub fn reserve(&mut self, additional: usize) {
+        self.shapes.reserve(additional);
+        self.ranks.reserve(additional);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn Shape>> {
+        self.take(name).map(|(shape, _)| shape)
+    }
+
+    /// Remove a shape along with its place in the drawing order, for
+    /// `insert_at` to put it back where it was.
+    pub fn take(&mut self, name: &str) -> Option<(Box<dyn Shape>, usize)> {
+        let shape = self.shapes.remove(name)?;
+        Some((shape, self.ranks.remove(name)?))
+    }
+
+    /// Take back the shapes added under the `created` names, putting the
+    /// shapes they `replaced` back in their place.
+    pub fn put_back(&mut self, created: Vec<String>, replaced: Vec<(String, Box<dyn Shape>)>) {
+        let replaced: HashMap<String, Box<dyn Shape>> = replaced.into_iter().collect();
+        for name in created {
+            if !replaced.contains_key(&name) {
+                self.remove(&name);
+            }
+        }
+        self.extend(replaced);
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Box<dyn Shape>> {
+        self.shapes.get_mut(name)
+    }
+
+    /// Roughly how many bytes the shapes, their order and metadata take.
+    pub fn memory(&self) -> usize {
+        let shapes = (self.shapes.iter()).map(|(name, shape)| name.len() + shape.memory());
+        let metadata =
+            (self.metadata.iter()).map(|(name, metadata)| name.len() + metadata.memory());
+        let ranks = (self.ranks.keys()).map(|name| name.len() + std::mem::size_of::<usize>());
+        std::mem::size_of_val(self)
+            + shapes.sum::<usize>()
+            + ranks.sum::<usize>()
+            + metadata.sum::<usize>()
+            + self.constraints.len() * std::mem::size_of::<Constraint>()
+    }
+
+    /// The shapes in drawing order, from the bottom up.
+    pub fn ordered(&self) -> Vec<(&String, &dyn Shape)> {
+        let mut shapes: Vec<(&String, &dyn Shape)> = (self.shapes.iter())
+            .map(|(name, shape)| (name, shape.as_ref()))
+            .collect();
+        shapes.sort_by_key(|(name, _)| self.ranks[*name]);
+        shapes
+    }
+
+    /// Snapshot the metadata of `names`, to be put back by `restore_metadata`.
+    pub fn save_metadata(&self, names: &[String]) -> Vec<(String, Option<Metadata>)> {
+        names
+            .iter()
+            .map(|name| (name.clone(), self.metadata.get(name).cloned()))
+            .collect()
+    }
+
+    pub fn restore_metadata(&mut self, saved: Vec<(String, Option<Metadata>)>) {
+        for (name, metadata) in saved {
+            match metadata {
+                Some(metadata) => self.metadata.insert(name, metadata),
+                None => self.metadata.remove(&name),
+            };
+        }
+    }
+
+    /// Give `to` a copy of the metadata of `from`.
+    pub fn copy_metadata(&mut self, from: &str, to: &str) {
+        match self.metadata.get(from).cloned() {
+            Some(metadata) => self.metadata.insert(to.to_string(), metadata),
+            None => self.metadata.remove(to),
+        };
+    }
+}
+/// Roughly how many bytes metadata saved by `Shapes::save_metadata` takes.
+pub fn metadata_memory(saved: &[(String, Option<Metadata>)]) -> usize {
+    (saved.iter())
+        .map(|(name, metadata)| name.len() + metadata.as_ref().map_or(0, Metadata::memory))
+        .sum()
 }
 
-pub type Shapes = HashMap<String, Box<dyn Shape>>;
+impl Deref for Shapes {
+    type Target = HashMap<String, Box<dyn Shape>>;
+    fn deref(&self) -> &Self::Target {
+        &self.shapes
+    }
+}
+impl Extend<(String, Box<dyn Shape>)> for Shapes {
+    fn extend<I: IntoIterator<Item = (String, Box<dyn Shape>)>>(&mut self, shapes: I) {
+        for (name, shape) in shapes {
+            self.insert(name, shape);
+        }
+    }
+}
+impl<const N: usize> From<[(String, Box<dyn Shape>); N]> for Shapes {
+    fn from(shapes: [(String, Box<dyn Shape>); N]) -> Self {
+        let mut this = Self::default();
+        this.extend(shapes);
+        this
+    }
+}
+impl IntoIterator for Shapes {
+    type Item = (String, Box<dyn Shape>);
+    type IntoIter = hash_map::IntoIter<String, Box<dyn Shape>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.shapes.into_iter()
+    }
+}
+impl<'a> IntoIterator for &'a Shapes {
+    type Item = (&'a String, &'a Box<dyn Shape>);
+    type IntoIter = hash_map::Iter<'a, String, Box<dyn Shape>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.shapes.iter()
+    }
+}
 
 pub type DataType = i32;
 
+/// Segments used to approximate a circle as a polygon.
+const CIRCLE_SEGMENTS: u32 = 32;
+
+/// The most sides of a regular polygon, and vertices of a star.
+pub const MAX_SIDES: u32 = 1024;
+
+const COLLAPSED: &str = "The offset shape would have no area left.";
+
+fn round(value: f64) -> DataType {
+    value.round() as DataType
+}
+
+/// Whether mirroring across `axis` keeps axis aligned shapes axis aligned.
+fn keeps_alignment(axis: &Line) -> bool {
+    let (dx, dy) = (axis.1.x - axis.0.x, axis.1.y - axis.0.y);
+    dx == 0 || dy == 0 || dx.abs() == dy.abs()
+}
+
+/// The smallest axis aligned rectangle containing all `points`.
+pub fn bounding_box(points: &[Point]) -> Rectangle {
+    let min_x = points.iter().map(|p| p.x).min().unwrap_or_default();
+    let min_y = points.iter().map(|p| p.y).min().unwrap_or_default();
+    let max_x = points.iter().map(|p| p.x).max().unwrap_or_default();
+    let max_y = points.iter().map(|p| p.y).max().unwrap_or_default();
+    Rectangle {
+        corner: Point { x: min_x, y: min_y },
+        w: max_x - min_x,
+        h: max_y - min_y,
+        radius: 0,
+    }
+}
+
+fn check_not_negative(what: &str, value: DataType) -> Result<(), Box<dyn Error>> {
+    if value < 0 {
+        let err_msg = format!("The {} can not be negative.", what);
+        return Err(ClevisError::Validation(err_msg).into());
+    }
+    Ok(())
+}
+
+fn get_dependency<'a>(shapes: &'a Shapes, name: &str) -> Result<&'a dyn Shape, Box<dyn Error>> {
+    let shape = shapes.get(name);
+    Ok(shape
+        .ok_or_else(|| ClevisError::ShapeNotFound(name.to_string()))?
+        .as_ref())
+}
+
+fn get_point(shapes: &Shapes, name: &str) -> Result<Point, Box<dyn Error>> {
+    let err_msg = format!("Shape {:?} is not a point.", name);
+    let point = get_dependency(shapes, name)?.as_point();
+    Ok(*point.ok_or(ClevisError::Validation(err_msg))?)
+}
+
+/// Derived shapes can only be changed through the shapes they depend on.
+pub fn ensure_independent(name: &str, shape: &dyn Shape) -> Result<(), Box<dyn Error>> {
+    let dependencies = shape.dependencies();
+    if dependencies.is_empty() {
+        Ok(())
+    } else {
+        let err_msg = format!("Shape {:?} follows {:?}.", name, dependencies);
+        Err(ClevisError::Validation(err_msg).into())
+    }
+}
+
+/// Names of all shapes, and constraints, that directly depend on the shape
+/// called `name`.
+pub fn dependents_of(shapes: &Shapes, name: &str) -> Vec<String> {
+    let mut dependents: Vec<String> = shapes
+        .iter()
+        .filter(|(_, shape)| shape.dependencies().iter().any(|dep| dep == name))
+        .map(|(dependent, _)| dependent.clone())
+        .collect();
+    dependents.sort();
+    dependents.extend(
+        shapes
+            .constraints
+            .iter()
+            .filter(|constraint| constraint.names().contains(&name))
+            .map(|constraint| constraint.to_string()),
+    );
+    dependents
+}
+
+/// Recompute every derived shape, dependencies first.
+pub fn update_dependents(shapes: &mut Shapes) -> Result<(), Box<dyn Error>> {
+    fn visit(
+        name: &str,
+        shapes: &Shapes,
+        visiting: &mut HashSet<String>,
+        order: &mut Vec<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        if order.iter().any(|done| done == name) {
+            return Ok(());
+        }
+        if !visiting.insert(name.to_string()) {
+            let err_msg = format!("Shape {:?} depends on itself.", name);
+            return Err(ClevisError::Validation(err_msg).into());
+        }
+        if let Some(shape) = shapes.get(name) {
+            for dep in shape.dependencies() {
+                visit(&dep, shapes, visiting, order)?;
+            }
+        }
+        visiting.remove(name);
+        order.push(name.to_string());
+        Ok(())
+    }
+
+    let mut names: Vec<&String> = shapes
+        .iter()
+        .filter(|(_, shape)| !shape.dependencies().is_empty())
+        .map(|(name, _)| name)
+        .collect();
+    names.sort();
+
+    let mut order = Vec::new();
+    let mut visiting = HashSet::new();
+    for name in names {
+        visit(name, shapes, &mut visiting, &mut order)?;
+    }
+
+    for name in order {
+        if let Some((mut shape, rank)) = shapes.take(&name) {
+            let res = shape.update(shapes);
+            shapes.insert_at(name, shape, rank);
+            res?;
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
 pub struct Point {
     pub x: DataType,
@@ -25,6 +429,42 @@ impl Shape for Point {
     fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
         render.draw_point(self)
     }
+    fn kind(&self) -> &'static str {
+        "point"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![("x", self.x as f64), ("y", self.y as f64)]
+    }
+    fn center(&self) -> Point {
+        *self
+    }
+    fn as_point(&self) -> Option<&Point> {
+        Some(self)
+    }
+    fn outline(&self) -> Vec<Point> {
+        vec![*self]
+    }
+    fn offset(&self, distance: DataType, _round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        if distance <= 0 {
+            return Err(ClevisError::validation(COLLAPSED).into());
+        }
+        Ok(Box::new(Circle {
+            center: *self,
+            radius: distance,
+        }))
+    }
+    fn reflect(&mut self, axis: &Line) {
+        let (ax, ay) = (axis.0.x as f64, axis.0.y as f64);
+        let dx = (axis.1.x - axis.0.x) as f64;
+        let dy = (axis.1.y - axis.0.y) as f64;
+        let len2 = dx * dx + dy * dy;
+        if len2 == 0.0 {
+            return;
+        }
+        let t = ((self.x as f64 - ax) * dx + (self.y as f64 - ay) * dy) / len2;
+        self.x = round(2.0 * (ax + t * dx) - self.x as f64);
+        self.y = round(2.0 * (ay + t * dy) - self.y as f64);
+    }
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
@@ -32,14 +472,142 @@ pub struct Rectangle {
     pub corner: Point,
     pub w: DataType,
     pub h: DataType,
+    /// Radius of the rounded corners, 0 for sharp corners.
+    pub radius: DataType,
 }
 impl Shape for Rectangle {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        check_not_negative("corner radius", self.radius)
+    }
     fn move_by(&mut self, x: DataType, y: DataType) {
         self.corner.move_by(x, y);
     }
     fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
         render.draw_rectangle(self)
     }
+    fn kind(&self) -> &'static str {
+        "rectangle"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("x", self.corner.x as f64),
+            ("y", self.corner.y as f64),
+            ("w", self.w as f64),
+            ("h", self.h as f64),
+            ("radius", self.radius as f64),
+        ]
+    }
+    fn center(&self) -> Point {
+        Point {
+            x: self.corner.x + self.w / 2,
+            y: self.corner.y + self.h / 2,
+        }
+    }
+    fn reflect(&mut self, axis: &Line) {
+        let mut corners = self.corners();
+        for corner in corners.iter_mut() {
+            corner.reflect(axis);
+        }
+        *self = Rectangle {
+            radius: self.radius,
+            ..bounding_box(&corners)
+        };
+    }
+    fn mirrored(&self, axis: &Line) -> Box<dyn Shape> {
+        if keeps_alignment(axis) {
+            let mut rectangle = *self;
+            rectangle.reflect(axis);
+            return Box::new(rectangle);
+        }
+        let mut vertices = if self.radius > 0 {
+            rounded_rectangle_path(self)
+        } else {
+            self.corners().to_vec()
+        };
+        for vertex in vertices.iter_mut() {
+            vertex.reflect(axis);
+        }
+        Box::new(Polygon { vertices })
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        let [a, b, c, d] = self.corners();
+        [Line(a, b), Line(b, c), Line(c, d), Line(d, a)]
+            .iter()
+            .flat_map(|edge| edge.crossings(line))
+            .collect()
+    }
+    fn outline(&self) -> Vec<Point> {
+        self.corners().to_vec()
+    }
+    fn offset(&self, distance: DataType, round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        Ok(Box::new(self.grown(distance, round)?))
+    }
+    fn anchor(&self, toward: Point) -> Point {
+        // intersect the ray from the center to `toward` with the border
+        let cx = self.corner.x as f64 + self.w as f64 / 2.0;
+        let cy = self.corner.y as f64 + self.h as f64 / 2.0;
+        let dx = toward.x as f64 - cx;
+        let dy = toward.y as f64 - cy;
+        let half_w = (self.w as f64 / 2.0).abs();
+        let half_h = (self.h as f64 / 2.0).abs();
+        let scale = match (dx == 0.0, dy == 0.0) {
+            (true, true) => 0.0,
+            (true, false) => half_h / dy.abs(),
+            (false, true) => half_w / dx.abs(),
+            (false, false) => (half_w / dx.abs()).min(half_h / dy.abs()),
+        };
+        Point {
+            x: round(cx + dx * scale),
+            y: round(cy + dy * scale),
+        }
+    }
+}
+
+impl Rectangle {
+    pub fn corners(&self) -> [Point; 4] {
+        let Point { x, y } = self.corner;
+        [
+            Point { x, y },
+            Point { x: x + self.w, y },
+            Point {
+                x: x + self.w,
+                y: y + self.h,
+            },
+            Point { x, y: y + self.h },
+        ]
+    }
+
+    /// The rectangle moved `distance` outward on every side. Rounded corners
+    /// stay rounded, sharp ones are rounded too if `round` is set.
+    pub fn grown(&self, distance: DataType, round: bool) -> Result<Rectangle, Box<dyn Error>> {
+        let rect = self.normalized();
+        let (w, h) = (rect.w + 2 * distance, rect.h + 2 * distance);
+        if w <= 0 || h <= 0 {
+            return Err(ClevisError::validation(COLLAPSED).into());
+        }
+        let radius = if round || rect.radius > 0 {
+            (rect.radius + distance).max(0)
+        } else {
+            0
+        };
+        Ok(Rectangle {
+            corner: Point {
+                x: rect.corner.x - distance,
+                y: rect.corner.y - distance,
+            },
+            w,
+            h,
+            radius,
+        })
+    }
+
+    /// The same rectangle with a non-negative width and height.
+    pub fn normalized(&self) -> Rectangle {
+        Rectangle {
+            radius: self.radius,
+            ..bounding_box(&self.corners())
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
@@ -52,6 +620,102 @@ impl Shape for Line {
     fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
         render.draw_line(self)
     }
+    fn kind(&self) -> &'static str {
+        "line"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("x1", self.0.x as f64),
+            ("y1", self.0.y as f64),
+            ("x2", self.1.x as f64),
+            ("y2", self.1.y as f64),
+            ("length", self.length()),
+        ]
+    }
+    fn center(&self) -> Point {
+        Point {
+            x: (self.0.x + self.1.x) / 2,
+            y: (self.0.y + self.1.y) / 2,
+        }
+    }
+    fn reflect(&mut self, axis: &Line) {
+        self.0.reflect(axis);
+        self.1.reflect(axis);
+    }
+    fn as_line(&self) -> Option<&Line> {
+        Some(self)
+    }
+    fn outline(&self) -> Vec<Point> {
+        vec![self.0, self.1]
+    }
+    fn offset(&self, distance: DataType, rounded: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        let len = self.length();
+        if distance <= 0 || len == 0.0 {
+            return Err(ClevisError::validation(COLLAPSED).into());
+        }
+        // normal to the line, `distance` long
+        let nx = -((self.1.y - self.0.y) as f64) / len * distance as f64;
+        let ny = (self.1.x - self.0.x) as f64 / len * distance as f64;
+        if rounded {
+            // half circles around both ends
+            let (d, angle) = (distance as f64, ny.atan2(nx));
+            let pi = std::f64::consts::PI;
+            let mut vertices = arc(self.1, d, angle, -pi);
+            vertices.extend(arc(self.0, d, angle + pi, -pi));
+            return Ok(Box::new(Polygon { vertices }));
+        }
+        let shifted = |p: Point, sign: f64| Point {
+            x: round(p.x as f64 + sign * nx),
+            y: round(p.y as f64 + sign * ny),
+        };
+        Ok(Box::new(Polygon {
+            vertices: vec![
+                shifted(self.0, 1.0),
+                shifted(self.1, 1.0),
+                shifted(self.1, -1.0),
+                shifted(self.0, -1.0),
+            ],
+        }))
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        let (dx, dy) = ((line.1.x - line.0.x) as f64, (line.1.y - line.0.y) as f64);
+        let (ex, ey) = ((self.1.x - self.0.x) as f64, (self.1.y - self.0.y) as f64);
+        let denom = dx * ey - dy * ex;
+        if denom == 0.0 {
+            return Vec::new();
+        }
+        let (cx, cy) = ((self.0.x - line.0.x) as f64, (self.0.y - line.0.y) as f64);
+        let t = (cx * ey - cy * ex) / denom;
+        let s = (cx * dy - cy * dx) / denom;
+        if (0.0..=1.0).contains(&s) {
+            vec![t]
+        } else {
+            Vec::new()
+        }
+    }
+}
+impl Line {
+    pub fn length(&self) -> f64 {
+        ((self.1.x - self.0.x) as f64).hypot((self.1.y - self.0.y) as f64)
+    }
+
+    /// The point at parameter `t`, where `self.0` is at 0 and `self.1` at 1.
+    pub fn point_at(&self, t: f64) -> Point {
+        Point {
+            x: round(self.0.x as f64 + t * (self.1.x - self.0.x) as f64),
+            y: round(self.0.y as f64 + t * (self.1.y - self.0.y) as f64),
+        }
+    }
+
+    /// The parameter of the point on the line closest to `point`.
+    pub fn project(&self, point: Point) -> f64 {
+        let (dx, dy) = ((self.1.x - self.0.x) as f64, (self.1.y - self.0.y) as f64);
+        let len2 = dx * dx + dy * dy;
+        if len2 == 0.0 {
+            return 0.0;
+        }
+        ((point.x - self.0.x) as f64 * dx + (point.y - self.0.y) as f64 * dy) / len2
+    }
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
@@ -66,20 +730,737 @@ impl Shape for Circle {
     fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
         render.draw_circle(self)
     }
+    fn kind(&self) -> &'static str {
+        "circle"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("x", self.center.x as f64),
+            ("y", self.center.y as f64),
+            ("radius", self.radius as f64),
+        ]
+    }
+    fn center(&self) -> Point {
+        self.center
+    }
+    fn reflect(&mut self, axis: &Line) {
+        self.center.reflect(axis);
+    }
+    fn outline(&self) -> Vec<Point> {
+        ring_vertices(self.center, &[self.radius], CIRCLE_SEGMENTS, 0)
+    }
+    fn offset(&self, distance: DataType, _round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        let radius = self.radius + distance;
+        if radius <= 0 {
+            return Err(ClevisError::validation(COLLAPSED).into());
+        }
+        Ok(Box::new(Circle {
+            center: self.center,
+            radius,
+        }))
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        let (dx, dy) = ((line.1.x - line.0.x) as f64, (line.1.y - line.0.y) as f64);
+        let (fx, fy) = (
+            (line.0.x - self.center.x) as f64,
+            (line.0.y - self.center.y) as f64,
+        );
+        let a = dx * dx + dy * dy;
+        let b = 2.0 * (dx * fx + dy * fy);
+        let c = fx * fx + fy * fy - (self.radius as f64).powi(2);
+        let disc = b * b - 4.0 * a * c;
+        if a == 0.0 || disc < 0.0 {
+            return Vec::new();
+        }
+        let root = disc.sqrt();
+        vec![(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+    }
+    fn anchor(&self, toward: Point) -> Point {
+        let dx = (toward.x - self.center.x) as f64;
+        let dy = (toward.y - self.center.y) as f64;
+        let len = dx.hypot(dy);
+        if len == 0.0 {
+            return self.center;
+        }
+        let r = self.radius as f64;
+        Point {
+            x: self.center.x + round(dx / len * r),
+            y: self.center.y + round(dy / len * r),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
 pub struct Square {
     pub corner: Point,
     pub side: DataType,
+    /// Radius of the rounded corners, 0 for sharp corners.
+    pub radius: DataType,
 }
 impl Shape for Square {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        check_not_negative("corner radius", self.radius)
+    }
     fn move_by(&mut self, x: DataType, y: DataType) {
         self.corner.move_by(x, y);
     }
     fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
         render.draw_square(self)
     }
+    fn kind(&self) -> &'static str {
+        "square"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("x", self.corner.x as f64),
+            ("y", self.corner.y as f64),
+            ("side", self.side as f64),
+            ("radius", self.radius as f64),
+        ]
+    }
+    fn center(&self) -> Point {
+        self.as_rectangle().center()
+    }
+    fn reflect(&mut self, axis: &Line) {
+        let mut rectangle = self.as_rectangle();
+        rectangle.reflect(axis);
+        self.corner = rectangle.corner;
+        self.side = rectangle.w.max(rectangle.h);
+    }
+    fn mirrored(&self, axis: &Line) -> Box<dyn Shape> {
+        if keeps_alignment(axis) {
+            let mut square = *self;
+            square.reflect(axis);
+            return Box::new(square);
+        }
+        self.as_rectangle().mirrored(axis)
+    }
+    fn anchor(&self, toward: Point) -> Point {
+        self.as_rectangle().anchor(toward)
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        self.as_rectangle().crossings(line)
+    }
+    fn outline(&self) -> Vec<Point> {
+        self.as_rectangle().outline()
+    }
+    fn offset(&self, distance: DataType, round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        let rect = self.as_rectangle().grown(distance, round)?;
+        Ok(Box::new(Square {
+            corner: rect.corner,
+            side: rect.w,
+            radius: rect.radius,
+        }))
+    }
+}
+impl Square {
+    pub fn as_rectangle(&self) -> Rectangle {
+        Rectangle {
+            corner: self.corner,
+            w: self.side,
+            h: self.side,
+            radius: self.radius,
+        }
+    }
+}
+
+/// `count` vertices evenly spaced around `center`, the first one `rotation`
+/// degrees clockwise from straight up. Their distances to the center cycle
+/// through `radii`.
+fn ring_vertices(center: Point, radii: &[DataType], count: u32, rotation: DataType) -> Vec<Point> {
+    let step = 2.0 * std::f64::consts::PI / count as f64;
+    (0..count)
+        .map(|i| {
+            let r = radii[i as usize % radii.len()] as f64;
+            let angle = (rotation as f64 - 90.0).to_radians() + step * i as f64;
+            Point {
+                x: center.x + round(r * angle.cos()),
+                y: center.y + round(r * angle.sin()),
+            }
+        })
+        .collect()
+}
+
+fn polygon_crossings(vertices: &[Point], line: &Line) -> Vec<f64> {
+    (0..vertices.len())
+        .flat_map(|i| Line(vertices[i], vertices[(i + 1) % vertices.len()]).crossings(line))
+        .collect()
+}
+
+/// The rotation, in degrees, of a shape mirrored across `axis`.
+fn reflect_rotation(rotation: DataType, axis: &Line) -> DataType {
+    let (dx, dy) = ((axis.1.x - axis.0.x) as f64, (axis.1.y - axis.0.y) as f64);
+    let theta = dy.atan2(dx).to_degrees();
+    // the first vertex points at rotation - 90 degrees
+    let mirrored = 2.0 * theta - (rotation as f64 - 90.0) + 90.0;
+    round(mirrored.rem_euclid(360.0))
+}
+
+/// A regular polygon, kept as its parameters rather than its vertices.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct RegularPolygon {
+    pub center: Point,
+    pub radius: DataType,
+    pub sides: u32,
+    /// Degrees clockwise from a vertex pointing straight up.
+    pub rotation: DataType,
+}
+impl RegularPolygon {
+    pub fn vertices(&self) -> Vec<Point> {
+        let sides = self.sides.min(MAX_SIDES);
+        ring_vertices(self.center, &[self.radius], sides, self.rotation)
+    }
+}
+impl Shape for RegularPolygon {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.sides < 3 {
+            return Err(ClevisError::validation("A polygon needs at least 3 sides.").into());
+        }
+        if self.sides > MAX_SIDES {
+            let err_msg = format!("A polygon can have at most {} sides.", MAX_SIDES);
+            return Err(ClevisError::Validation(err_msg).into());
+        }
+        check_not_negative("radius", self.radius)
+    }
+    fn move_by(&mut self, x: DataType, y: DataType) {
+        self.center.move_by(x, y);
+    }
+    fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        render.draw_regular_polygon(self)
+    }
+    fn kind(&self) -> &'static str {
+        "ngon"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("x", self.center.x as f64),
+            ("y", self.center.y as f64),
+            ("radius", self.radius as f64),
+            ("sides", self.sides as f64),
+            ("rotation", self.rotation as f64),
+        ]
+    }
+    fn center(&self) -> Point {
+        self.center
+    }
+    fn reflect(&mut self, axis: &Line) {
+        self.center.reflect(axis);
+        self.rotation = reflect_rotation(self.rotation, axis);
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        polygon_crossings(&self.vertices(), line)
+    }
+    fn outline(&self) -> Vec<Point> {
+        self.vertices()
+    }
+    fn offset(&self, distance: DataType, rounded: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        if self.sides < 3 {
+            return Err(ClevisError::validation(COLLAPSED).into());
+        }
+        if rounded && distance > 0 {
+            return offset_polygon(&self.vertices(), distance, true);
+        }
+        // the sides move by `distance`, the vertices further out
+        let apothem = (std::f64::consts::PI / self.sides as f64).cos();
+        let radius = self.radius + round(distance as f64 / apothem);
+        if radius <= 0 {
+            return Err(ClevisError::validation(COLLAPSED).into());
+        }
+        Ok(Box::new(RegularPolygon { radius, ..*self }))
+    }
+}
+
+/// A star with `points` outer points, kept as its parameters.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Star {
+    pub center: Point,
+    pub outer: DataType,
+    pub inner: DataType,
+    pub points: u32,
+    /// Degrees clockwise from an outer point pointing straight up.
+    pub rotation: DataType,
+}
+impl Star {
+    pub fn vertices(&self) -> Vec<Point> {
+        // an outer and an inner vertex for each point
+        let count = self.points.min(MAX_SIDES / 2) * 2;
+        ring_vertices(self.center, &[self.outer, self.inner], count, self.rotation)
+    }
+}
+impl Shape for Star {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.points < 2 {
+            return Err(ClevisError::validation("A star needs at least 2 points.").into());
+        }
+        if self.points > MAX_SIDES / 2 {
+            let err_msg = format!("A star can have at most {} points.", MAX_SIDES / 2);
+            return Err(ClevisError::Validation(err_msg).into());
+        }
+        check_not_negative("outer radius", self.outer)?;
+        check_not_negative("inner radius", self.inner)
+    }
+    fn move_by(&mut self, x: DataType, y: DataType) {
+        self.center.move_by(x, y);
+    }
+    fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        render.draw_star(self)
+    }
+    fn kind(&self) -> &'static str {
+        "star"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("x", self.center.x as f64),
+            ("y", self.center.y as f64),
+            ("outer", self.outer as f64),
+            ("inner", self.inner as f64),
+            ("points", self.points as f64),
+            ("rotation", self.rotation as f64),
+        ]
+    }
+    fn center(&self) -> Point {
+        self.center
+    }
+    fn reflect(&mut self, axis: &Line) {
+        self.center.reflect(axis);
+        self.rotation = reflect_rotation(self.rotation, axis);
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        polygon_crossings(&self.vertices(), line)
+    }
+    fn outline(&self) -> Vec<Point> {
+        self.vertices()
+    }
+    fn offset(&self, distance: DataType, round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        offset_polygon(&self.vertices(), distance, round)
+    }
+}
+
+/// A closed polygon through arbitrary vertices.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Polygon {
+    pub vertices: Vec<Point>,
+}
+impl Shape for Polygon {
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.vertices.len() < 3 {
+            return Err(ClevisError::validation("A polygon needs at least 3 vertices.").into());
+        }
+        Ok(())
+    }
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.vertices.len() * std::mem::size_of::<Point>()
+    }
+    fn move_by(&mut self, x: DataType, y: DataType) {
+        for vertex in self.vertices.iter_mut() {
+            vertex.move_by(x, y);
+        }
+    }
+    fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        render.draw_polygon(&self.vertices)
+    }
+    fn kind(&self) -> &'static str {
+        "polygon"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![("vertices", self.vertices.len() as f64)]
+    }
+    fn center(&self) -> Point {
+        bounding_box(&self.vertices).center()
+    }
+    fn reflect(&mut self, axis: &Line) {
+        for vertex in self.vertices.iter_mut() {
+            vertex.reflect(axis);
+        }
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        polygon_crossings(&self.vertices, line)
+    }
+    fn outline(&self) -> Vec<Point> {
+        self.vertices.clone()
+    }
+    fn offset(&self, distance: DataType, round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        offset_polygon(&self.vertices, distance, round)
+    }
+}
+
+/// Points on the arc around `center` at `radius`, from the angle `from`
+/// turning by `sweep` radians, both ends included.
+fn arc(center: Point, radius: f64, from: f64, sweep: f64) -> Vec<Point> {
+    let full = 2.0 * std::f64::consts::PI;
+    let segments = ((sweep.abs() / full * CIRCLE_SEGMENTS as f64).ceil() as u32).max(1);
+    (0..=segments)
+        .map(|i| {
+            let angle = from + sweep * i as f64 / segments as f64;
+            Point {
+                x: round(center.x as f64 + radius * angle.cos()),
+                y: round(center.y as f64 + radius * angle.sin()),
+            }
+        })
+        .collect()
+}
+
+/// Move every edge of a polygon `distance` outward. Where the moved edges
+/// part, they are joined by an arc if `rounded`, else at a sharp mitred
+/// corner.
+fn offset_polygon(
+    vertices: &[Point],
+    distance: DataType,
+    rounded: bool,
+) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+    let mut ring = vertices.to_vec();
+    ring.dedup();
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    let orientation = double_area(&ring).signum() as f64;
+    if ring.len() < 3 || orientation == 0.0 {
+        return Err(ClevisError::validation(COLLAPSED).into());
+    }
+
+    let n = ring.len();
+    let normal = |a: Point, b: Point| {
+        let (dx, dy) = ((b.x - a.x) as f64, (b.y - a.y) as f64);
+        let len = dx.hypot(dy);
+        (orientation * dy / len, -orientation * dx / len)
+    };
+    let d = distance as f64;
+    // the points replacing each vertex, one for a mitre, more for an arc
+    let corners: Vec<Vec<Point>> = (0..n)
+        .map(|i| {
+            let (prev, here, next) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+            let (ax, ay) = normal(prev, here);
+            let (bx, by) = normal(here, next);
+            // the turn from one normal to the next, parting edges turn with `d`
+            let sweep = (ax * by - ay * bx).atan2(ax * bx + ay * by);
+            if rounded && sweep * d * orientation > 0.0 {
+                return arc(here, d, ay.atan2(ax), sweep);
+            }
+            let dot = 1.0 + ax * bx + ay * by;
+            let (mx, my) = if dot < 1e-9 {
+                (ax, ay)
+            } else {
+                ((ax + bx) / dot, (ay + by) / dot)
+            };
+            vec![Point {
+                x: round(here.x as f64 + d * mx),
+                y: round(here.y as f64 + d * my),
+            }]
+        })
+        .collect();
+    // an inset that went too far turns some edges around
+    let reversed = (0..n).any(|i| {
+        let (a, b) = (ring[i], ring[(i + 1) % n]);
+        let (c, d) = (corners[i][corners[i].len() - 1], corners[(i + 1) % n][0]);
+        (b.x - a.x) as i64 * (d.x - c.x) as i64 + (b.y - a.y) as i64 * (d.y - c.y) as i64 <= 0
+    });
+    if reversed {
+        return Err(ClevisError::validation(COLLAPSED).into());
+    }
+    let mut vertices: Vec<Point> = corners.into_iter().flatten().collect();
+    vertices.dedup();
+    if vertices.len() > 1 && vertices.first() == vertices.last() {
+        vertices.pop();
+    }
+    Ok(Box::new(Polygon { vertices }))
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `c`; positive when the
+/// turn at `b` is counter-clockwise in a y-up frame.
+fn cross(a: Point, b: Point, c: Point) -> i64 {
+    (b.x - a.x) as i64 * (c.y - a.y) as i64 - (b.y - a.y) as i64 * (c.x - a.x) as i64
+}
+
+/// Twice the signed area enclosed by `vertices`.
+fn double_area(vertices: &[Point]) -> i64 {
+    (0..vertices.len())
+        .map(|i| {
+            let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+            a.x as i64 * b.y as i64 - b.x as i64 * a.y as i64
+        })
+        .sum()
+}
+
+/// The smallest convex polygon containing all `points`, without collinear
+/// vertices.
+pub fn convex_hull(points: &[Point]) -> Vec<Point> {
+    let mut points = points.to_vec();
+    points.sort_by_key(|p| (p.x, p.y));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    // Andrew's monotone chain: the lower half, then the upper half
+    let mut hull: Vec<Point> = Vec::new();
+    for pass in 0..2 {
+        let start = hull.len();
+        for &p in points.iter() {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        hull.pop();
+        if pass == 0 {
+            points.reverse();
+        }
+    }
+    hull
+}
+
+/// The center of mass of the area enclosed by `vertices`, or the average of
+/// the vertices when they enclose no area.
+pub fn centroid(vertices: &[Point]) -> Point {
+    if vertices.is_empty() {
+        return Point::default();
+    }
+    let area = double_area(vertices);
+    if area == 0 {
+        let n = vertices.len() as f64;
+        return Point {
+            x: round(vertices.iter().map(|p| p.x as f64).sum::<f64>() / n),
+            y: round(vertices.iter().map(|p| p.y as f64).sum::<f64>() / n),
+        };
+    }
+    let (mut cx, mut cy) = (0.0, 0.0);
+    for i in 0..vertices.len() {
+        let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+        let w = (a.x as i64 * b.y as i64 - b.x as i64 * a.y as i64) as f64;
+        cx += (a.x + b.x) as f64 * w;
+        cy += (a.y + b.y) as f64 * w;
+    }
+    let scale = 3.0 * area as f64;
+    Point {
+        x: round(cx / scale),
+        y: round(cy / scale),
+    }
+}
+
+/// Split a simple polygon into triangles by ear clipping.
+pub fn triangulate(vertices: &[Point]) -> Result<Vec<[Point; 3]>, Box<dyn Error>> {
+    let mut ring = vertices.to_vec();
+    ring.dedup();
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    let orientation = double_area(&ring).signum();
+    if ring.len() < 3 || orientation == 0 {
+        let err_msg = "A polygon needs at least 3 vertices enclosing an area.";
+        return Err(ClevisError::validation(err_msg).into());
+    }
+
+    let inside = |p: Point, [a, b, c]: [Point; 3]| {
+        p != a
+            && p != b
+            && p != c
+            && cross(a, b, p) * orientation >= 0
+            && cross(b, c, p) * orientation >= 0
+            && cross(c, a, p) * orientation >= 0
+    };
+    let mut triangles = Vec::new();
+    while ring.len() > 3 {
+        let n = ring.len();
+        let ear = (0..n).find_map(|i| {
+            let triangle = [ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]];
+            let turn = cross(triangle[0], triangle[1], triangle[2]) * orientation;
+            if turn == 0 {
+                // a straight vertex adds nothing, drop it without a triangle
+                return Some((i, None));
+            }
+            if turn < 0 || ring.iter().any(|&p| inside(p, triangle)) {
+                return None;
+            }
+            Some((i, Some(triangle)))
+        });
+        let (i, triangle) = ear.ok_or(ClevisError::validation("The polygon intersects itself."))?;
+        triangles.extend(triangle);
+        ring.remove(i);
+    }
+    if cross(ring[0], ring[1], ring[2]) != 0 {
+        triangles.push([ring[0], ring[1], ring[2]]);
+    }
+    Ok(triangles)
+}
+
+/// A line whose ends stay attached to two other shapes.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Connector {
+    pub from: String,
+    pub to: String,
+    pub line: Line,
+}
+impl Connector {
+    pub fn new(from: String, to: String) -> Self {
+        Self {
+            from,
+            to,
+            line: Line::default(),
+        }
+    }
+}
+impl Shape for Connector {
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.from.len() + self.to.len()
+    }
+    fn move_by(&mut self, _x: DataType, _y: DataType) {
+        // the ends follow the connected shapes
+    }
+    fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        render.draw_line(&self.line)
+    }
+    fn kind(&self) -> &'static str {
+        "connector"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        self.line.params()
+    }
+    fn center(&self) -> Point {
+        self.line.center()
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        self.line.crossings(line)
+    }
+    fn outline(&self) -> Vec<Point> {
+        self.line.outline()
+    }
+    fn offset(&self, distance: DataType, round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        self.line.offset(distance, round)
+    }
+    fn reflect(&mut self, _axis: &Line) {
+        // the ends follow the connected shapes
+    }
+    fn dependencies(&self) -> Vec<String> {
+        vec![self.from.clone(), self.to.clone()]
+    }
+    fn update(&mut self, shapes: &Shapes) -> Result<(), Box<dyn Error>> {
+        if self.from == self.to {
+            let err_msg = format!("Can not connect shape {:?} to itself.", self.from);
+            return Err(ClevisError::Validation(err_msg).into());
+        }
+        let from = get_dependency(shapes, &self.from)?;
+        let to = get_dependency(shapes, &self.to)?;
+        self.line = Line(from.anchor(to.center()), to.anchor(from.center()));
+        Ok(())
+    }
+}
+
+/// A line between two named points.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct LinkedLine {
+    pub from: String,
+    pub to: String,
+    pub line: Line,
+}
+impl LinkedLine {
+    pub fn new(from: String, to: String) -> Self {
+        Self {
+            from,
+            to,
+            line: Line::default(),
+        }
+    }
+}
+impl Shape for LinkedLine {
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.from.len() + self.to.len()
+    }
+    fn move_by(&mut self, _x: DataType, _y: DataType) {
+        // the ends follow the points
+    }
+    fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        render.draw_line(&self.line)
+    }
+    fn kind(&self) -> &'static str {
+        "line"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        self.line.params()
+    }
+    fn center(&self) -> Point {
+        self.line.center()
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        self.line.crossings(line)
+    }
+    fn outline(&self) -> Vec<Point> {
+        self.line.outline()
+    }
+    fn offset(&self, distance: DataType, round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        self.line.offset(distance, round)
+    }
+    fn reflect(&mut self, _axis: &Line) {
+        // the ends follow the points
+    }
+    fn dependencies(&self) -> Vec<String> {
+        vec![self.from.clone(), self.to.clone()]
+    }
+    fn update(&mut self, shapes: &Shapes) -> Result<(), Box<dyn Error>> {
+        self.line = Line(get_point(shapes, &self.from)?, get_point(shapes, &self.to)?);
+        Ok(())
+    }
+}
+
+/// A circle around a named point.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct LinkedCircle {
+    pub center: String,
+    pub circle: Circle,
+}
+impl LinkedCircle {
+    pub fn new(center: String, radius: DataType) -> Self {
+        Self {
+            center,
+            circle: Circle {
+                center: Point::default(),
+                radius,
+            },
+        }
+    }
+}
+impl Shape for LinkedCircle {
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.center.len()
+    }
+    fn move_by(&mut self, _x: DataType, _y: DataType) {
+        // the center follows the point
+    }
+    fn draw_on(&self, render: &mut dyn Renderer) -> Result<(), Box<dyn Error>> {
+        render.draw_circle(&self.circle)
+    }
+    fn kind(&self) -> &'static str {
+        "circle"
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        self.circle.params()
+    }
+    fn center(&self) -> Point {
+        self.circle.center
+    }
+    fn anchor(&self, toward: Point) -> Point {
+        self.circle.anchor(toward)
+    }
+    fn crossings(&self, line: &Line) -> Vec<f64> {
+        self.circle.crossings(line)
+    }
+    fn outline(&self) -> Vec<Point> {
+        self.circle.outline()
+    }
+    fn offset(&self, distance: DataType, round: bool) -> Result<Box<dyn Shape>, Box<dyn Error>> {
+        self.circle.offset(distance, round)
+    }
+    fn reflect(&mut self, _axis: &Line) {
+        // the center follows the point
+    }
+    fn dependencies(&self) -> Vec<String> {
+        vec![self.center.clone()]
+    }
+    fn update(&mut self, shapes: &Shapes) -> Result<(), Box<dyn Error>> {
+        self.circle.center = get_point(shapes, &self.center)?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -94,7 +1475,7 @@ pub mod tests {
         Circle: Shape,
         Square: Shape,
     {
-        let shapes: Shapes = HashMap::from([
+        Shapes::from([
             (
                 std::any::type_name::<Point>().into(),
                 Box::new(Point::default()) as Box<dyn Shape>,
@@ -115,9 +1496,7 @@ pub mod tests {
                 std::any::type_name::<Square>().into(),
                 Box::new(Square::default()) as Box<dyn Shape>,
             ),
-        ]);
-
-        shapes
+        ])
     }
 
     #[test]
@@ -133,7 +1512,8 @@ pub mod tests {
             Rectangle {
                 corner: Point { x: 2, y: 3 },
                 w: rect.w,
-                h: rect.h
+                h: rect.h,
+                radius: 0,
             }
         );
 
@@ -157,6 +1537,7 @@ pub mod tests {
         let mut square = Square {
             corner: Point { x: 1, y: 2 },
             side: 5,
+            radius: 0,
         };
         square.move_by(2, 3);
         assert_eq!(
@@ -164,7 +1545,209 @@ pub mod tests {
             Square {
                 corner: Point { x: 3, y: 5 },
                 side: square.side,
+                radius: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_reflect() {
+        let vertical = Line(Point { x: 5, y: 0 }, Point { x: 5, y: 1 });
+
+        let mut point = Point { x: 1, y: 2 };
+        point.reflect(&vertical);
+        assert_eq!(point, Point { x: 9, y: 2 });
+
+        let mut circle = Circle {
+            center: Point { x: 1, y: 2 },
+            radius: 3,
+        };
+        circle.reflect(&Line(Point { x: 0, y: 0 }, Point { x: 1, y: 1 }));
+        assert_eq!(circle.center, Point { x: 2, y: 1 });
+
+        let mut rect = Rectangle {
+            corner: Point { x: 0, y: 0 },
+            w: 2,
+            h: -3,
+            radius: 0,
+        };
+        rect.reflect(&vertical);
+        assert_eq!(
+            rect,
+            Rectangle {
+                corner: Point { x: 8, y: -3 },
+                w: 2,
+                h: 3,
+                radius: 0,
             }
         );
     }
+
+    #[test]
+    fn test_parametric_vertices() {
+        let square = RegularPolygon {
+            center: Point { x: 0, y: 0 },
+            radius: 10,
+            sides: 4,
+            rotation: 0,
+        };
+        assert_eq!(
+            square.vertices(),
+            vec![
+                Point { x: 0, y: -10 },
+                Point { x: 10, y: 0 },
+                Point { x: 0, y: 10 },
+                Point { x: -10, y: 0 },
+            ]
+        );
+
+        let star = Star {
+            center: Point { x: 0, y: 0 },
+            outer: 10,
+            inner: 5,
+            points: 2,
+            rotation: 0,
+        };
+        assert_eq!(
+            star.vertices(),
+            vec![
+                Point { x: 0, y: -10 },
+                Point { x: 5, y: 0 },
+                Point { x: 0, y: 10 },
+                Point { x: -5, y: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_offset_polygon() {
+        let square = Polygon {
+            vertices: vec![
+                Point { x: 0, y: 0 },
+                Point { x: 4, y: 0 },
+                Point { x: 4, y: 4 },
+                Point { x: 0, y: 4 },
+            ],
+        };
+        assert_eq!(
+            square.offset(1, false).unwrap().outline(),
+            vec![
+                Point { x: -1, y: -1 },
+                Point { x: 5, y: -1 },
+                Point { x: 5, y: 5 },
+                Point { x: -1, y: 5 },
+            ]
+        );
+        assert_eq!(
+            square.offset(-1, false).unwrap().outline(),
+            vec![
+                Point { x: 1, y: 1 },
+                Point { x: 3, y: 1 },
+                Point { x: 3, y: 3 },
+                Point { x: 1, y: 3 },
+            ]
+        );
+        assert!(square.offset(-3, false).is_err());
+
+        // rounded corners stay 4 away from the square, in either orientation
+        let distance = |p: &Point| {
+            let dx = (-p.x).max(p.x - 4).max(0) as f64;
+            let dy = (-p.y).max(p.y - 4).max(0) as f64;
+            dx.hypot(dy)
+        };
+        let mut clockwise = square.clone();
+        clockwise.vertices.reverse();
+        for polygon in [&square, &clockwise] {
+            let outline = polygon.offset(4, true).unwrap().outline();
+            assert_eq!(outline.len(), 36);
+            assert!(outline.iter().all(|p| (distance(p) - 4.0).abs() < 0.75));
+        }
+        // an inset only rounds the inner corner of an L
+        let l = Polygon {
+            vertices: vec![
+                Point { x: 0, y: 0 },
+                Point { x: 8, y: 0 },
+                Point { x: 8, y: 4 },
+                Point { x: 4, y: 4 },
+                Point { x: 4, y: 8 },
+                Point { x: 0, y: 8 },
+            ],
+        };
+        assert_eq!(
+            l.offset(-1, true).unwrap().outline(),
+            vec![
+                Point { x: 1, y: 1 },
+                Point { x: 7, y: 1 },
+                Point { x: 7, y: 3 },
+                Point { x: 4, y: 3 },
+                Point { x: 3, y: 3 },
+                Point { x: 3, y: 4 },
+                Point { x: 3, y: 7 },
+                Point { x: 1, y: 7 },
+            ]
+        );
+        let line = Line(Point { x: 0, y: 0 }, Point { x: 10, y: 0 });
+        let outline = line.offset(2, true).unwrap().outline();
+        assert_eq!(outline[0], Point { x: 10, y: 2 });
+        assert!(outline.contains(&Point { x: 12, y: 0 }));
+        assert!(outline.contains(&Point { x: -2, y: 0 }));
+    }
+
+    #[test]
+    fn test_connector() {
+        let mut shapes = Shapes::from([
+            (
+                "a".to_string(),
+                Box::new(Rectangle {
+                    corner: Point { x: 0, y: 0 },
+                    w: 10,
+                    h: 10,
+                    radius: 0,
+                }) as Box<dyn Shape>,
+            ),
+            (
+                "b".to_string(),
+                Box::new(Circle {
+                    center: Point { x: 30, y: 5 },
+                    radius: 5,
+                }) as Box<dyn Shape>,
+            ),
+        ]);
+
+        let mut connector = Connector::new("a".into(), "b".into());
+        connector.update(&shapes).unwrap();
+        assert_eq!(
+            connector.line,
+            Line(Point { x: 10, y: 5 }, Point { x: 25, y: 5 })
+        );
+        shapes.insert("c".into(), Box::new(connector));
+        assert_eq!(dependents_of(&shapes, "a"), vec!["c".to_string()]);
+
+        shapes.get_mut("b").unwrap().move_by(-25, 30);
+        update_dependents(&mut shapes).unwrap();
+        assert_eq!(
+            format!("{:?}", shapes["c"]),
+            format!(
+                "{:?}",
+                Connector {
+                    from: "a".into(),
+                    to: "b".into(),
+                    line: Line(Point { x: 5, y: 10 }, Point { x: 5, y: 30 }),
+                }
+            )
+        );
+
+        let mut loop_connector = Connector::new("a".into(), "a".into());
+        assert!(loop_connector.update(&shapes).is_err());
+        let mut not_points = LinkedLine::new("a".into(), "b".into());
+        assert_eq!(
+            not_points.update(&shapes).unwrap_err().to_string(),
+            r#"Shape "a" is not a point."#
+        );
+        let mut dangling = Connector::new("a".into(), "nothing".into());
+        assert_eq!(
+            dangling.update(&shapes).unwrap_err().to_string(),
+            r#"Shape "nothing" is not found."#
+        );
+    }
 }